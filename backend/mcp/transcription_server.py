@@ -16,7 +16,8 @@ import os
 
 # Ensure generated stubs are on PYTHONPATH and generate if missing
 PROJECT_ROOT = os.path.abspath(os.path.join(os.path.dirname(__file__), '..', '..'))
-GEN_DIR = os.path.join(PROJECT_ROOT, 'backend', 'generated')
+# The desktop app points this at a writable dir; the bundled backend may be read-only
+GEN_DIR = os.environ.get('AURALINK_STUBS_DIR') or os.path.join(PROJECT_ROOT, 'backend', 'generated')
 if GEN_DIR not in sys.path:
     sys.path.insert(0, GEN_DIR)
 
@@ -195,14 +196,96 @@ class TranscriptionService(BaseServicer):
             context.set_details(str(e))
             return auralink_pb2.TranscribeResponse(text="", language="unknown", confidence=0.0)
 
+    def StreamTranscription(self, request, context):
+        """gRPC handler for StreamTranscription: yields segments as each 30s window finishes"""
+        import tempfile
+        temp_path = None
+        try:
+            format_type = request.format or "mp4"
+            temp_fd, temp_path = tempfile.mkstemp(suffix=f".{format_type}")
+            with os.fdopen(temp_fd, 'wb') as f:
+                f.write(request.audio_data)
+
+            # whisper.load_audio decodes through ffmpeg into 16kHz mono float32
+            audio = whisper.load_audio(temp_path)
+            sample_rate = whisper.audio.SAMPLE_RATE
+            window = sample_rate * 30
+            language = ""
+            for offset in range(0, len(audio), window):
+                result = self.whisper_model.transcribe(audio[offset:offset + window])
+                # Keep the language detected on the first window that reported one
+                language = language or result.get("language", "")
+                base = offset / sample_rate
+                for seg in result.get("segments", []):
+                    yield auralink_pb2.TranscribeChunk(
+                        text=seg.get("text", ""),
+                        is_final=False,
+                        start_time=base + seg.get("start", 0.0),
+                        end_time=base + seg.get("end", 0.0),
+                    )
+            yield auralink_pb2.TranscribeChunk(text="", is_final=True, language=language)
+        except Exception as e:
+            context.set_code(grpc.StatusCode.INTERNAL)
+            context.set_details(str(e))
+        finally:
+            if temp_path and os.path.exists(temp_path):
+                os.unlink(temp_path)
+
+    def TranscribeUpload(self, request_iterator, context):
+        """gRPC handler for TranscribeUpload: reassembles streamed chunks on disk, then transcribes"""
+        import tempfile
+        temp_path = None
+        try:
+            format_type = "mp4"
+            temp_fd, temp_path = tempfile.mkstemp(suffix=".upload")
+            with os.fdopen(temp_fd, 'wb') as f:
+                for i, chunk in enumerate(request_iterator):
+                    if i == 0 and chunk.format:
+                        format_type = chunk.format
+                    f.write(chunk.data)
+            print(f"[Transcription Agent] Received {os.path.getsize(temp_path)} bytes ({format_type}) via upload stream")
+
+            # whisper.load_audio decodes audio or video containers through ffmpeg
+            audio = whisper.load_audio(temp_path)
+            result = self.whisper_model.transcribe(audio)
+            segments = [
+                auralink_pb2.TimestampSegment(
+                    text=seg.get("text", ""),
+                    start_time=seg.get("start", 0.0),
+                    end_time=seg.get("end", 0.0)
+                )
+                for seg in result.get("segments", [])
+            ]
+            return auralink_pb2.TranscribeResponse(
+                text=result.get("text", ""),
+                segments=segments,
+                language=result.get("language", "unknown"),
+                confidence=0.95
+            )
+        except Exception as e:
+            context.set_code(grpc.StatusCode.INTERNAL)
+            context.set_details(str(e))
+            return auralink_pb2.TranscribeResponse(text="", language="unknown", confidence=0.0)
+        finally:
+            if temp_path and os.path.exists(temp_path):
+                os.unlink(temp_path)
+
+
+def max_message_bytes() -> int:
+    """Message size cap passed down by the desktop app, so both ends agree; 50 MB otherwise"""
+    try:
+        return int(os.environ.get("AURALINK_MAX_MESSAGE_BYTES", ""))
+    except ValueError:
+        return 50 * 1024 * 1024
+
 
 def serve(port: int = 50051, model_path: str = "base"):
     """Start gRPC server"""
     server = grpc.server(
         futures.ThreadPoolExecutor(max_workers=4),
         options=[
-            ('grpc.max_send_message_length', 50 * 1024 * 1024),
-            ('grpc.max_receive_message_length', 50 * 1024 * 1024),
+            ('grpc.max_send_message_length', max_message_bytes()),
+            ('grpc.max_receive_message_length', max_message_bytes()),
         ],
     )
     if not _GRPC_AVAILABLE or auralink_pb2_grpc is None:
@@ -217,6 +300,13 @@ def serve(port: int = 50051, model_path: str = "base"):
     print(f"[Transcription Agent] gRPC server started on port {port}")
     print(f"[Transcription Agent] Model '{model_path}' ready for requests")
     
+    # The desktop app sends SIGTERM on exit; let in-flight calls finish briefly
+    import signal
+    def _on_sigterm(signum, frame):
+        print("[Transcription Agent] SIGTERM received, shutting down...")
+        server.stop(grace=1.5)
+    signal.signal(signal.SIGTERM, _on_sigterm)
+
     try:
         server.wait_for_termination()
     except KeyboardInterrupt:
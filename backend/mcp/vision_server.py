@@ -16,7 +16,8 @@ import os
 
 # Ensure generated stubs are on PYTHONPATH and generate if missing
 PROJECT_ROOT = os.path.abspath(os.path.join(os.path.dirname(__file__), '..', '..'))
-GEN_DIR = os.path.join(PROJECT_ROOT, 'backend', 'generated')
+# The desktop app points this at a writable dir; the bundled backend may be read-only
+GEN_DIR = os.environ.get('AURALINK_STUBS_DIR') or os.path.join(PROJECT_ROOT, 'backend', 'generated')
 if GEN_DIR not in sys.path:
     sys.path.insert(0, GEN_DIR)
 
@@ -156,8 +157,9 @@ class VisionService(BaseServicer):
             with torch.no_grad():
                 outputs = self.object_detection_model(**inputs)
             
+            # Without target_sizes the boxes come back relative (0-1) and truncate to 0 as ints
             results = self.od_processor.post_process_object_detection(
-                outputs, threshold=0.5
+                outputs, threshold=0.5, target_sizes=[(image.height, image.width)]
             )[0]
             
             objects = []
@@ -185,7 +187,9 @@ class VisionService(BaseServicer):
             
             return auralink_pb2.ObjectDetectionResponse(
                 objects=objects,
-                caption=caption
+                caption=caption,
+                image_width=image.width,
+                image_height=image.height
             )
         except Exception as e:
             context.set_code(grpc.StatusCode.INTERNAL)
@@ -260,13 +264,21 @@ class VisionService(BaseServicer):
             return auralink_pb2.GraphIdentificationResponse(description="")
 
 
+def max_message_bytes() -> int:
+    """Message size cap passed down by the desktop app, so both ends agree; 50 MB otherwise"""
+    try:
+        return int(os.environ.get("AURALINK_MAX_MESSAGE_BYTES", ""))
+    except ValueError:
+        return 50 * 1024 * 1024
+
+
 def serve(port: int = 50051):
     """Start gRPC server"""
     server = grpc.server(
         futures.ThreadPoolExecutor(max_workers=4),
         options=[
-            ('grpc.max_send_message_length', 50 * 1024 * 1024),
-            ('grpc.max_receive_message_length', 50 * 1024 * 1024),
+            ('grpc.max_send_message_length', max_message_bytes()),
+            ('grpc.max_receive_message_length', max_message_bytes()),
         ],
     )
     if not _GRPC_AVAILABLE or auralink_pb2_grpc is None:
@@ -281,6 +293,13 @@ def serve(port: int = 50051):
     print(f"[Vision Agent] gRPC server started on port {port}")
     print(f"[Vision Agent] Models ready for requests")
     
+    # The desktop app sends SIGTERM on exit; let in-flight calls finish briefly
+    import signal
+    def _on_sigterm(signum, frame):
+        print("[Vision Agent] SIGTERM received, shutting down...")
+        server.stop(grace=1.5)
+    signal.signal(signal.SIGTERM, _on_sigterm)
+
     try:
         server.wait_for_termination()
     except KeyboardInterrupt:
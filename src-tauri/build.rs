@@ -1,6 +1,7 @@
 fn main() {
   tonic_build::configure()
-      .build_server(false) 
+      // Servers are only used by the tests, which stand in for the agents
+      .build_server(true)
       .build_client(true)
       .compile_protos(
           &["../proto/audio_service.proto"],
@@ -0,0 +1,654 @@
+use crate::grpc_client;
+use std::io::{BufRead, BufReader, Write};
+use std::net::{SocketAddr, TcpStream, ToSocketAddrs};
+use std::process::{Child, Command, Stdio};
+use std::sync::{Arc, Mutex, OnceLock};
+use std::time::{Duration, Instant};
+
+/// A locally spawned agent plus what is needed to respawn it.
+pub struct AgentProcess {
+    pub name: &'static str,
+    pub script: String,
+    pub args: Vec<String>,
+    pub addr: String,
+    pub child: Option<Child>,
+    pub restarts: u32,
+    next_restart: Option<Instant>,
+}
+
+/// Model names accepted by whisper.load_model.
+pub const WHISPER_MODELS: &[&str] = &[
+    "tiny", "tiny.en", "base", "base.en", "small", "small.en", "medium", "medium.en",
+    "large", "large-v1", "large-v2", "large-v3", "turbo",
+];
+
+pub struct AgentHandles(pub Arc<Mutex<Vec<AgentProcess>>>);
+
+static RESOURCE_DIR: OnceLock<std::path::PathBuf> = OnceLock::new();
+
+/// Records Tauri's resource dir, where bundled builds ship backend/ and proto/.
+/// Must be called before start_agents.
+pub fn set_resource_dir(dir: std::path::PathBuf) {
+    let _ = RESOURCE_DIR.set(dir);
+}
+
+fn resolve_script(rel: &str) -> std::path::PathBuf {
+    // Try current working directory first (dev usually runs from repo root)
+    let cwd = std::env::current_dir().unwrap_or_else(|_| std::path::PathBuf::from("."));
+    let p1 = cwd.join(rel);
+    if p1.exists() { return p1; }
+
+    // Installed app: the backend is bundled as a Tauri resource
+    if let Some(dir) = RESOURCE_DIR.get() {
+        let p = dir.join(rel);
+        if p.exists() { return p; }
+    }
+
+    // Try project root calculated from src-tauri manifest dir
+    let tauri_dir = std::path::PathBuf::from(env!("CARGO_MANIFEST_DIR"));
+    let project_root = tauri_dir.parent().unwrap_or(&tauri_dir);
+    let p2 = project_root.join(rel);
+    if p2.exists() { return p2; }
+
+    // Fall back to returning first candidate (even if missing)
+    p1
+}
+
+static PYTHON: OnceLock<String> = OnceLock::new();
+
+/// The interpreter used for agents: AURALINK_PYTHON if set, else the first of
+/// python3/python that answers `--version`. Probed once per run.
+pub fn resolve_python() -> &'static str {
+    PYTHON.get_or_init(|| {
+        let mut candidates = Vec::new();
+        if let Ok(p) = std::env::var("AURALINK_PYTHON") {
+            if !p.trim().is_empty() { candidates.push(p.trim().to_string()); }
+        }
+        candidates.push("python3".to_string());
+        candidates.push("python".to_string());
+        for candidate in &candidates {
+            let ok = Command::new(candidate)
+                .arg("--version")
+                .stdout(Stdio::null())
+                .stderr(Stdio::null())
+                .status()
+                .is_ok_and(|s| s.success());
+            if ok {
+                println!("[Tauri] Using Python interpreter: {}", candidate);
+                return candidate.clone();
+            }
+            println!("[Tauri] Python interpreter not usable: {}", candidate);
+        }
+        // Nothing answered; keep the conventional name so spawn errors are readable
+        println!("[Tauri] Warning: no Python interpreter found (set AURALINK_PYTHON)");
+        "python3".to_string()
+    })
+}
+
+// Directory holding backend/ and proto/, wherever resolve_script found them
+fn project_root() -> std::path::PathBuf {
+    let backend = resolve_script("backend");
+    backend.parent().map(|p| p.to_path_buf()).unwrap_or_else(|| std::path::PathBuf::from("."))
+}
+
+fn ensure_dir(path: &std::path::Path) {
+    let _ = std::fs::create_dir_all(path);
+}
+
+// Generated Python gRPC stubs. Kept in the data dir rather than next to backend/,
+// which is read-only in an installed app (/Applications, Program Files)
+fn python_stubs_dir() -> std::path::PathBuf {
+    crate::db::data_dir().join("generated")
+}
+
+fn protoc_flag(name: &str, path: &std::path::Path) -> std::ffi::OsString {
+    let mut arg = std::ffi::OsString::from(name);
+    arg.push(path);
+    arg
+}
+
+fn generate_python_protos() {
+    let root = project_root();
+    let gen_dir = python_stubs_dir();
+    ensure_dir(&gen_dir);
+    let proto_dir = root.join("proto");
+    let proto_path = proto_dir.join("audio_service.proto");
+    if !proto_path.exists() { return; }
+
+    let status = Command::new(resolve_python())
+        .args(["-m", "grpc_tools.protoc"])
+        .arg(protoc_flag("-I", &proto_dir))
+        .arg(protoc_flag("--python_out=", &gen_dir))
+        .arg(protoc_flag("--grpc_python_out=", &gen_dir))
+        .arg(&proto_path)
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .status();
+    if let Ok(s) = status {
+        if !s.success() {
+            println!("[Tauri] Warning: failed to generate Python gRPC stubs (grpc_tools not installed?)");
+        }
+    }
+}
+
+/// How long start-up waits for each agent, and how often it checks.
+/// AURALINK_AGENT_STARTUP_TIMEOUT (seconds, default 20; raise it for a first run that
+/// downloads model weights), AURALINK_AGENT_STARTUP_POLL_MS (default 200) and
+/// AURALINK_AGENT_STARTUP_PROBE=0 to accept a bare TCP connect as ready.
+struct StartupWait {
+    timeout: Duration,
+    poll: Duration,
+    probe: bool,
+}
+
+impl StartupWait {
+    fn from_env() -> Self {
+        let read = |key: &str| std::env::var(key).ok().and_then(|v| v.trim().parse::<u64>().ok());
+        Self {
+            timeout: Duration::from_secs(read("AURALINK_AGENT_STARTUP_TIMEOUT").unwrap_or(20)),
+            poll: Duration::from_millis(read("AURALINK_AGENT_STARTUP_POLL_MS").filter(|ms| *ms > 0).unwrap_or(200)),
+            probe: read("AURALINK_AGENT_STARTUP_PROBE") != Some(0),
+        }
+    }
+}
+
+// A gRPC server answers the HTTP/2 client preface with its own SETTINGS frame; a port
+// that is open but not serving yet (or serving something else) doesn't
+fn speaks_http2(stream: &mut TcpStream) -> bool {
+    use std::io::Read;
+    const PREFACE: &[u8] = b"PRI * HTTP/2.0\r\n\r\nSM\r\n\r\n";
+    // Empty SETTINGS frame: length 0, type 0x4, no flags, stream 0
+    const SETTINGS: [u8; 9] = [0, 0, 0, 4, 0, 0, 0, 0, 0];
+    let _ = stream.set_read_timeout(Some(Duration::from_millis(500)));
+    if stream.write_all(PREFACE).and_then(|_| stream.write_all(&SETTINGS)).is_err() {
+        return false;
+    }
+    let mut header = [0u8; 9];
+    stream.read_exact(&mut header).is_ok() && header[3] == 4
+}
+
+fn wait_for_agent(name: &str, addr: &str, wait: &StartupWait) -> bool {
+    let target = grpc_client::socket_target(addr);
+    // TLS endpoints would need a handshake first; a TCP connect is all we check there
+    let probe = wait.probe && !addr.starts_with("https://");
+    let started = Instant::now();
+    let mut next_report = started + Duration::from_secs(5);
+    while started.elapsed() < wait.timeout {
+        let addrs: Vec<SocketAddr> = target.to_socket_addrs().map(|a| a.collect()).unwrap_or_default();
+        let ready = addrs.iter().any(|a| match TcpStream::connect_timeout(a, Duration::from_millis(300)) {
+            Ok(mut stream) => !probe || speaks_http2(&mut stream),
+            Err(_) => false,
+        });
+        if ready {
+            return true;
+        }
+        if Instant::now() >= next_report {
+            let left = wait.timeout.saturating_sub(started.elapsed());
+            println!("[Tauri] Waiting for {} agent on {} ({}s left)", name, target, left.as_secs());
+            next_report += Duration::from_secs(5);
+        }
+        std::thread::sleep(wait.poll);
+    }
+    false
+}
+
+// Rotate at 5 MB, keeping one previous file (name.log.1)
+const LOG_MAX_BYTES: u64 = 5 * 1024 * 1024;
+
+/// Known agent names; also the stems of their log files.
+pub const AGENT_NAMES: &[&str] = &["transcription", "vision", "generation"];
+
+pub fn log_path(name: &str) -> std::path::PathBuf {
+    crate::db::db_path()
+        .parent()
+        .unwrap_or(std::path::Path::new("."))
+        .join("logs")
+        .join(format!("{}.log", name))
+}
+
+/// Appends an agent's output to logs/<name>.log, rotating by size.
+struct AgentLog {
+    path: std::path::PathBuf,
+    file: Option<std::fs::File>,
+    written: u64,
+}
+
+impl AgentLog {
+    fn open(name: &str) -> Self {
+        let path = log_path(name);
+        if let Some(dir) = path.parent() { ensure_dir(dir); }
+        let file = std::fs::OpenOptions::new().create(true).append(true).open(&path).ok();
+        let written = file.as_ref().and_then(|f| f.metadata().ok()).map(|m| m.len()).unwrap_or(0);
+        Self { path, file, written }
+    }
+
+    fn write_line(&mut self, stream: &str, line: &str) {
+        if self.written >= LOG_MAX_BYTES {
+            self.file = None;
+            let _ = std::fs::rename(&self.path, self.path.with_extension("log.1"));
+            self.file = std::fs::OpenOptions::new().create(true).append(true).open(&self.path).ok();
+            self.written = 0;
+        }
+        let Some(file) = self.file.as_mut() else { return };
+        let entry = format!("{} [{}] {}\n", chrono::Local::now().format("%Y-%m-%d %H:%M:%S"), stream, line);
+        if file.write_all(entry.as_bytes()).is_ok() {
+            self.written += entry.len() as u64;
+        }
+    }
+}
+
+/// Last `lines` lines of an agent's log (including the rotated file if needed).
+pub fn tail_log(name: &str, lines: usize) -> Result<String, String> {
+    if !AGENT_NAMES.contains(&name) {
+        return Err(format!("Unknown agent: {} (expected one of: {})", name, AGENT_NAMES.join(", ")));
+    }
+    let path = log_path(name);
+    let current = std::fs::read_to_string(&path).unwrap_or_default();
+    let mut all: Vec<&str> = current.lines().collect();
+    let previous;
+    if all.len() < lines {
+        previous = std::fs::read_to_string(path.with_extension("log.1")).unwrap_or_default();
+        let mut older: Vec<&str> = previous.lines().collect();
+        older.extend(all);
+        all = older;
+    }
+    let start = all.len().saturating_sub(lines);
+    Ok(all[start..].join("\n"))
+}
+
+fn spawn_python_agent(name: &str, script_rel: &str, args: &[String]) -> Option<Child> {
+    let script = resolve_script(script_rel);
+    if !script.exists() { 
+        println!("[Tauri] Agent script not found: {}", script.to_string_lossy());
+        return None; 
+    }
+    let mut cmd = Command::new(resolve_python());
+    // Ensure Python can import generated stubs and backend package
+    let root = project_root();
+    let gen_dir = python_stubs_dir();
+    let backend_dir = root.join("backend");
+    // Where the agent writes stubs itself if ours are missing
+    cmd.env("AURALINK_STUBS_DIR", &gen_dir);
+    // ";" on Windows, ":" elsewhere
+    let existing = std::env::var_os("PYTHONPATH").unwrap_or_default();
+    let paths = std::env::split_paths(&existing)
+        .filter(|p| !p.as_os_str().is_empty())
+        .chain([gen_dir, backend_dir]);
+    match std::env::join_paths(paths) {
+        Ok(pythonpath) => { cmd.env("PYTHONPATH", pythonpath); }
+        Err(e) => println!("[Tauri] Warning: couldn't extend PYTHONPATH for {} agent: {}", name, e),
+    }
+    // The server's caps must match the client's, or it rejects what we're willing to send.
+    // Chat shares the transcription agent, and its cap is never above the agents' one
+    cmd.env("AURALINK_MAX_MESSAGE_BYTES", grpc_client::MessageLimits::from_env().agents.to_string());
+    let mut child = cmd
+        .arg(script)
+        .args(args)
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+        .ok()?;
+
+    // Packaged builds have no console, so output always goes to the log file;
+    // debug builds also echo it for easier debugging
+    let log = Arc::new(Mutex::new(AgentLog::open(name)));
+    if let Some(out) = child.stdout.take() {
+        let reader = BufReader::new(out);
+        let log = log.clone();
+        std::thread::spawn(move || {
+            for line in reader.lines() {
+                let Ok(l) = line else { break };
+                if cfg!(debug_assertions) { println!("[agent stdout] {}", l); }
+                if let Ok(mut log) = log.lock() { log.write_line("stdout", &l); }
+            }
+        });
+    }
+    if let Some(err) = child.stderr.take() {
+        let reader = BufReader::new(err);
+        std::thread::spawn(move || {
+            for line in reader.lines() {
+                let Ok(l) = line else { break };
+                if cfg!(debug_assertions) { eprintln!("[agent stderr] {}", l); }
+                if let Ok(mut log) = log.lock() { log.write_line("stderr", &l); }
+            }
+        });
+    }
+
+    Some(child)
+}
+
+// Ports tried after a taken default, e.g. 50051 -> 50054..50073
+const PORT_SEARCH_SPAN: u16 = 20;
+
+fn port_free(port: u16) -> bool {
+    std::net::TcpListener::bind(("127.0.0.1", port)).is_ok()
+        && !port_open(&format!("http://127.0.0.1:{}", port))
+}
+
+// First free port after `port` that no other agent is configured to use
+fn next_free_port(port: u16, reserved: &[u16]) -> Option<u16> {
+    (1..=PORT_SEARCH_SPAN)
+        .filter_map(|i| port.checked_add(i))
+        .find(|p| !reserved.contains(p) && port_free(*p))
+}
+
+// Agents pointed at another host are expected to be running there already
+fn launch_agent(name: &'static str, addr: &str, settings: &crate::settings::Settings, reserved: &mut Vec<u16>) -> Option<AgentProcess> {
+    if !grpc_client::is_loopback(addr) {
+        println!("[Tauri] Using remote {} agent at {}", name, addr);
+        return None;
+    }
+    let Some(mut port) = grpc_client::port_of(addr) else {
+        println!("[Tauri] Warning: no port in {} endpoint {}", name, addr);
+        return None;
+    };
+    let mut addr = addr.to_string();
+    // A leftover agent from an unclean exit (or anything else) may hold the port;
+    // the new agent couldn't bind it, so move to a free one and tell the client
+    if !port_free(port) {
+        match next_free_port(port, reserved) {
+            Some(free) => {
+                println!("[Tauri] Port {} for {} agent is in use; using {} instead", port, name, free);
+                port = free;
+                addr = grpc_client::with_port(&addr, port);
+                reserved.push(port);
+                grpc_client::override_endpoint(name, addr.clone());
+            }
+            None => println!("[Tauri] Warning: port {} for {} agent is in use and no free port was found", port, name),
+        }
+    }
+    let script = format!("backend/mcp/{}_server.py", name);
+    let args = agent_args(name, port, settings);
+    let child = spawn_python_agent(name, &script, &args)?;
+    println!("[Tauri] Started {} agent on port {}", name, port);
+    Some(AgentProcess {
+        name,
+        script,
+        args,
+        addr,
+        child: Some(child),
+        restarts: 0,
+        next_restart: None,
+    })
+}
+
+fn agent_args(name: &str, port: u16, settings: &crate::settings::Settings) -> Vec<String> {
+    let mut args = vec!["--port".to_string(), port.to_string()];
+    if name == "transcription" { args.extend(["--model".to_string(), settings.whisper_model()]); }
+    args
+}
+
+/// Agents whose process has to change to follow `new`: switched on or off,
+/// or (for transcription) told to load a different Whisper model.
+pub fn affected_agents(old: &crate::settings::Settings, new: &crate::settings::Settings) -> Vec<&'static str> {
+    AGENT_NAMES
+        .iter()
+        .copied()
+        .filter(|name| {
+            old.agent_enabled(name) != new.agent_enabled(name)
+                || (*name == "transcription" && old.whisper_model() != new.whisper_model())
+        })
+        .collect()
+}
+
+/// Brings the named agents in line with `settings`: disabled ones are stopped,
+/// enabled ones are restarted with fresh arguments, or started if they never were.
+/// Returns the agents that are now running a new process.
+pub fn apply_settings(
+    agents: &mut Vec<AgentProcess>,
+    names: &[&'static str],
+    settings: &crate::settings::Settings,
+    grace: Duration,
+) -> Result<Vec<&'static str>, String> {
+    let mut started = Vec::new();
+    for &name in names {
+        if !settings.agent_enabled(name) {
+            if let Some(agent) = agents.iter_mut().find(|a| a.name == name) {
+                // With no child and nothing scheduled the supervisor leaves it alone
+                agent.next_restart = None;
+                if let Some(mut child) = agent.child.take() {
+                    stop_child(&mut child, grace);
+                    println!("[Tauri] Stopped {} agent; it is disabled in settings", name);
+                }
+            }
+            continue;
+        }
+        let port = agents.iter().find(|a| a.name == name).map(|a| grpc_client::port_of(&a.addr).unwrap_or(0));
+        match port {
+            Some(port) => {
+                let args = agent_args(name, port, settings);
+                if restart_agent(agents, name, args, grace)? {
+                    started.push(name);
+                }
+            }
+            None => {
+                let endpoints = grpc_client::GrpcEndpoints::current();
+                let Some((_, addr)) = endpoints.all().into_iter().find(|(n, _)| *n == name) else { continue };
+                let mut reserved: Vec<u16> = agents.iter().filter_map(|a| grpc_client::port_of(&a.addr)).collect();
+                if let Some(agent) = launch_agent(name, addr, settings, &mut reserved) {
+                    agents.push(agent);
+                    started.push(name);
+                }
+            }
+        }
+    }
+    Ok(started)
+}
+
+pub fn start_agents() -> Vec<AgentProcess> {
+    let mut agents = Vec::new();
+    let endpoints = grpc_client::GrpcEndpoints::from_env();
+    let mut reserved: Vec<u16> = endpoints.all().iter().filter_map(|(_, a)| grpc_client::port_of(a)).collect();
+    let settings = crate::settings::get();
+    // Generate stubs first so servers can import auralink_pb2*
+    generate_python_protos();
+    // Start transcription, vision, generation servers if scripts exist
+    // Models will load automatically on startup when servers are instantiated
+    for (name, addr) in endpoints.all() {
+        if !settings.agent_enabled(name) {
+            println!("[Tauri] {} agent is disabled in settings; not starting it", name);
+            continue;
+        }
+        agents.extend(launch_agent(name, addr, &settings, &mut reserved));
+    }
+    // Wait briefly for ports to be ready to avoid initial transport errors
+    let mut ready_count = 0usize;
+    let endpoints = grpc_client::GrpcEndpoints::current();
+    let wait = StartupWait::from_env();
+    for (name, addr) in endpoints.all() {
+        if !settings.agent_enabled(name) { continue; }
+        let target = grpc_client::socket_target(addr);
+        if wait_for_agent(name, addr, &wait) {
+            println!("[Tauri] {} agent is serving on {}", name, target);
+            ready_count += 1;
+        } else {
+            println!("[Tauri] Warning: {} agent was not serving on {} within {}s (AURALINK_AGENT_STARTUP_TIMEOUT)", name, target, wait.timeout.as_secs());
+        }
+    }
+    println!("[Tauri] Launched {} process(es); {} ready", agents.len(), ready_count);
+    agents
+}
+
+fn max_restarts() -> u32 {
+    std::env::var("AURALINK_AGENT_MAX_RESTARTS")
+        .ok()
+        .and_then(|v| v.trim().parse().ok())
+        .unwrap_or(5)
+}
+
+fn port_open(addr: &str) -> bool {
+    let target = grpc_client::socket_target(addr);
+    let addrs: Vec<SocketAddr> = target.to_socket_addrs().map(|a| a.collect()).unwrap_or_default();
+    addrs.iter().any(|a| TcpStream::connect_timeout(a, Duration::from_millis(300)).is_ok())
+}
+
+/// Watches spawned agents and respawns any that exit, backing off
+/// exponentially and giving up after AURALINK_AGENT_MAX_RESTARTS attempts.
+/// Clearing the handle list (on shutdown) stops supervision.
+pub fn spawn_supervisor(agents: Arc<Mutex<Vec<AgentProcess>>>) {
+    let limit = max_restarts();
+    std::thread::spawn(move || loop {
+        std::thread::sleep(Duration::from_secs(2));
+        let Ok(mut guard) = agents.lock() else { return };
+        for agent in guard.iter_mut() {
+            let exited = match agent.child.as_mut().map(|c| c.try_wait()) {
+                Some(Ok(Some(status))) => Some(status.to_string()),
+                Some(Err(e)) => Some(e.to_string()),
+                Some(Ok(None)) | None => None,
+            };
+            if let Some(reason) = exited {
+                println!("[Tauri] {} agent (port {}) exited: {}", agent.name, grpc_client::port_of(&agent.addr).unwrap_or(0), reason);
+                agent.child = None;
+                if agent.restarts >= limit {
+                    println!("[Tauri] {} agent reached {} restart(s); not restarting again", agent.name, limit);
+                    continue;
+                }
+                // 2s, 4s, 8s ... capped at one minute
+                let delay = Duration::from_secs(2u64.saturating_pow(agent.restarts + 1).min(60));
+                agent.next_restart = Some(Instant::now() + delay);
+            }
+            let due = agent.next_restart.map(|t| Instant::now() >= t).unwrap_or(false);
+            if agent.child.is_some() || !due { continue; }
+            agent.next_restart = None;
+            agent.restarts += 1;
+            // Something else may already be serving the port (e.g. a manual run)
+            if port_open(&agent.addr) {
+                println!("[Tauri] {} port is already open; not respawning", agent.name);
+                continue;
+            }
+            match spawn_python_agent(agent.name, &agent.script, &agent.args) {
+                Some(c) => {
+                    println!("[Tauri] Restarted {} agent on port {} (attempt {}/{})", agent.name, grpc_client::port_of(&agent.addr).unwrap_or(0), agent.restarts, limit);
+                    // Drop channels to the old process so the next call dials the new one
+                    tauri::async_runtime::spawn(grpc_client::GrpcClients::reset());
+                    agent.child = Some(c);
+                }
+                None => {
+                    println!("[Tauri] Failed to restart {} agent (attempt {}/{})", agent.name, agent.restarts, limit);
+                    if agent.restarts < limit {
+                        let delay = Duration::from_secs(2u64.saturating_pow(agent.restarts + 1).min(60));
+                        agent.next_restart = Some(Instant::now() + delay);
+                    }
+                }
+            }
+        }
+    });
+}
+
+// Ask a process to exit on its own; returns false if the request couldn't be sent
+fn request_terminate(child: &Child) -> bool {
+    let pid = child.id().to_string();
+    #[cfg(unix)]
+    let status = Command::new("kill").args(["-TERM", &pid]).status();
+    // Without /F, taskkill asks the process to close instead of terminating it
+    #[cfg(windows)]
+    let status = Command::new("taskkill").args(["/PID", &pid]).status();
+    #[cfg(not(any(unix, windows)))]
+    let status: std::io::Result<std::process::ExitStatus> = Err(std::io::ErrorKind::Unsupported.into());
+    matches!(status, Ok(s) if s.success())
+}
+
+// Ask first, then kill whatever is still running after `grace`
+fn stop_child(child: &mut Child, grace: Duration) {
+    request_terminate(child);
+    let deadline = Instant::now() + grace;
+    while matches!(child.try_wait(), Ok(None)) && Instant::now() < deadline {
+        std::thread::sleep(Duration::from_millis(100));
+    }
+    if matches!(child.try_wait(), Ok(None)) {
+        let _ = child.kill();
+    }
+    let _ = child.wait();
+}
+
+/// Stops one running agent and starts it again with `args`. Returns false when
+/// no agent by that name is managed here (remote, disabled or never started).
+pub fn restart_agent(agents: &mut [AgentProcess], name: &str, args: Vec<String>, grace: Duration) -> Result<bool, String> {
+    let Some(agent) = agents.iter_mut().find(|a| a.name == name) else { return Ok(false) };
+    if let Some(mut child) = agent.child.take() {
+        stop_child(&mut child, grace);
+    }
+    agent.args = args;
+    agent.restarts = 0;
+    agent.next_restart = None;
+    let child = spawn_python_agent(agent.name, &agent.script, &agent.args)
+        .ok_or_else(|| format!("Failed to restart {} agent", name))?;
+    println!("[Tauri] Restarted {} agent with {}", name, agent.args.join(" "));
+    agent.child = Some(child);
+    Ok(true)
+}
+
+/// Stops all agents: SIGTERM first, up to `grace` for them to exit, then kill.
+pub fn shutdown(agents: &mut Vec<AgentProcess>, grace: Duration) {
+    for agent in agents.iter_mut() {
+        if let Some(child) = agent.child.as_ref() {
+            if !request_terminate(child) {
+                println!("[Tauri] Could not signal {} agent; will force kill", agent.name);
+            }
+        }
+    }
+    let deadline = Instant::now() + grace;
+    loop {
+        let running = agents
+            .iter_mut()
+            .filter_map(|a| a.child.as_mut())
+            .filter(|c| matches!(c.try_wait(), Ok(None)))
+            .count();
+        if running == 0 || Instant::now() >= deadline { break; }
+        std::thread::sleep(Duration::from_millis(100));
+    }
+    for agent in agents.iter_mut() {
+        if let Some(mut child) = agent.child.take() {
+            if matches!(child.try_wait(), Ok(None)) {
+                println!("[Tauri] {} agent did not exit in time; killing", agent.name);
+                let _ = child.kill();
+                let _ = child.wait();
+            }
+        }
+    }
+    agents.clear();
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::settings::Settings;
+
+    fn with_model(model: &str) -> Settings {
+        Settings { whisper_model: Some(model.to_string()), ..Settings::default() }
+    }
+
+    #[test]
+    fn settings_changes_pick_the_agents_to_restart() {
+        let base = with_model("base");
+        assert!(affected_agents(&base, &base.clone()).is_empty());
+        // Settings the agents don't read leave them running
+        let chatty = Settings { verbose: true, response_max_chars: 900, ..base.clone() };
+        assert!(affected_agents(&base, &chatty).is_empty());
+
+        assert_eq!(affected_agents(&base, &with_model("small")), vec!["transcription"]);
+        let no_vision = Settings { enable_vision: false, ..base.clone() };
+        assert_eq!(affected_agents(&base, &no_vision), vec!["vision"]);
+        assert_eq!(affected_agents(&no_vision, &base), vec!["vision"]);
+        let both = Settings { enable_generation: false, ..with_model("tiny") };
+        assert_eq!(affected_agents(&base, &both), vec!["transcription", "generation"]);
+    }
+
+    #[test]
+    fn disabling_an_agent_stops_it_without_a_respawn() {
+        let mut agents = vec![AgentProcess {
+            name: "vision",
+            script: "backend/mcp/vision_server.py".to_string(),
+            args: vec!["--port".to_string(), "50052".to_string()],
+            addr: "http://127.0.0.1:50052".to_string(),
+            child: None,
+            restarts: 2,
+            next_restart: Some(Instant::now()),
+        }];
+        let disabled = Settings { enable_vision: false, ..Settings::default() };
+        let started = apply_settings(&mut agents, &["vision"], &disabled, Duration::from_millis(10)).unwrap();
+        assert!(started.is_empty());
+        assert!(agents[0].child.is_none());
+        assert!(agents[0].next_restart.is_none());
+    }
+}
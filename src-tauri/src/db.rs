@@ -0,0 +1,1499 @@
+use rusqlite::{params, Connection, OptionalExtension, Transaction, TransactionBehavior};
+use std::path::{Path, PathBuf};
+
+pub struct FileRow {
+    pub id: String,
+    pub name: Option<String>,
+    pub path: String,
+    pub thumb_path: Option<String>,
+    pub created_at: String,
+    pub duration: Option<f64>,
+    pub tags: Vec<String>,
+    pub title: Option<String>,
+    pub updated_at: String,
+}
+
+pub struct ConversationRow {
+    pub file: FileRow,
+    pub message_count: i64,
+    pub last_message_at: String,
+    pub last_message: String,
+    pub last_is_user: bool,
+}
+
+pub struct MessagesPage {
+    pub messages: Vec<serde_json::Value>,
+    pub next_cursor: Option<String>,
+}
+
+pub struct SearchHit {
+    pub id: String,
+    pub file_id: String,
+    pub text: String,
+    pub is_user: bool,
+    pub created_at: String,
+    pub snippet: String,
+}
+
+pub struct ArtifactRow {
+    pub id: String,
+    pub kind: String,
+    pub path: String,
+    pub created_at: String,
+}
+
+pub struct LibraryCounts {
+    pub files: i64,
+    pub messages: i64,
+    pub artifacts: i64,
+}
+
+/// Per-file defaults; files without a row behave like `FileSettingsRow::default()`.
+#[derive(Clone, Debug, Default)]
+pub struct FileSettingsRow {
+    pub auto_transcribe: bool,
+    // Task names that settle an ambiguous request for this file
+    pub preferred_intents: Vec<String>,
+}
+
+pub struct TranscriptRow {
+    pub text: String,
+    pub content_hash: String,
+    pub language: Option<String>,
+}
+
+pub fn default_data_dir() -> PathBuf {
+    let base = dirs::data_local_dir().unwrap_or_else(|| PathBuf::from("."));
+    base.join("auralink")
+}
+
+/// Directory given by AURALINK_DATA_DIR, which overrides the storage_dir setting.
+pub fn data_dir_from_env() -> Option<PathBuf> {
+    std::env::var("AURALINK_DATA_DIR")
+        .ok()
+        .map(|d| d.trim().to_string())
+        .filter(|d| !d.is_empty())
+        .map(PathBuf::from)
+}
+
+/// Where the database, uploaded media, thumbnails and exports live.
+pub fn data_dir() -> PathBuf {
+    data_dir_from_env()
+        .or_else(|| crate::settings::get().storage_dir.map(PathBuf::from))
+        .unwrap_or_else(default_data_dir)
+}
+
+pub fn db_path() -> PathBuf {
+    data_dir().join("auralink.db")
+}
+
+fn open() -> rusqlite::Result<Connection> {
+    let path = db_path();
+    if let Some(dir) = path.parent() {
+        let _ = std::fs::create_dir_all(dir);
+    }
+    let conn = Connection::open(path)?;
+    // Wait for competing writers instead of failing immediately with SQLITE_BUSY
+    conn.busy_timeout(std::time::Duration::from_millis(5000))?;
+    conn.execute_batch("PRAGMA journal_mode=WAL; PRAGMA foreign_keys=ON;")?;
+    Ok(conn)
+}
+
+fn is_transient(e: &rusqlite::Error) -> bool {
+    matches!(
+        e,
+        rusqlite::Error::SqliteFailure(err, _)
+            if matches!(err.code, rusqlite::ErrorCode::DatabaseBusy | rusqlite::ErrorCode::DatabaseLocked)
+    )
+}
+
+// Shared by every with_conn call and held exclusively by compact(), so VACUUM
+// never runs while this process has another connection mid-transaction
+static MAINTENANCE: std::sync::RwLock<()> = std::sync::RwLock::new(());
+
+/// Runs `f` on a fresh connection, retrying a few times if the database stays locked
+/// past the busy timeout.
+fn with_conn<T>(mut f: impl FnMut(&Connection) -> rusqlite::Result<T>) -> rusqlite::Result<T> {
+    let _shared = MAINTENANCE.read().unwrap_or_else(|e| e.into_inner());
+    let mut attempt = 0;
+    loop {
+        match open().and_then(|conn| f(&conn)) {
+            Err(e) if is_transient(&e) && attempt < 3 => {
+                attempt += 1;
+                std::thread::sleep(std::time::Duration::from_millis(100 * attempt));
+            }
+            other => return other,
+        }
+    }
+}
+
+/// The database plus its WAL and shared-memory files, in bytes.
+pub fn size_on_disk() -> u64 {
+    let path = db_path();
+    ["", "-wal", "-shm"]
+        .iter()
+        .map(|suffix| {
+            let mut p = path.clone().into_os_string();
+            p.push(suffix);
+            std::fs::metadata(p).map(|m| m.len()).unwrap_or(0)
+        })
+        .sum()
+}
+
+// Kept where they are when the library moves: settings.json stays in the default
+// dir, agents hold their logs open, and the WAL is folded in before copying
+const UNMOVED: &[&str] = &["settings.json", "logs", "auralink.db-wal", "auralink.db-shm"];
+
+fn copy_all(from: &Path, to: &Path) -> std::io::Result<()> {
+    if from.is_dir() {
+        std::fs::create_dir_all(to)?;
+        for entry in std::fs::read_dir(from)? {
+            let entry = entry?;
+            copy_all(&entry.path(), &to.join(entry.file_name()))?;
+        }
+        Ok(())
+    } else {
+        std::fs::copy(from, to).map(|_| ())
+    }
+}
+
+/// Copies the library from the current data dir into `to` and points the stored
+/// media, thumbnail and artifact paths at the copies. No other connection runs
+/// meanwhile; `switch` makes `to` the data dir before they're let back in.
+/// Returns the originals, which the caller removes once it's safe to.
+pub fn copy_library(to: &Path, switch: impl FnOnce() -> Result<(), String>) -> Result<Vec<PathBuf>, String> {
+    let _exclusive = MAINTENANCE.write().unwrap_or_else(|e| e.into_inner());
+    let from = data_dir();
+    let conn = open().map_err(|e| e.to_string())?;
+    conn.query_row("PRAGMA wal_checkpoint(TRUNCATE)", [], |_| Ok(())).map_err(|e| e.to_string())?;
+    drop(conn);
+
+    let mut originals = Vec::new();
+    let copied = std::fs::read_dir(&from).map_err(|e| e.to_string())?.try_for_each(|entry| {
+        let entry = entry?;
+        if UNMOVED.iter().any(|n| entry.file_name() == **n) {
+            return Ok(());
+        }
+        originals.push(entry.path());
+        copy_all(&entry.path(), &to.join(entry.file_name()))
+    });
+    let repointed = copied.map_err(|e| e.to_string()).and_then(|()| {
+        let conn = Connection::open(to.join("auralink.db")).map_err(|e| e.to_string())?;
+        let old = format!("{}{}", from.display(), std::path::MAIN_SEPARATOR);
+        let new = format!("{}{}", to.display(), std::path::MAIN_SEPARATOR);
+        for (table, column) in [("files", "path"), ("files", "thumb_path"), ("artifacts", "path")] {
+            conn.execute(
+                &format!("UPDATE {table} SET {column} = ?2 || substr({column}, length(?1) + 1) WHERE substr({column}, 1, length(?1)) = ?1"),
+                params![old, new],
+            )
+            .map_err(|e| e.to_string())?;
+        }
+        Ok(())
+    });
+    if let Err(e) = repointed.and_then(|()| switch()) {
+        // Leave the destination as it was; the library stays where it is
+        for original in &originals {
+            if let Some(name) = original.file_name() {
+                let copy = to.join(name);
+                let _ = if copy.is_dir() { std::fs::remove_dir_all(&copy) } else { std::fs::remove_file(&copy) };
+            }
+        }
+        return Err(e);
+    }
+    Ok(originals)
+}
+
+/// Checkpoints the WAL into the main file and rebuilds it without dead pages.
+/// Returns the on-disk size (database + WAL) before and after.
+pub fn compact() -> rusqlite::Result<(u64, u64)> {
+    let _exclusive = MAINTENANCE.write().unwrap_or_else(|e| e.into_inner());
+    let before = size_on_disk();
+    let conn = open()?;
+    let checkpoint = |conn: &Connection| conn.query_row("PRAGMA wal_checkpoint(TRUNCATE)", [], |_| Ok(()));
+    checkpoint(&conn)?;
+    conn.execute_batch("VACUUM")?;
+    // VACUUM goes through the WAL too; fold it back in so the WAL ends up empty
+    checkpoint(&conn)?;
+    drop(conn);
+    Ok((before, size_on_disk()))
+}
+
+type Migration = fn(&Connection) -> rusqlite::Result<()>;
+
+// Applied in order; migration N leaves the database at user_version N.
+// Append new entries here, never edit or reorder existing ones.
+const MIGRATIONS: &[Migration] = &[
+    // 1: original schema
+    |conn| {
+        conn.execute_batch(
+            "CREATE TABLE IF NOT EXISTS files (
+                id TEXT PRIMARY KEY,
+                path TEXT NOT NULL,
+                created_at TEXT NOT NULL
+            );
+            CREATE TABLE IF NOT EXISTS messages (
+                id TEXT PRIMARY KEY,
+                file_id TEXT NOT NULL,
+                text TEXT NOT NULL,
+                is_user_message INTEGER NOT NULL,
+                created_at TEXT NOT NULL
+            );",
+        )
+    },
+    // 2: display name and thumbnail for files
+    |conn| {
+        if !has_column(conn, "files", "name")? {
+            conn.execute("ALTER TABLE files ADD COLUMN name TEXT", [])?;
+        }
+        if !has_column(conn, "files", "thumb_path")? {
+            conn.execute("ALTER TABLE files ADD COLUMN thumb_path TEXT", [])?;
+        }
+        Ok(())
+    },
+    // 3: transcript cache and pending clarifications
+    |conn| {
+        conn.execute_batch(
+            "CREATE TABLE IF NOT EXISTS transcripts (
+                file_id TEXT PRIMARY KEY,
+                content_hash TEXT NOT NULL,
+                text TEXT NOT NULL,
+                updated_at TEXT NOT NULL
+            );
+            CREATE TABLE IF NOT EXISTS pending_clarifications (
+                file_id TEXT PRIMARY KEY,
+                options TEXT NOT NULL,
+                created_at TEXT NOT NULL
+            );",
+        )
+    },
+    // 4: pagination indexes
+    |conn| {
+        conn.execute_batch(
+            "DROP INDEX IF EXISTS idx_messages_file_created;
+            CREATE INDEX IF NOT EXISTS idx_messages_file_created_id ON messages(file_id, created_at, id);
+            CREATE INDEX IF NOT EXISTS idx_files_created ON files(created_at);",
+        )
+    },
+    // 5: cached ffprobe results
+    |conn| {
+        conn.execute_batch(
+            "CREATE TABLE IF NOT EXISTS file_metadata (
+                file_id TEXT PRIMARY KEY,
+                duration REAL,
+                width INTEGER,
+                height INTEGER,
+                video_codec TEXT,
+                audio_codec TEXT,
+                bitrate INTEGER,
+                frame_rate REAL,
+                has_video INTEGER NOT NULL,
+                has_audio INTEGER NOT NULL,
+                updated_at TEXT NOT NULL
+            );",
+        )
+    },
+    // 6: generated documents per file
+    |conn| {
+        conn.execute_batch(
+            "CREATE TABLE IF NOT EXISTS artifacts (
+                id TEXT PRIMARY KEY,
+                file_id TEXT NOT NULL,
+                kind TEXT NOT NULL,
+                path TEXT NOT NULL,
+                created_at TEXT NOT NULL
+            );
+            CREATE INDEX IF NOT EXISTS idx_artifacts_file_created ON artifacts(file_id, created_at);",
+        )
+    },
+    // 7: last key-moments list per file, so "moment 3" can be resolved later
+    |conn| {
+        conn.execute_batch(
+            "CREATE TABLE IF NOT EXISTS key_moments (
+                file_id TEXT NOT NULL,
+                idx INTEGER NOT NULL,
+                at_secs REAL NOT NULL,
+                label TEXT NOT NULL,
+                created_at TEXT NOT NULL,
+                PRIMARY KEY (file_id, idx)
+            );",
+        )
+    },
+    // 8: language Whisper detected for a cached transcript
+    |conn| {
+        if !has_column(conn, "transcripts", "language")? {
+            conn.execute("ALTER TABLE transcripts ADD COLUMN language TEXT", [])?;
+        }
+        Ok(())
+    },
+    // 9: SHA-256 of uploaded bytes, to spot the same video dropped in twice
+    |conn| {
+        if !has_column(conn, "files", "content_hash")? {
+            conn.execute("ALTER TABLE files ADD COLUMN content_hash TEXT", [])?;
+        }
+        conn.execute("CREATE INDEX IF NOT EXISTS idx_files_content_hash ON files(content_hash)", [])?;
+        Ok(())
+    },
+    // 10: user tags on files
+    |conn| {
+        conn.execute_batch(
+            "CREATE TABLE IF NOT EXISTS tags (
+                file_id TEXT NOT NULL,
+                tag TEXT NOT NULL,
+                PRIMARY KEY (file_id, tag)
+            );
+            CREATE INDEX IF NOT EXISTS idx_tags_tag ON tags(tag);",
+        )
+    },
+    // 11: timed transcript segments, stored alongside the cached transcript
+    |conn| {
+        conn.execute_batch(
+            "CREATE TABLE IF NOT EXISTS transcript_segments (
+                file_id TEXT NOT NULL,
+                idx INTEGER NOT NULL,
+                start_secs REAL NOT NULL,
+                end_secs REAL NOT NULL,
+                text TEXT NOT NULL,
+                PRIMARY KEY (file_id, idx)
+            );",
+        )
+    },
+    // 12: generated title shown in place of the filename
+    |conn| {
+        if !has_column(conn, "files", "title")? {
+            conn.execute("ALTER TABLE files ADD COLUMN title TEXT", [])?;
+        }
+        Ok(())
+    },
+    // 13: last activity on a file (edits and new messages), for a "recently active" sort.
+    // Existing files start from their latest message, or when they were added.
+    |conn| {
+        if !has_column(conn, "files", "updated_at")? {
+            conn.execute("ALTER TABLE files ADD COLUMN updated_at TEXT", [])?;
+        }
+        conn.execute_batch(
+            "UPDATE files SET updated_at = COALESCE(
+                (SELECT MAX(created_at) FROM messages WHERE file_id = files.id), created_at)
+             WHERE updated_at IS NULL;
+             CREATE INDEX IF NOT EXISTS idx_files_updated ON files(updated_at);",
+        )
+    },
+    // 14: per-file analysis defaults
+    |conn| {
+        conn.execute_batch(
+            "CREATE TABLE IF NOT EXISTS file_settings (
+                file_id TEXT PRIMARY KEY,
+                auto_transcribe INTEGER NOT NULL DEFAULT 0,
+                preferred_intents TEXT NOT NULL DEFAULT '[]'
+            );",
+        )
+    },
+    // 15: frame time and width the thumbnail was last made with
+    |conn| {
+        if !has_column(conn, "files", "thumb_at")? {
+            conn.execute("ALTER TABLE files ADD COLUMN thumb_at REAL", [])?;
+        }
+        if !has_column(conn, "files", "thumb_width")? {
+            conn.execute("ALTER TABLE files ADD COLUMN thumb_width INTEGER", [])?;
+        }
+        Ok(())
+    },
+    // 16: the request a numeric reply to a clarification stood for, by user message
+    |conn| {
+        conn.execute_batch(
+            "CREATE TABLE IF NOT EXISTS clarification_answers (
+                message_id TEXT PRIMARY KEY,
+                file_id TEXT NOT NULL,
+                request TEXT NOT NULL
+            );",
+        )
+    },
+    // 17: back to the idx_messages_file_created name, now covering id for cursor ties
+    |conn| {
+        conn.execute_batch(
+            "DROP INDEX IF EXISTS idx_messages_file_created_id;
+            DROP INDEX IF EXISTS idx_messages_file_created;
+            CREATE INDEX IF NOT EXISTS idx_messages_file_created ON messages(file_id, created_at, id);",
+        )
+    },
+];
+
+fn has_table(conn: &Connection, name: &str) -> rusqlite::Result<bool> {
+    conn.query_row(
+        "SELECT 1 FROM sqlite_master WHERE name = ?1",
+        params![name],
+        |_| Ok(()),
+    )
+    .optional()
+    .map(|r| r.is_some())
+}
+
+fn has_column(conn: &Connection, table: &str, column: &str) -> rusqlite::Result<bool> {
+    let mut stmt = conn.prepare(&format!("PRAGMA table_info({})", table))?;
+    let names = stmt.query_map([], |r| r.get::<_, String>(1))?;
+    for name in names {
+        if name? == column {
+            return Ok(true);
+        }
+    }
+    Ok(false)
+}
+
+// Databases created before user_version was tracked: work out how far along they are
+// so only the missing steps run.
+fn detect_legacy_version(conn: &Connection) -> rusqlite::Result<i64> {
+    if !has_table(conn, "messages")? {
+        return Ok(0);
+    }
+    if !(has_column(conn, "files", "name")? && has_column(conn, "files", "thumb_path")?) {
+        return Ok(1);
+    }
+    if !(has_table(conn, "transcripts")? && has_table(conn, "pending_clarifications")?) {
+        return Ok(2);
+    }
+    if !(has_table(conn, "idx_messages_file_created_id")? && has_table(conn, "idx_files_created")?) {
+        return Ok(3);
+    }
+    // Later versions only exist alongside user_version tracking
+    Ok(4)
+}
+
+pub fn schema_version() -> rusqlite::Result<i64> {
+    with_conn(|conn| conn.pragma_query_value(None, "user_version", |r| r.get(0)))
+}
+
+fn migrate(conn: &Connection) -> rusqlite::Result<()> {
+    let mut version: i64 = conn.pragma_query_value(None, "user_version", |r| r.get(0))?;
+    if version == 0 {
+        version = detect_legacy_version(conn)?;
+        if version > 0 {
+            println!("[Tauri] Stamping existing database at schema version {}", version);
+            conn.pragma_update(None, "user_version", version)?;
+        }
+    }
+    for (i, step) in MIGRATIONS.iter().enumerate() {
+        let target = i as i64 + 1;
+        if target <= version {
+            continue;
+        }
+        let tx = conn.unchecked_transaction()?;
+        step(&tx)?;
+        tx.pragma_update(None, "user_version", target)?;
+        tx.commit()?;
+        println!("[Tauri] Applied schema migration {}", target);
+    }
+    Ok(())
+}
+
+pub fn init() -> rusqlite::Result<()> {
+    let conn = open()?;
+    migrate(&conn)?;
+    if let Err(e) = init_fts(&conn) {
+        println!("[Tauri] Full-text search unavailable, falling back to LIKE: {}", e);
+    }
+    Ok(())
+}
+
+fn fts_available(conn: &Connection) -> bool {
+    conn.query_row(
+        "SELECT 1 FROM sqlite_master WHERE type = 'table' AND name = 'messages_fts'",
+        [],
+        |_| Ok(()),
+    )
+    .optional()
+    .map(|r| r.is_some())
+    .unwrap_or(false)
+}
+
+// External-content FTS5 index over messages.text, kept in sync by triggers.
+// Fails (and is skipped) when the linked SQLite was built without FTS5.
+fn init_fts(conn: &Connection) -> rusqlite::Result<()> {
+    let existed = fts_available(conn);
+    conn.execute_batch(
+        "CREATE VIRTUAL TABLE IF NOT EXISTS messages_fts USING fts5(text, content='messages', content_rowid='rowid');
+        CREATE TRIGGER IF NOT EXISTS messages_fts_ai AFTER INSERT ON messages BEGIN
+            INSERT INTO messages_fts(rowid, text) VALUES (new.rowid, new.text);
+        END;
+        CREATE TRIGGER IF NOT EXISTS messages_fts_ad AFTER DELETE ON messages BEGIN
+            INSERT INTO messages_fts(messages_fts, rowid, text) VALUES ('delete', old.rowid, old.text);
+        END;
+        CREATE TRIGGER IF NOT EXISTS messages_fts_au AFTER UPDATE OF text ON messages BEGIN
+            INSERT INTO messages_fts(messages_fts, rowid, text) VALUES ('delete', old.rowid, old.text);
+            INSERT INTO messages_fts(rowid, text) VALUES (new.rowid, new.text);
+        END;",
+    )?;
+    if !existed {
+        // Index messages written before the FTS table existed
+        conn.execute("INSERT INTO messages_fts(messages_fts) VALUES ('rebuild')", [])?;
+    }
+    Ok(())
+}
+
+fn message_json(id: String, text: String, is_user: bool, created_at: String) -> serde_json::Value {
+    serde_json::json!({
+        "id": id,
+        "text": text,
+        "isUserMessage": is_user,
+        "createdAt": created_at,
+    })
+}
+
+pub fn insert_message(
+    id: &str,
+    file_id: &str,
+    text: &str,
+    is_user: bool,
+    created_at: &str,
+) -> rusqlite::Result<()> {
+    with_conn(|conn| {
+        conn.execute(
+            "INSERT INTO messages (id, file_id, text, is_user_message, created_at) VALUES (?1, ?2, ?3, ?4, ?5)",
+            params![id, file_id, text, is_user as i64, created_at],
+        )?;
+        conn.execute("UPDATE files SET updated_at = ?1 WHERE id = ?2", params![created_at, file_id])?;
+        Ok(())
+    })
+}
+
+/// Deletes one message; false if no message had that id. Replies are left alone
+/// since messages carry no link to the turn that produced them.
+pub fn delete_message(id: &str) -> rusqlite::Result<bool> {
+    with_conn(|conn| {
+        // The messages_fts delete trigger keeps search in sync
+        let tx = conn.unchecked_transaction()?;
+        let removed = tx.execute("DELETE FROM messages WHERE id = ?1", params![id])?;
+        tx.execute("DELETE FROM clarification_answers WHERE message_id = ?1", params![id])?;
+        tx.commit()?;
+        Ok(removed > 0)
+    })
+}
+
+/// Removes every message for a file (and any pending clarification), keeping the
+/// file itself. Returns how many messages were deleted.
+pub fn clear_messages(file_id: &str) -> rusqlite::Result<usize> {
+    with_conn(|conn| {
+        let tx = conn.unchecked_transaction()?;
+        let removed = tx.execute("DELETE FROM messages WHERE file_id = ?1", params![file_id])?;
+        tx.execute("DELETE FROM pending_clarifications WHERE file_id = ?1", params![file_id])?;
+        tx.execute("DELETE FROM clarification_answers WHERE file_id = ?1", params![file_id])?;
+        tx.commit()?;
+        Ok(removed)
+    })
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Direction {
+    Asc,
+    Desc,
+}
+
+// Cursors are "created_at|id" so rows sharing a timestamp are not skipped at page
+// boundaries. A bare timestamp (older clients) includes every row at that time.
+fn split_cursor(cursor: &str, direction: Direction) -> (&str, &str) {
+    let bare_id = match direction {
+        Direction::Asc => "",
+        Direction::Desc => "\u{10FFFF}",
+    };
+    cursor.rsplit_once('|').unwrap_or((cursor, bare_id))
+}
+
+pub fn list_messages(
+    file_id: &str,
+    limit: i64,
+    cursor: Option<&str>,
+    direction: Direction,
+) -> rusqlite::Result<MessagesPage> {
+    with_conn(|conn| messages_page(conn, file_id, limit, cursor, direction))
+}
+
+fn messages_page(
+    conn: &Connection,
+    file_id: &str,
+    limit: i64,
+    cursor: Option<&str>,
+    direction: Direction,
+) -> rusqlite::Result<MessagesPage> {
+    let limit = limit.max(1);
+    // Fetch one extra row to know whether another page exists
+    let mut stmt = conn.prepare(match direction {
+        Direction::Asc => {
+            "SELECT id, text, is_user_message, created_at FROM messages
+             WHERE file_id = ?1 AND (?2 IS NULL OR (created_at, id) > (?2, ?3))
+             ORDER BY created_at ASC, id ASC
+             LIMIT ?4"
+        }
+        Direction::Desc => {
+            "SELECT id, text, is_user_message, created_at FROM messages
+             WHERE file_id = ?1 AND (?2 IS NULL OR (created_at, id) < (?2, ?3))
+             ORDER BY created_at DESC, id DESC
+             LIMIT ?4"
+        }
+    })?;
+    let (after_ts, after_id) = match cursor.map(|c| split_cursor(c, direction)) {
+        Some((ts, id)) => (Some(ts), id),
+        None => (None, ""),
+    };
+    let rows = stmt.query_map(params![file_id, after_ts, after_id, limit + 1], |r| {
+        Ok((
+            r.get::<_, String>(0)?,
+            r.get::<_, String>(1)?,
+            r.get::<_, i64>(2)? != 0,
+            r.get::<_, String>(3)?,
+        ))
+    })?;
+    let mut items = Vec::new();
+    for row in rows {
+        items.push(row?);
+    }
+    let mut next_cursor = None;
+    if items.len() as i64 > limit {
+        items.truncate(limit as usize);
+        next_cursor = items.last().map(|(id, _, _, created_at)| format!("{}|{}", created_at, id));
+    }
+    let messages = items
+        .into_iter()
+        .map(|(id, text, is_user, created_at)| message_json(id, text, is_user, created_at))
+        .collect();
+    Ok(MessagesPage { messages, next_cursor })
+}
+
+/// One message in the list shape, plus the `fileId` it belongs to.
+pub fn get_message(id: &str) -> rusqlite::Result<Option<serde_json::Value>> {
+    with_conn(|conn| {
+        conn.query_row(
+            "SELECT id, file_id, text, is_user_message, created_at FROM messages WHERE id = ?1",
+            params![id],
+            |r| {
+                let file_id: String = r.get(1)?;
+                let mut message = message_json(r.get(0)?, r.get(2)?, r.get::<_, i64>(3)? != 0, r.get(4)?);
+                message["fileId"] = serde_json::json!(file_id);
+                Ok(message)
+            },
+        )
+        .optional()
+    })
+}
+
+/// (id, text, created_at) of the newest user message for a file.
+pub fn last_user_message(file_id: &str) -> rusqlite::Result<Option<(String, String, String)>> {
+    with_conn(|conn| {
+        conn.query_row(
+            "SELECT id, text, created_at FROM messages WHERE file_id = ?1 AND is_user_message = 1
+             ORDER BY created_at DESC, id DESC LIMIT 1",
+            params![file_id],
+            |r| Ok((r.get(0)?, r.get(1)?, r.get(2)?)),
+        )
+        .optional()
+    })
+}
+
+/// Swaps the assistant messages after (created_at, id) for a single new reply in
+/// one transaction, so a failed save leaves the old replies in place. Returns how
+/// many were replaced.
+pub fn replace_replies_after(
+    file_id: &str,
+    created_at: &str,
+    id: &str,
+    reply_id: &str,
+    text: &str,
+    now: &str,
+) -> rusqlite::Result<usize> {
+    with_conn(|conn| {
+        let tx = conn.unchecked_transaction()?;
+        let removed = tx.execute(
+            "DELETE FROM messages WHERE file_id = ?1 AND is_user_message = 0 AND (created_at, id) > (?2, ?3)",
+            params![file_id, created_at, id],
+        )?;
+        tx.execute(
+            "INSERT INTO messages (id, file_id, text, is_user_message, created_at) VALUES (?1, ?2, ?3, 0, ?4)",
+            params![reply_id, file_id, text, now],
+        )?;
+        tx.execute("UPDATE files SET updated_at = ?1 WHERE id = ?2", params![now, file_id])?;
+        tx.commit()?;
+        Ok(removed)
+    })
+}
+
+/// Most recent messages first, as (text, is_user) pairs.
+pub fn recent_messages(file_id: &str, limit: i64) -> rusqlite::Result<Vec<(String, bool)>> {
+    with_conn(|conn| {
+        let mut stmt = conn.prepare(
+            "SELECT text, is_user_message FROM messages WHERE file_id = ?1
+             ORDER BY created_at DESC, id DESC LIMIT ?2",
+        )?;
+        let rows = stmt.query_map(params![file_id, limit], |r| {
+            Ok((r.get::<_, String>(0)?, r.get::<_, i64>(1)? != 0))
+        })?;
+        rows.collect()
+    })
+}
+
+/// Adds a file, or updates the name and path of an existing id (keeping its `created_at`).
+pub fn insert_file(id: &str, name: &str, path: &str, created_at: &str) -> rusqlite::Result<()> {
+    with_conn(|conn| {
+        let now = chrono::Utc::now().to_rfc3339();
+        conn.execute(
+            "INSERT INTO files (id, name, path, created_at, updated_at) VALUES (?1, ?2, ?3, ?4, ?4)
+             ON CONFLICT(id) DO UPDATE SET name = excluded.name, path = excluded.path, updated_at = ?5",
+            params![id, name, path, created_at, now],
+        )?;
+        Ok(())
+    })
+}
+
+pub fn library_counts() -> rusqlite::Result<LibraryCounts> {
+    with_conn(|conn| {
+        let count = |table: &str| conn.query_row(&format!("SELECT COUNT(*) FROM {}", table), [], |r| r.get::<_, i64>(0));
+        Ok(LibraryCounts {
+            files: count("files")?,
+            messages: count("messages")?,
+            artifacts: count("artifacts")?,
+        })
+    })
+}
+
+pub fn get_file_path(id: &str) -> rusqlite::Result<Option<String>> {
+    with_conn(|conn| {
+        conn.query_row("SELECT path FROM files WHERE id = ?1", params![id], |r| r.get(0))
+            .optional()
+    })
+}
+
+/// Points a file at a new location. Returns false if no file has that id.
+pub fn set_file_path(id: &str, path: &str) -> rusqlite::Result<bool> {
+    with_conn(|conn| {
+        let now = chrono::Utc::now().to_rfc3339();
+        let n = conn.execute("UPDATE files SET path = ?1, updated_at = ?2 WHERE id = ?3", params![path, now, id])?;
+        Ok(n > 0)
+    })
+}
+
+pub fn set_file_title(id: &str, title: &str) -> rusqlite::Result<()> {
+    with_conn(|conn| {
+        let now = chrono::Utc::now().to_rfc3339();
+        conn.execute("UPDATE files SET title = ?1, updated_at = ?2 WHERE id = ?3", params![title, now, id])?;
+        Ok(())
+    })
+}
+
+pub fn get_file_hash(id: &str) -> rusqlite::Result<Option<String>> {
+    with_conn(|conn| {
+        conn.query_row("SELECT content_hash FROM files WHERE id = ?1", params![id], |r| r.get(0))
+            .optional()
+            .map(Option::flatten)
+    })
+}
+
+pub fn set_file_hash(id: &str, content_hash: &str) -> rusqlite::Result<()> {
+    with_conn(|conn| {
+        conn.execute(
+            "UPDATE files SET content_hash = ?1 WHERE id = ?2",
+            params![content_hash, id],
+        )?;
+        Ok(())
+    })
+}
+
+/// Id and path of the oldest file whose contents hash to `content_hash`.
+pub fn find_file_by_hash(content_hash: &str) -> rusqlite::Result<Option<(String, String)>> {
+    with_conn(|conn| {
+        conn.query_row(
+            "SELECT id, path FROM files WHERE content_hash = ?1 ORDER BY created_at ASC LIMIT 1",
+            params![content_hash],
+            |r| Ok((r.get(0)?, r.get(1)?)),
+        )
+        .optional()
+    })
+}
+
+pub fn set_file_thumb(id: &str, thumb_path: &str) -> rusqlite::Result<()> {
+    with_conn(|conn| {
+        let now = chrono::Utc::now().to_rfc3339();
+        conn.execute(
+            "UPDATE files SET thumb_path = ?1, updated_at = ?2 WHERE id = ?3",
+            params![thumb_path, now, id],
+        )?;
+        Ok(())
+    })
+}
+
+// A file's tags as one column, split back apart by split_tags
+const FILE_TAGS_SQL: &str = "(SELECT GROUP_CONCAT(tag, char(31)) FROM tags WHERE tags.file_id = f.id)";
+
+fn split_tags(joined: Option<String>) -> Vec<String> {
+    let mut tags: Vec<String> = joined
+        .unwrap_or_default()
+        .split('\u{1f}')
+        .filter(|t| !t.is_empty())
+        .map(str::to_string)
+        .collect();
+    tags.sort();
+    tags
+}
+
+/// (frame time, width) the thumbnail was last generated with, where recorded.
+pub fn get_thumb_params(id: &str) -> rusqlite::Result<(Option<f64>, Option<u32>)> {
+    with_conn(|conn| {
+        conn.query_row("SELECT thumb_at, thumb_width FROM files WHERE id = ?1", params![id], |r| Ok((r.get(0)?, r.get(1)?)))
+            .optional()
+            .map(Option::unwrap_or_default)
+    })
+}
+
+pub fn set_thumb_params(id: &str, at: f64, width: u32) -> rusqlite::Result<()> {
+    with_conn(|conn| {
+        conn.execute("UPDATE files SET thumb_at = ?1, thumb_width = ?2 WHERE id = ?3", params![at, width, id])?;
+        Ok(())
+    })
+}
+
+/// Order for `list_files_sorted`; most recent first either way.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum FileSort {
+    Created,
+    Updated,
+}
+
+/// Every file, newest first; with `tag`, only files carrying that tag.
+pub fn list_files(tag: Option<&str>) -> rusqlite::Result<Vec<FileRow>> {
+    list_files_sorted(tag, FileSort::Created)
+}
+
+pub fn list_files_sorted(tag: Option<&str>, sort: FileSort) -> rusqlite::Result<Vec<FileRow>> {
+    let order = match sort {
+        FileSort::Created => "f.created_at DESC",
+        FileSort::Updated => "COALESCE(f.updated_at, f.created_at) DESC, f.created_at DESC",
+    };
+    with_conn(|conn| {
+        let mut stmt = conn.prepare(&format!(
+            "SELECT f.id, f.name, f.path, f.thumb_path, f.created_at, m.duration, {}, f.title,
+                    COALESCE(f.updated_at, f.created_at)
+             FROM files f LEFT JOIN file_metadata m ON m.file_id = f.id
+             WHERE ?1 IS NULL OR EXISTS (SELECT 1 FROM tags t WHERE t.file_id = f.id AND t.tag = ?1)
+             ORDER BY {}",
+            FILE_TAGS_SQL, order
+        ))?;
+        let rows = stmt.query_map(params![tag], |r| {
+            Ok(FileRow {
+                id: r.get(0)?,
+                name: r.get(1)?,
+                path: r.get(2)?,
+                thumb_path: r.get(3)?,
+                created_at: r.get(4)?,
+                duration: r.get(5)?,
+                tags: split_tags(r.get(6)?),
+                title: r.get(7)?,
+                updated_at: r.get(8)?,
+            })
+        })?;
+        rows.collect()
+    })
+}
+
+/// Adds a tag to a file; returns false if the file already had it.
+pub fn add_tag(file_id: &str, tag: &str) -> rusqlite::Result<bool> {
+    with_conn(|conn| {
+        let n = conn.execute("INSERT OR IGNORE INTO tags (file_id, tag) VALUES (?1, ?2)", params![file_id, tag])?;
+        Ok(n > 0)
+    })
+}
+
+/// Removes a tag from a file; returns false if it wasn't there.
+pub fn remove_tag(file_id: &str, tag: &str) -> rusqlite::Result<bool> {
+    with_conn(|conn| {
+        let n = conn.execute("DELETE FROM tags WHERE file_id = ?1 AND tag = ?2", params![file_id, tag])?;
+        Ok(n > 0)
+    })
+}
+
+/// Tags in use with how many files carry each, most used first.
+pub fn list_tags() -> rusqlite::Result<Vec<(String, i64)>> {
+    with_conn(|conn| {
+        let mut stmt = conn.prepare("SELECT tag, COUNT(*) FROM tags GROUP BY tag ORDER BY COUNT(*) DESC, tag ASC")?;
+        let rows = stmt.query_map([], |r| Ok((r.get(0)?, r.get(1)?)))?;
+        rows.collect()
+    })
+}
+
+/// Files that have at least one message, most recently active first, with the
+/// message count and latest message for each.
+pub fn list_files_with_messages() -> rusqlite::Result<Vec<ConversationRow>> {
+    with_conn(|conn| {
+        let mut stmt = conn.prepare(&format!(
+            "SELECT f.id, f.name, f.path, f.thumb_path, f.created_at, m.duration,
+                    s.cnt, s.last_at, last.text, last.is_user_message, {}, f.title,
+                    COALESCE(f.updated_at, f.created_at)
+             FROM files f
+             JOIN (SELECT file_id, COUNT(*) AS cnt, MAX(created_at) AS last_at
+                   FROM messages GROUP BY file_id) s ON s.file_id = f.id
+             JOIN messages last ON last.id = (
+                 SELECT id FROM messages WHERE file_id = f.id
+                 ORDER BY created_at DESC, id DESC LIMIT 1)
+             LEFT JOIN file_metadata m ON m.file_id = f.id
+             ORDER BY s.last_at DESC",
+            FILE_TAGS_SQL
+        ))?;
+        let rows = stmt.query_map([], |r| {
+            Ok(ConversationRow {
+                file: FileRow {
+                    id: r.get(0)?,
+                    name: r.get(1)?,
+                    path: r.get(2)?,
+                    thumb_path: r.get(3)?,
+                    created_at: r.get(4)?,
+                    duration: r.get(5)?,
+                    tags: split_tags(r.get(10)?),
+                    title: r.get(11)?,
+                    updated_at: r.get(12)?,
+                },
+                message_count: r.get(6)?,
+                last_message_at: r.get(7)?,
+                last_message: r.get(8)?,
+                last_is_user: r.get::<_, i64>(9)? != 0,
+            })
+        })?;
+        rows.collect()
+    })
+}
+
+pub fn delete_file(id: &str) -> rusqlite::Result<()> {
+    with_conn(|conn| {
+        // All or nothing, so a failure can't leave a file row without its messages
+        let tx = conn.unchecked_transaction()?;
+        // Messages belong to the file; remove them alongside it
+        tx.execute("DELETE FROM messages WHERE file_id = ?1", params![id])?;
+        tx.execute("DELETE FROM transcripts WHERE file_id = ?1", params![id])?;
+        tx.execute("DELETE FROM transcript_segments WHERE file_id = ?1", params![id])?;
+        tx.execute("DELETE FROM pending_clarifications WHERE file_id = ?1", params![id])?;
+        tx.execute("DELETE FROM clarification_answers WHERE file_id = ?1", params![id])?;
+        tx.execute("DELETE FROM file_metadata WHERE file_id = ?1", params![id])?;
+        tx.execute("DELETE FROM artifacts WHERE file_id = ?1", params![id])?;
+        tx.execute("DELETE FROM key_moments WHERE file_id = ?1", params![id])?;
+        tx.execute("DELETE FROM tags WHERE file_id = ?1", params![id])?;
+        tx.execute("DELETE FROM file_settings WHERE file_id = ?1", params![id])?;
+        tx.execute("DELETE FROM files WHERE id = ?1", params![id])?;
+        tx.commit()
+    })
+}
+
+/// Drops everything derived from a file's contents: transcript, probed metadata
+/// and key moments. The file row and its chat history are kept.
+pub fn invalidate_file_cache(id: &str) -> rusqlite::Result<()> {
+    with_conn(|conn| {
+        conn.execute("DELETE FROM transcripts WHERE file_id = ?1", params![id])?;
+        conn.execute("DELETE FROM transcript_segments WHERE file_id = ?1", params![id])?;
+        conn.execute("DELETE FROM file_metadata WHERE file_id = ?1", params![id])?;
+        conn.execute("DELETE FROM key_moments WHERE file_id = ?1", params![id])?;
+        Ok(())
+    })
+}
+
+pub fn get_file_settings(file_id: &str) -> rusqlite::Result<Option<FileSettingsRow>> {
+    with_conn(|conn| {
+        conn.query_row(
+            "SELECT auto_transcribe, preferred_intents FROM file_settings WHERE file_id = ?1",
+            params![file_id],
+            |r| {
+                let intents: String = r.get(1)?;
+                Ok(FileSettingsRow {
+                    auto_transcribe: r.get::<_, i64>(0)? != 0,
+                    preferred_intents: serde_json::from_str(&intents).unwrap_or_default(),
+                })
+            },
+        )
+        .optional()
+    })
+}
+
+pub fn set_file_settings(file_id: &str, settings: &FileSettingsRow) -> rusqlite::Result<()> {
+    with_conn(|conn| {
+        let intents = serde_json::to_string(&settings.preferred_intents).unwrap_or_else(|_| "[]".to_string());
+        conn.execute(
+            "INSERT INTO file_settings (file_id, auto_transcribe, preferred_intents) VALUES (?1, ?2, ?3)
+             ON CONFLICT(file_id) DO UPDATE SET auto_transcribe = excluded.auto_transcribe,
+             preferred_intents = excluded.preferred_intents",
+            params![file_id, settings.auto_transcribe as i64, intents],
+        )?;
+        Ok(())
+    })
+}
+
+pub fn get_file_metadata(file_id: &str) -> rusqlite::Result<Option<crate::media::VideoMetadata>> {
+    with_conn(|conn| {
+        conn.query_row(
+            "SELECT duration, width, height, video_codec, audio_codec, bitrate, frame_rate, has_video, has_audio
+             FROM file_metadata WHERE file_id = ?1",
+            params![file_id],
+            |r| {
+                Ok(crate::media::VideoMetadata {
+                    duration: r.get(0)?,
+                    width: r.get(1)?,
+                    height: r.get(2)?,
+                    video_codec: r.get(3)?,
+                    audio_codec: r.get(4)?,
+                    bitrate: r.get(5)?,
+                    frame_rate: r.get(6)?,
+                    has_video: r.get::<_, i64>(7)? != 0,
+                    has_audio: r.get::<_, i64>(8)? != 0,
+                })
+            },
+        )
+        .optional()
+    })
+}
+
+pub fn set_file_metadata(file_id: &str, m: &crate::media::VideoMetadata) -> rusqlite::Result<()> {
+    with_conn(|conn| {
+        let now = chrono::Utc::now().to_rfc3339();
+        conn.execute(
+            "INSERT OR REPLACE INTO file_metadata
+             (file_id, duration, width, height, video_codec, audio_codec, bitrate, frame_rate, has_video, has_audio, updated_at)
+             VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10, ?11)",
+            params![
+                file_id,
+                m.duration,
+                m.width,
+                m.height,
+                m.video_codec,
+                m.audio_codec,
+                m.bitrate,
+                m.frame_rate,
+                m.has_video as i64,
+                m.has_audio as i64,
+                now
+            ],
+        )?;
+        Ok(())
+    })
+}
+
+pub fn get_transcript(file_id: &str) -> rusqlite::Result<Option<TranscriptRow>> {
+    with_conn(|conn| {
+        conn.query_row(
+            "SELECT text, content_hash, language FROM transcripts WHERE file_id = ?1",
+            params![file_id],
+            |r| Ok(TranscriptRow { text: r.get(0)?, content_hash: r.get(1)?, language: r.get(2)? }),
+        )
+        .optional()
+    })
+}
+
+pub fn insert_artifact(file_id: &str, kind: &str, path: &str) -> rusqlite::Result<()> {
+    with_conn(|conn| {
+        let id = uuid::Uuid::new_v4().to_string();
+        let now = chrono::Utc::now().to_rfc3339();
+        conn.execute(
+            "INSERT INTO artifacts (id, file_id, kind, path, created_at) VALUES (?1, ?2, ?3, ?4, ?5)",
+            params![id, file_id, kind, path, now],
+        )?;
+        Ok(())
+    })
+}
+
+/// Generated documents for a file, newest first.
+pub fn list_artifacts(file_id: &str) -> rusqlite::Result<Vec<ArtifactRow>> {
+    with_conn(|conn| {
+        let mut stmt = conn.prepare(
+            "SELECT id, kind, path, created_at FROM artifacts
+             WHERE file_id = ?1
+             ORDER BY created_at DESC, id DESC",
+        )?;
+        let rows = stmt.query_map(params![file_id], |r| {
+            Ok(ArtifactRow {
+                id: r.get(0)?,
+                kind: r.get(1)?,
+                path: r.get(2)?,
+                created_at: r.get(3)?,
+            })
+        })?;
+        rows.collect()
+    })
+}
+
+/// Paths of every recorded artifact, across all files.
+pub fn list_artifact_paths() -> rusqlite::Result<Vec<String>> {
+    with_conn(|conn| {
+        let mut stmt = conn.prepare("SELECT path FROM artifacts")?;
+        let rows = stmt.query_map([], |r| r.get(0))?;
+        rows.collect()
+    })
+}
+
+/// Replaces the stored key moments for a file; numbering starts at 1.
+pub fn set_key_moments(file_id: &str, moments: &[(f64, String)]) -> rusqlite::Result<()> {
+    with_conn(|conn| {
+        let now = chrono::Utc::now().to_rfc3339();
+        let tx = conn.unchecked_transaction()?;
+        tx.execute("DELETE FROM key_moments WHERE file_id = ?1", params![file_id])?;
+        for (i, (at, label)) in moments.iter().enumerate() {
+            tx.execute(
+                "INSERT INTO key_moments (file_id, idx, at_secs, label, created_at) VALUES (?1, ?2, ?3, ?4, ?5)",
+                params![file_id, i as i64 + 1, at, label, now],
+            )?;
+        }
+        tx.commit()
+    })
+}
+
+/// Moment `index` (1-based) from the last key-moments list, as (seconds, label).
+pub fn get_key_moment(file_id: &str, index: usize) -> rusqlite::Result<Option<(f64, String)>> {
+    with_conn(|conn| {
+        conn.query_row(
+            "SELECT at_secs, label FROM key_moments WHERE file_id = ?1 AND idx = ?2",
+            params![file_id, index as i64],
+            |r| Ok((r.get(0)?, r.get(1)?)),
+        )
+        .optional()
+    })
+}
+
+/// Stores a transcript and replaces its timed segments in one transaction.
+pub fn set_transcript(
+    file_id: &str,
+    content_hash: &str,
+    transcript: &crate::grpc_client::Transcript,
+) -> rusqlite::Result<()> {
+    with_conn(|conn| {
+        let now = chrono::Utc::now().to_rfc3339();
+        let tx = conn.unchecked_transaction()?;
+        tx.execute(
+            "INSERT INTO transcripts (file_id, content_hash, text, language, updated_at) VALUES (?1, ?2, ?3, ?4, ?5)
+             ON CONFLICT(file_id) DO UPDATE SET content_hash = excluded.content_hash, text = excluded.text,
+             language = excluded.language, updated_at = excluded.updated_at",
+            params![file_id, content_hash, transcript.text, transcript.language, now],
+        )?;
+        tx.execute("DELETE FROM transcript_segments WHERE file_id = ?1", params![file_id])?;
+        for (i, segment) in transcript.segments.iter().enumerate() {
+            tx.execute(
+                "INSERT INTO transcript_segments (file_id, idx, start_secs, end_secs, text) VALUES (?1, ?2, ?3, ?4, ?5)",
+                params![file_id, i as i64, segment.start, segment.end, segment.text],
+            )?;
+        }
+        tx.commit()
+    })
+}
+
+/// Timed segments of the cached transcript, in order. Empty if none were stored.
+pub fn get_transcript_segments(file_id: &str) -> rusqlite::Result<Vec<crate::grpc_client::TranscriptSegment>> {
+    with_conn(|conn| {
+        let mut stmt = conn.prepare(
+            "SELECT start_secs, end_secs, text FROM transcript_segments WHERE file_id = ?1 ORDER BY idx",
+        )?;
+        let rows = stmt.query_map(params![file_id], |r| {
+            Ok(crate::grpc_client::TranscriptSegment { start: r.get(0)?, end: r.get(1)?, text: r.get(2)? })
+        })?;
+        rows.collect()
+    })
+}
+
+/// Remembers the ordered requests offered in the last clarification prompt.
+pub fn set_pending_clarification(file_id: &str, options: &[String]) -> rusqlite::Result<()> {
+    with_conn(|conn| {
+        let json = serde_json::to_string(options).unwrap_or_else(|_| "[]".to_string());
+        let now = chrono::Utc::now().to_rfc3339();
+        conn.execute(
+            "INSERT INTO pending_clarifications (file_id, options, created_at) VALUES (?1, ?2, ?3)
+             ON CONFLICT(file_id) DO UPDATE SET options = excluded.options, created_at = excluded.created_at",
+            params![file_id, json, now],
+        )?;
+        Ok(())
+    })
+}
+
+/// Returns and clears the pending clarification options, if any. Read and delete
+/// share a transaction so two messages can't both answer the same clarification.
+pub fn take_pending_clarification(file_id: &str) -> rusqlite::Result<Option<Vec<String>>> {
+    with_conn(|conn| {
+        // Immediate, so the write lock is held from the read on rather than upgraded after it
+        let tx = Transaction::new_unchecked(conn, TransactionBehavior::Immediate)?;
+        let json: Option<String> = tx
+            .query_row(
+                "SELECT options FROM pending_clarifications WHERE file_id = ?1",
+                params![file_id],
+                |r| r.get(0),
+            )
+            .optional()?;
+        tx.execute("DELETE FROM pending_clarifications WHERE file_id = ?1", params![file_id])?;
+        tx.commit()?;
+        Ok(json.and_then(|j| serde_json::from_str(&j).ok()))
+    })
+}
+
+/// Records that user message `message_id` picked `request` from a clarification,
+/// so regenerating its reply resolves the number the same way.
+pub fn set_clarification_answer(message_id: &str, file_id: &str, request: &str) -> rusqlite::Result<()> {
+    with_conn(|conn| {
+        conn.execute(
+            "INSERT OR REPLACE INTO clarification_answers (message_id, file_id, request) VALUES (?1, ?2, ?3)",
+            params![message_id, file_id, request],
+        )?;
+        Ok(())
+    })
+}
+
+/// The request a numeric reply stood for, if `message_id` was one.
+pub fn clarification_answer(message_id: &str) -> rusqlite::Result<Option<String>> {
+    with_conn(|conn| {
+        conn.query_row(
+            "SELECT request FROM clarification_answers WHERE message_id = ?1",
+            params![message_id],
+            |r| r.get(0),
+        )
+        .optional()
+    })
+}
+
+/// The pending clarification options without consuming them.
+pub fn get_pending_clarification(file_id: &str) -> rusqlite::Result<Option<Vec<String>>> {
+    with_conn(|conn| {
+        let json: Option<String> = conn
+            .query_row(
+                "SELECT options FROM pending_clarifications WHERE file_id = ?1",
+                params![file_id],
+                |r| r.get(0),
+            )
+            .optional()?;
+        Ok(json.and_then(|j| serde_json::from_str(&j).ok()))
+    })
+}
+
+// Quote every term so user input is matched literally rather than parsed as FTS syntax
+fn fts_query(query: &str) -> String {
+    query
+        .split_whitespace()
+        .map(|t| format!("\"{}\"", t.replace('"', "\"\"")))
+        .collect::<Vec<_>>()
+        .join(" ")
+}
+
+/// Escapes `%`, `_` and `\` so user input matches literally in a
+/// `LIKE ... ESCAPE '\'` pattern.
+pub(crate) fn escape_like(input: &str) -> String {
+    let mut out = String::with_capacity(input.len());
+    for c in input.chars() {
+        if matches!(c, '\\' | '%' | '_') {
+            out.push('\\');
+        }
+        out.push(c);
+    }
+    out
+}
+
+fn like_snippet(text: &str, query: &str) -> String {
+    const CONTEXT: usize = 40;
+    let lower = text.to_lowercase();
+    let start = match lower.find(&query.to_lowercase()) {
+        // Lowercasing can shift byte offsets for some scripts; only trust it when it lines up
+        Some(pos) if lower.len() == text.len() => pos,
+        _ => 0,
+    };
+    let mut from = start.saturating_sub(CONTEXT);
+    while !text.is_char_boundary(from) { from -= 1; }
+    let mut to = (start + query.len() + CONTEXT).min(text.len());
+    while !text.is_char_boundary(to) { to += 1; }
+    let mut out = String::new();
+    if from > 0 { out.push('…'); }
+    out.push_str(&text[from..to]);
+    if to < text.len() { out.push('…'); }
+    out
+}
+
+/// Searches message text, ranked by relevance with FTS5 or by recency otherwise.
+pub fn search_messages(
+    query: &str,
+    file_id: Option<&str>,
+    limit: i64,
+) -> rusqlite::Result<Vec<SearchHit>> {
+    let query = query.trim();
+    if query.is_empty() {
+        return Ok(Vec::new());
+    }
+    let limit = limit.max(1);
+    with_conn(|conn| search_in(conn, query, file_id, limit))
+}
+
+// FTS5 ranking when the index exists, otherwise a plain LIKE scan, newest first
+fn search_in(conn: &Connection, query: &str, file_id: Option<&str>, limit: i64) -> rusqlite::Result<Vec<SearchHit>> {
+    if fts_available(conn) {
+        let mut stmt = conn.prepare(
+            "SELECT m.id, m.file_id, m.text, m.is_user_message, m.created_at,
+                    snippet(messages_fts, 0, '**', '**', '…', 12)
+             FROM messages_fts JOIN messages m ON m.rowid = messages_fts.rowid
+             WHERE messages_fts MATCH ?1 AND (?2 IS NULL OR m.file_id = ?2)
+             ORDER BY rank
+             LIMIT ?3",
+        )?;
+        let rows = stmt.query_map(params![fts_query(query), file_id, limit], |r| {
+            Ok(SearchHit {
+                id: r.get(0)?,
+                file_id: r.get(1)?,
+                text: r.get(2)?,
+                is_user: r.get::<_, i64>(3)? != 0,
+                created_at: r.get(4)?,
+                snippet: r.get(5)?,
+            })
+        })?;
+        return rows.collect();
+    }
+    let mut stmt = conn.prepare(
+        "SELECT id, file_id, text, is_user_message, created_at FROM messages
+         WHERE text LIKE '%' || ?1 || '%' ESCAPE '\\' AND (?2 IS NULL OR file_id = ?2)
+         ORDER BY created_at DESC, id DESC
+         LIMIT ?3",
+    )?;
+    let rows = stmt.query_map(params![escape_like(query), file_id, limit], |r| {
+        let text: String = r.get(2)?;
+        Ok(SearchHit {
+            id: r.get(0)?,
+            file_id: r.get(1)?,
+            snippet: like_snippet(&text, query),
+            text,
+            is_user: r.get::<_, i64>(3)? != 0,
+            created_at: r.get(4)?,
+        })
+    })?;
+    rows.collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn migrated() -> Connection {
+        let conn = Connection::open_in_memory().unwrap();
+        migrate(&conn).unwrap();
+        conn
+    }
+
+    fn add(conn: &Connection, id: &str, file_id: &str, text: &str, created_at: &str) {
+        conn.execute(
+            "INSERT INTO messages (id, file_id, text, is_user_message, created_at) VALUES (?1, ?2, ?3, 1, ?4)",
+            params![id, file_id, text, created_at],
+        )
+        .unwrap();
+    }
+
+    fn ids(page: &MessagesPage) -> Vec<String> {
+        page.messages.iter().map(|m| m["id"].as_str().unwrap().to_string()).collect()
+    }
+
+    fn walk(conn: &Connection, direction: Direction) -> Vec<String> {
+        let mut all = Vec::new();
+        let mut cursor: Option<String> = None;
+        loop {
+            let page = messages_page(conn, "f1", 2, cursor.as_deref(), direction).unwrap();
+            all.extend(ids(&page));
+            match page.next_cursor {
+                Some(next) => cursor = Some(next),
+                None => return all,
+            }
+        }
+    }
+
+    #[test]
+    fn fresh_database_runs_every_migration() {
+        let conn = migrated();
+        let version: i64 = conn.pragma_query_value(None, "user_version", |r| r.get(0)).unwrap();
+        assert_eq!(version, MIGRATIONS.len() as i64);
+        assert!(has_table(&conn, "idx_messages_file_created").unwrap());
+        assert!(!has_table(&conn, "idx_messages_file_created_id").unwrap());
+        assert!(has_column(&conn, "files", "thumb_width").unwrap());
+        // Running again is a no-op
+        migrate(&conn).unwrap();
+    }
+
+    #[test]
+    fn legacy_database_is_stamped_and_brought_up_to_date() {
+        let conn = Connection::open_in_memory().unwrap();
+        for step in &MIGRATIONS[..4] {
+            step(&conn).unwrap();
+        }
+        add(&conn, "m1", "f1", "kept across the upgrade", "2024-01-01T00:00:00Z");
+        assert_eq!(detect_legacy_version(&conn).unwrap(), 4);
+        migrate(&conn).unwrap();
+        let version: i64 = conn.pragma_query_value(None, "user_version", |r| r.get(0)).unwrap();
+        assert_eq!(version, MIGRATIONS.len() as i64);
+        assert_eq!(ids(&messages_page(&conn, "f1", 10, None, Direction::Asc).unwrap()), vec!["m1"]);
+    }
+
+    #[test]
+    fn pagination_uses_the_file_time_index() {
+        let conn = migrated();
+        let mut stmt = conn
+            .prepare(
+                "EXPLAIN QUERY PLAN SELECT id, text, is_user_message, created_at FROM messages
+                 WHERE file_id = ?1 AND (?2 IS NULL OR (created_at, id) > (?2, ?3))
+                 ORDER BY created_at ASC, id ASC LIMIT ?4",
+            )
+            .unwrap();
+        let plan: Vec<String> = stmt
+            .query_map(params!["f1", "2024", "", 10], |r| r.get::<_, String>(3))
+            .unwrap()
+            .collect::<Result<_, _>>()
+            .unwrap();
+        assert!(plan.iter().any(|step| step.contains("idx_messages_file_created")), "{:?}", plan);
+    }
+
+    #[test]
+    fn cursor_pages_cover_every_message_once() {
+        let conn = migrated();
+        // b, c and d share a timestamp, so a page boundary falls between equal times
+        add(&conn, "a", "f1", "one", "2024-01-01T00:00:00Z");
+        add(&conn, "b", "f1", "two", "2024-01-01T00:00:01Z");
+        add(&conn, "c", "f1", "three", "2024-01-01T00:00:01Z");
+        add(&conn, "d", "f1", "four", "2024-01-01T00:00:01Z");
+        add(&conn, "e", "f1", "five", "2024-01-01T00:00:02Z");
+        add(&conn, "x", "f2", "other file", "2024-01-01T00:00:01Z");
+
+        assert_eq!(walk(&conn, Direction::Asc), vec!["a", "b", "c", "d", "e"]);
+        assert_eq!(walk(&conn, Direction::Desc), vec!["e", "d", "c", "b", "a"]);
+
+        let first = messages_page(&conn, "f1", 2, None, Direction::Asc).unwrap();
+        assert_eq!(first.next_cursor.as_deref(), Some("2024-01-01T00:00:01Z|b"));
+        // A bare timestamp from an older client includes every row at that time
+        let bare = messages_page(&conn, "f1", 10, Some("2024-01-01T00:00:01Z"), Direction::Asc).unwrap();
+        assert_eq!(ids(&bare), vec!["b", "c", "d", "e"]);
+        let bare = messages_page(&conn, "f1", 10, Some("2024-01-01T00:00:01Z"), Direction::Desc).unwrap();
+        assert_eq!(ids(&bare), vec!["d", "c", "b", "a"]);
+        assert!(messages_page(&conn, "f1", 10, None, Direction::Asc).unwrap().next_cursor.is_none());
+    }
+
+    #[test]
+    fn search_falls_back_to_like_without_fts() {
+        let conn = migrated();
+        add(&conn, "a", "f1", "Revenue grew 50% in Q3", "2024-01-01T00:00:00Z");
+        add(&conn, "b", "f1", "revenue_total is a column", "2024-01-01T00:00:01Z");
+        add(&conn, "c", "f2", "Revenue dipped", "2024-01-01T00:00:02Z");
+        assert!(!fts_available(&conn));
+
+        let hit_ids = |hits: Vec<SearchHit>| hits.into_iter().map(|h| h.id).collect::<Vec<_>>();
+        // LIKE: newest first, case-insensitive, wildcards taken literally
+        assert_eq!(hit_ids(search_in(&conn, "revenue", None, 10).unwrap()), vec!["c", "b", "a"]);
+        assert_eq!(hit_ids(search_in(&conn, "50%", None, 10).unwrap()), vec!["a"]);
+        assert_eq!(hit_ids(search_in(&conn, "e_t", None, 10).unwrap()), vec!["b"]);
+        assert_eq!(hit_ids(search_in(&conn, "revenue", Some("f2"), 10).unwrap()), vec!["c"]);
+        let hit = search_in(&conn, "grew", None, 10).unwrap().remove(0);
+        assert!(hit.snippet.contains("grew"));
+
+        // Both wildcards at once, and backslashes, the LIKE escape character itself
+        add(&conn, "d", "f3", "Use code 50%_off at checkout", "2024-01-01T00:00:03Z");
+        add(&conn, "e", "f3", "Everything 50% off", "2024-01-01T00:00:04Z");
+        add(&conn, "f", "f3", "50 percent_off", "2024-01-01T00:00:05Z");
+        add(&conn, "g", "f3", "Saved to C:\\Users\\demo\\clip.mp4", "2024-01-01T00:00:06Z");
+        add(&conn, "h", "f3", "Saved to C:/Users/demo/clip.mp4", "2024-01-01T00:00:07Z");
+        assert_eq!(hit_ids(search_in(&conn, "50%_off", None, 10).unwrap()), vec!["d"]);
+        assert_eq!(hit_ids(search_in(&conn, "users\\demo", None, 10).unwrap()), vec!["g"]);
+        assert_eq!(hit_ids(search_in(&conn, "demo\\", None, 10).unwrap()), vec!["g"]);
+
+        // SQLite builds without FTS5 stay on LIKE; the assertions above cover them
+        if init_fts(&conn).is_ok() {
+            assert!(fts_available(&conn));
+            // The tokenizer splits on "_", so "revenue_total" counts as the word too
+            let mut found = hit_ids(search_in(&conn, "revenue", None, 10).unwrap());
+            found.sort();
+            assert_eq!(found, vec!["a", "b", "c"]);
+            assert_eq!(hit_ids(search_in(&conn, "revenue", Some("f2"), 10).unwrap()), vec!["c"]);
+            // Quoted, so FTS syntax in the query is matched as text rather than parsed
+            assert!(search_in(&conn, "grew OR NEAR(", None, 10).is_ok());
+        }
+    }
+}
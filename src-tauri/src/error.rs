@@ -0,0 +1,69 @@
+use serde::Serialize;
+
+/// Error returned by Tauri commands. Serialized as `{ "kind": "notFound", "message": "…" }`
+/// so the frontend can branch on `kind`; Display gives just the message.
+#[derive(Clone, Debug, Serialize)]
+#[serde(tag = "kind", content = "message", rename_all = "camelCase")]
+pub enum AppError {
+    /// A file, message or other record that doesn't exist (or is gone from disk)
+    NotFound(String),
+    /// SQLite failure
+    Db(String),
+    /// An agent call failed: unreachable, timed out or reported an error
+    Agent(String),
+    /// Filesystem or external tool (ffmpeg, file manager) failure
+    Io(String),
+    /// Bad input from the caller
+    Validation(String),
+}
+
+impl AppError {
+    pub fn message(&self) -> &str {
+        match self {
+            AppError::NotFound(m) | AppError::Db(m) | AppError::Agent(m) | AppError::Io(m) | AppError::Validation(m) => m,
+        }
+    }
+}
+
+impl std::fmt::Display for AppError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(self.message())
+    }
+}
+
+impl std::error::Error for AppError {}
+
+impl From<rusqlite::Error> for AppError {
+    fn from(e: rusqlite::Error) -> Self {
+        AppError::Db(e.to_string())
+    }
+}
+
+impl From<std::io::Error> for AppError {
+    fn from(e: std::io::Error) -> Self {
+        match e.kind() {
+            std::io::ErrorKind::NotFound => AppError::NotFound(e.to_string()),
+            _ => AppError::Io(e.to_string()),
+        }
+    }
+}
+
+// A spawn_blocking task panicked or was cancelled
+impl From<tokio::task::JoinError> for AppError {
+    fn from(e: tokio::task::JoinError) -> Self {
+        AppError::Io(e.to_string())
+    }
+}
+
+impl From<crate::grpc_client::RpcError> for AppError {
+    fn from(e: crate::grpc_client::RpcError) -> Self {
+        AppError::Agent(e.to_string())
+    }
+}
+
+// Lets String-returning helpers call commands with `?`
+impl From<AppError> for String {
+    fn from(e: AppError) -> Self {
+        e.message().to_string()
+    }
+}
@@ -1,3 +1,4 @@
+use std::time::Duration;
 use tonic::Request;
 use auralink::transcription_service_client::TranscriptionServiceClient;
 use auralink::vision_service_client::VisionServiceClient;
@@ -8,45 +9,484 @@ pub mod auralink {
     tonic::include_proto!("auralink");
 }
 
-#[allow(dead_code)]
+/// Agent addresses, overridable via AURALINK_{TRANSCRIPTION,VISION,GENERATION}_ADDR.
+#[derive(Clone, Debug)]
+pub struct GrpcEndpoints {
+    pub transcription: String,
+    pub vision: String,
+    pub generation: String,
+}
+
+impl GrpcEndpoints {
+    pub fn from_env() -> Self {
+        let read = |key: &str, default: &str| {
+            let v = std::env::var(key).unwrap_or_default().trim().to_string();
+            if v.is_empty() {
+                default.to_string()
+            } else if v.contains("://") {
+                v
+            } else {
+                format!("http://{}", v)
+            }
+        };
+        Self {
+            transcription: read("AURALINK_TRANSCRIPTION_ADDR", "http://127.0.0.1:50051"),
+            vision: read("AURALINK_VISION_ADDR", "http://127.0.0.1:50052"),
+            generation: read("AURALINK_GENERATION_ADDR", "http://127.0.0.1:50053"),
+        }
+    }
+
+    /// Endpoints as configured, with any ports moved at startup applied.
+    pub fn current() -> Self {
+        let mut endpoints = Self::from_env();
+        if let Ok(overrides) = ENDPOINT_OVERRIDES.lock() {
+            for (name, addr) in overrides.iter() {
+                endpoints.set(name, addr.clone());
+            }
+        }
+        endpoints
+    }
+
+    fn set(&mut self, name: &str, addr: String) {
+        match name {
+            "transcription" => self.transcription = addr,
+            "vision" => self.vision = addr,
+            "generation" => self.generation = addr,
+            _ => {}
+        }
+    }
+
+    /// (agent name, address) pairs in startup order
+    pub fn all(&self) -> [(&'static str, &str); 3] {
+        [
+            ("transcription", self.transcription.as_str()),
+            ("vision", self.vision.as_str()),
+            ("generation", self.generation.as_str()),
+        ]
+    }
+}
+
+// Agents started on a different port than configured (the default was taken)
+static ENDPOINT_OVERRIDES: std::sync::Mutex<Vec<(&'static str, String)>> = std::sync::Mutex::new(Vec::new());
+
+/// Points the named agent's client at `addr` for the rest of this run.
+pub fn override_endpoint(name: &'static str, addr: String) {
+    if let Ok(mut overrides) = ENDPOINT_OVERRIDES.lock() {
+        overrides.retain(|(n, _)| *n != name);
+        overrides.push((name, addr));
+    }
+}
+
+/// Per-call deadlines, overridable via AURALINK_{TRANSCRIPTION,VISION,GENERATION}_TIMEOUT_SECS.
+/// Transcription gets the longest budget since it scales with recording length.
+#[derive(Clone, Copy, Debug)]
+pub struct RpcDeadlines {
+    pub transcription: Duration,
+    pub vision: Duration,
+    pub generation: Duration,
+}
+
+impl RpcDeadlines {
+    pub fn from_env() -> Self {
+        let read = |key: &str, default: u64| {
+            let secs = std::env::var(key)
+                .ok()
+                .and_then(|v| v.trim().parse::<u64>().ok())
+                .filter(|s| *s > 0)
+                .unwrap_or(default);
+            Duration::from_secs(secs)
+        };
+        Self {
+            transcription: read("AURALINK_TRANSCRIPTION_TIMEOUT_SECS", 600),
+            vision: read("AURALINK_VISION_TIMEOUT_SECS", 60),
+            generation: read("AURALINK_GENERATION_TIMEOUT_SECS", 120),
+        }
+    }
+}
+
+/// gRPC message size caps in bytes. AURALINK_MAX_MESSAGE_MB sets one limit for
+/// every client (clamped to 4–1024 MB); unset, agents get 50 MB and chat 20 MB.
+#[derive(Clone, Copy, Debug)]
+pub struct MessageLimits {
+    pub agents: usize,
+    pub chat: usize,
+}
+
+impl MessageLimits {
+    pub fn from_env() -> Self {
+        const MB: usize = 1024 * 1024;
+        let configured = std::env::var("AURALINK_MAX_MESSAGE_MB")
+            .ok()
+            .and_then(|v| v.trim().parse::<usize>().ok())
+            .map(|mb| mb.clamp(4, 1024) * MB);
+        let limits = match configured {
+            Some(bytes) => Self { agents: bytes, chat: bytes },
+            None => Self { agents: 50 * MB, chat: 20 * MB },
+        };
+        // Clients are rebuilt on reconnect; only report the value once
+        static LOGGED: std::sync::Once = std::sync::Once::new();
+        LOGGED.call_once(|| {
+            println!("[Tauri] gRPC message limit: {} MB (chat {} MB)", limits.agents / MB, limits.chat / MB);
+        });
+        limits
+    }
+}
+
+/// Retry schedule for agent calls: `attempts` tries in total, waiting `base`,
+/// then double that each time up to `max`, plus up to 25% random jitter so
+/// clients don't reconnect in lockstep. Attempts come from AURALINK_RPC_ATTEMPTS.
+#[derive(Clone, Copy, Debug)]
+pub struct RetryPolicy {
+    pub attempts: u32,
+    pub base: Duration,
+    pub max: Duration,
+}
+
+impl Default for RetryPolicy {
+    fn default() -> Self {
+        Self { attempts: 4, base: Duration::from_millis(250), max: Duration::from_secs(4) }
+    }
+}
+
+impl RetryPolicy {
+    pub fn from_env() -> Self {
+        let mut policy = Self::default();
+        if let Some(n) = std::env::var("AURALINK_RPC_ATTEMPTS").ok().and_then(|v| v.trim().parse::<u32>().ok()) {
+            policy.attempts = n.max(1);
+        }
+        policy
+    }
+
+    /// Wait before retry number `retry` (1-based).
+    pub fn delay(&self, retry: u32) -> Duration {
+        let backoff = self.base.saturating_mul(2u32.saturating_pow(retry.saturating_sub(1))).min(self.max);
+        // RandomState is seeded randomly per instance; good enough for jitter without a rand dependency
+        use std::hash::{BuildHasher, Hasher};
+        let noise = std::collections::hash_map::RandomState::new().build_hasher().finish();
+        let jitter_ms = backoff.as_millis() as u64 / 4;
+        backoff + Duration::from_millis(if jitter_ms == 0 { 0 } else { noise % (jitter_ms + 1) })
+    }
+
+    /// Runs `f` until it succeeds, fails with a non-transient error, or runs out of attempts.
+    /// Transient failures drop the shared clients so each retry dials a fresh channel.
+    pub async fn run<T, F, Fut>(&self, mut f: F) -> Result<T, RpcError>
+    where
+        F: FnMut() -> Fut,
+        Fut: std::future::Future<Output = Result<T, String>>,
+    {
+        let mut attempt = 1;
+        loop {
+            match f().await.map_err(|e| RpcError::classify(&e)) {
+                Ok(v) => return Ok(v),
+                Err(RpcError::Transient) => {
+                    // The agent may have restarted behind a stale channel; reconnect on the next call
+                    GrpcClients::reset().await;
+                    if attempt >= self.attempts {
+                        return Err(RpcError::Transient);
+                    }
+                    tokio::time::sleep(self.delay(attempt)).await;
+                    attempt += 1;
+                }
+                Err(e) => return Err(e),
+            }
+        }
+    }
+}
+
+/// How an agent call failed, as far as retrying is concerned. Display gives the
+/// message shown in chat.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum RpcError {
+    /// Agent unreachable; a later attempt may succeed
+    Transient,
+    /// The call used its whole deadline; retrying would only wait again
+    TimedOut,
+    /// Request or response over the gRPC message size limit
+    TooLarge,
+    /// Anything retrying won't fix (missing file, bad argument, agent-side failure)
+    Permanent(String),
+}
+
+impl RpcError {
+    pub fn classify(err: &str) -> Self {
+        let lower = err.to_lowercase();
+        // Per-call deadline hit (local timeout or DeadlineExceeded from the agent)
+        if lower.contains("timed out") {
+            return RpcError::TimedOut;
+        }
+        // Common connectivity failures
+        if lower.contains("transport") || lower.contains("unavailable") || lower.contains("deadline")
+            || lower.contains("connection refused") || lower.contains("broken pipe")
+        {
+            return RpcError::Transient;
+        }
+        // Message too large from gRPC (e.g., sending whole video bytes)
+        if lower.contains("resourceexhausted") || lower.contains("message larger than max") {
+            return RpcError::TooLarge;
+        }
+        RpcError::Permanent(err.to_string())
+    }
+}
+
+impl std::fmt::Display for RpcError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            RpcError::Transient => write!(f, "agent unavailable"),
+            RpcError::TimedOut => write!(f, "agent timed out; it may still be loading models, please try again shortly"),
+            RpcError::TooLarge => write!(f, "request too large for a single call; try a shorter clip or let me extract audio automatically"),
+            RpcError::Permanent(msg) => {
+                // Just the agent's own words from a gRPC status, without code, details and metadata
+                let text = status_message(msg).unwrap_or_else(|| msg.clone());
+                match text.trim() {
+                    "" => write!(f, "couldn’t complete this right now; please try again"),
+                    text => write!(f, "{}", text),
+                }
+            }
+        }
+    }
+}
+
+// The message of a status as rpc_err renders it: `status: Internal, message: "...", details: ...`,
+// with the message Debug-quoted. None for text that isn't a rendered status.
+fn status_message(text: &str) -> Option<String> {
+    let start = text.find("message: \"")? + "message: \"".len();
+    let mut message = String::new();
+    let mut chars = text[start..].chars();
+    while let Some(c) = chars.next() {
+        match c {
+            '"' => return Some(message),
+            '\\' => match chars.next()? {
+                'n' => message.push('\n'),
+                'r' => message.push('\r'),
+                't' => message.push('\t'),
+                escaped => message.push(escaped),
+            },
+            c => message.push(c),
+        }
+    }
+    None
+}
+
+fn rpc_err(status: tonic::Status) -> String {
+    if status.code() == tonic::Code::DeadlineExceeded {
+        "agent timed out".to_string()
+    } else {
+        status.to_string()
+    }
+}
+
+// The grpc-timeout header lets the agent give up too, but a server that accepted the
+// connection and then stalled may never answer, so enforce the deadline locally as well.
+async fn with_deadline<T, F>(limit: Duration, fut: F) -> Result<T, String>
+where
+    F: std::future::Future<Output = Result<T, String>>,
+{
+    tokio::time::timeout(limit, fut)
+        .await
+        .map_err(|_| format!("agent timed out after {}s", limit.as_secs()))?
+}
+
+fn timed<T>(message: T, limit: Duration) -> Request<T> {
+    let mut request = Request::new(message);
+    request.set_timeout(limit);
+    if let Some(token) = agent_token() {
+        request.metadata_mut().insert("authorization", token.clone());
+    }
+    request
+}
+
+/// "Bearer <AURALINK_AGENT_TOKEN>" metadata, read once; None when unset or not valid ASCII.
+fn agent_token() -> Option<&'static tonic::metadata::MetadataValue<tonic::metadata::Ascii>> {
+    static TOKEN: std::sync::OnceLock<Option<tonic::metadata::MetadataValue<tonic::metadata::Ascii>>> = std::sync::OnceLock::new();
+    TOKEN.get_or_init(|| {
+        let token = std::env::var("AURALINK_AGENT_TOKEN").ok()?;
+        let token = token.trim();
+        if token.is_empty() { return None; }
+        match format!("Bearer {}", token).parse() {
+            Ok(v) => Some(v),
+            Err(_) => {
+                println!("[Tauri] AURALINK_AGENT_TOKEN is not valid header text; sending no token");
+                None
+            }
+        }
+    }).as_ref()
+}
+
+// With a CA configured, remote http:// agents are upgraded to TLS. Loopback ones stay
+// plaintext: the agents we spawn ourselves only listen on insecure ports.
+fn tls_upgraded(addr: String, has_ca: bool) -> String {
+    match addr.strip_prefix("http://") {
+        Some(rest) if has_ca && !is_loopback(&addr) => format!("https://{}", rest),
+        _ => addr,
+    }
+}
+
+/// Channel builder for an agent address. TLS is used for https:// endpoints, or for
+/// every remote endpoint when AURALINK_TLS_CA names a PEM file to trust; otherwise the
+/// connection is plaintext, which is what the local agents speak.
+fn endpoint(addr: String) -> Result<tonic::transport::Endpoint, Box<dyn std::error::Error>> {
+    let ca_path = std::env::var("AURALINK_TLS_CA").ok().filter(|p| !p.trim().is_empty());
+    let addr = tls_upgraded(addr, ca_path.is_some());
+    // Keep-alive pings notice a dead agent while the channel is idle instead of on the next call
+    let endpoint = tonic::transport::Channel::from_shared(addr.clone())?
+        .connect_timeout(std::time::Duration::from_secs(2))
+        .http2_keep_alive_interval(Duration::from_secs(30))
+        .keep_alive_timeout(Duration::from_secs(10))
+        .keep_alive_while_idle(true);
+    if !addr.starts_with("https://") {
+        return Ok(endpoint);
+    }
+    let tls = match ca_path {
+        Some(path) => {
+            let pem = std::fs::read(path.trim()).map_err(|e| format!("AURALINK_TLS_CA {}: {}", path.trim(), e))?;
+            tonic::transport::ClientTlsConfig::new().ca_certificate(tonic::transport::Certificate::from_pem(pem))
+        }
+        None => tonic::transport::ClientTlsConfig::new().with_native_roots(),
+    };
+    Ok(endpoint.tls_config(tls)?)
+}
+
+fn host_port(addr: &str) -> &str {
+    let without_scheme = addr.split("://").nth(1).unwrap_or(addr);
+    without_scheme.split('/').next().unwrap_or("")
+}
+
+/// "host:port" form of an endpoint, suitable for a TCP probe
+pub fn socket_target(addr: &str) -> String {
+    let hp = host_port(addr);
+    let has_port = if hp.starts_with('[') { hp.contains("]:") } else { hp.contains(':') };
+    if has_port {
+        hp.to_string()
+    } else if addr.starts_with("https://") {
+        format!("{}:443", hp)
+    } else {
+        format!("{}:80", hp)
+    }
+}
+
+pub fn port_of(addr: &str) -> Option<u16> {
+    socket_target(addr).rsplit(':').next().and_then(|p| p.parse().ok())
+}
+
+/// The same endpoint on another port, e.g. "http://127.0.0.1:50051" -> "...:50061"
+pub fn with_port(addr: &str, port: u16) -> String {
+    let hp = host_port(addr);
+    let host = match socket_target(addr).rsplit_once(':') {
+        Some((host, _)) => host.to_string(),
+        None => hp.to_string(),
+    };
+    let scheme = addr.split_once("://").map(|(s, _)| s).unwrap_or("http");
+    format!("{}://{}:{}", scheme, host, port)
+}
+
+/// Where a generated document ended up, as far as this machine can tell.
+pub enum ArtifactLocation {
+    Local(std::path::PathBuf),
+    Remote(String),
+}
+
+pub fn is_loopback(addr: &str) -> bool {
+    let hp = host_port(addr);
+    let host = if hp.starts_with('[') {
+        hp.split(']').next().unwrap_or("").trim_start_matches('[')
+    } else {
+        hp.split(':').next().unwrap_or("")
+    };
+    matches!(host, "127.0.0.1" | "localhost" | "::1")
+}
+
+/// Turns a path reported by the generation agent into an absolute local path.
+/// Relative paths are tried against the configured output dir, then the agent's
+/// working dir (local agents inherit ours). Remote agents are not resolved.
+pub fn resolve_output_path(raw: &str) -> Result<ArtifactLocation, String> {
+    let raw = raw.trim();
+    if raw.is_empty() {
+        return Err("generation agent did not return an output path".to_string());
+    }
+    if !is_loopback(&GrpcEndpoints::current().generation) {
+        return Ok(ArtifactLocation::Remote(raw.to_string()));
+    }
+    let p = std::path::PathBuf::from(raw);
+    let mut candidates = Vec::new();
+    if p.is_absolute() {
+        candidates.push(p.clone());
+    } else {
+        if let Some(dir) = crate::settings::get().output_dir {
+            candidates.push(std::path::PathBuf::from(dir).join(&p));
+        }
+        if let Ok(cwd) = std::env::current_dir() {
+            candidates.push(cwd.join(&p));
+        }
+    }
+    candidates
+        .into_iter()
+        .find(|c| c.exists())
+        .map(|c| ArtifactLocation::Local(c.canonicalize().unwrap_or(c)))
+        .ok_or_else(|| format!("generated file not found at {}", raw))
+}
+
+fn describe_generated(label: &str, raw_path: &str) -> Result<String, String> {
+    match resolve_output_path(raw_path)? {
+        ArtifactLocation::Local(p) => Ok(format!("{} generated at {}", label, p.to_string_lossy())),
+        ArtifactLocation::Remote(p) => Ok(format!("{} generated on the remote agent (path: {})", label, p)),
+    }
+}
+
+#[derive(Clone)]
 pub struct GrpcClients {
     pub transcription: TranscriptionServiceClient<tonic::transport::Channel>,
     pub vision: VisionServiceClient<tonic::transport::Channel>,
     pub generation: GenerationServiceClient<tonic::transport::Channel>,
-    #[allow(dead_code)]
     pub chat: ChatServiceClient<tonic::transport::Channel>,
 }
 
+// Process-wide clients; tonic channels multiplex and are cheap to clone
+static SHARED: tokio::sync::Mutex<Option<GrpcClients>> = tokio::sync::Mutex::const_new(None);
+
 impl GrpcClients {
+    /// Returns a clone of the shared clients, building them on first use.
+    /// A bad endpoint is not cached, so the next call tries again.
+    pub async fn shared() -> Result<Self, String> {
+        let mut guard = SHARED.lock().await;
+        if let Some(clients) = guard.as_ref() {
+            return Ok(clients.clone());
+        }
+        let clients = Self::new().await.map_err(|e| e.to_string())?;
+        *guard = Some(clients.clone());
+        Ok(clients)
+    }
+
+    /// Drops the shared clients so the next call reconnects with current settings.
+    pub async fn reset() {
+        *SHARED.lock().await = None;
+    }
+
     pub async fn new() -> Result<Self, Box<dyn std::error::Error>> {
-        // Each service runs on a different port - create separate channels with short connect timeout
-        let transcription_channel = tonic::transport::Channel::from_static("http://127.0.0.1:50051")
-            .connect_timeout(std::time::Duration::from_secs(2))
-            .connect()
-            .await?;
-        let vision_channel = tonic::transport::Channel::from_static("http://127.0.0.1:50052")
-            .connect_timeout(std::time::Duration::from_secs(2))
-            .connect()
-            .await?;
-        let generation_channel = tonic::transport::Channel::from_static("http://127.0.0.1:50053")
-            .connect_timeout(std::time::Duration::from_secs(2))
-            .connect()
-            .await?;
+        Self::connect(GrpcEndpoints::current())
+    }
+
+    // Each service runs on a different port. Channels connect lazily, on their first
+    // call, so an agent that's disabled or down only fails the calls made to it.
+    fn connect(endpoints: GrpcEndpoints) -> Result<Self, Box<dyn std::error::Error>> {
+        let transcription_channel = endpoint(endpoints.transcription)?.connect_lazy();
+        let vision_channel = endpoint(endpoints.vision)?.connect_lazy();
+        let generation_channel = endpoint(endpoints.generation)?.connect_lazy();
         // Chat service shares port with transcription (or can be separate)
         let chat_channel = transcription_channel.clone();
         
+        let limits = MessageLimits::from_env();
         let transcription = TranscriptionServiceClient::new(transcription_channel)
-            .max_decoding_message_size(50 * 1024 * 1024)
-            .max_encoding_message_size(50 * 1024 * 1024);
+            .max_decoding_message_size(limits.agents)
+            .max_encoding_message_size(limits.agents);
         let vision = VisionServiceClient::new(vision_channel)
-            .max_decoding_message_size(50 * 1024 * 1024)
-            .max_encoding_message_size(50 * 1024 * 1024);
+            .max_decoding_message_size(limits.agents)
+            .max_encoding_message_size(limits.agents);
         let generation = GenerationServiceClient::new(generation_channel)
-            .max_decoding_message_size(50 * 1024 * 1024)
-            .max_encoding_message_size(50 * 1024 * 1024);
+            .max_decoding_message_size(limits.agents)
+            .max_encoding_message_size(limits.agents);
         let chat = ChatServiceClient::new(chat_channel)
-            .max_decoding_message_size(20 * 1024 * 1024)
-            .max_encoding_message_size(20 * 1024 * 1024);
+            .max_decoding_message_size(limits.chat)
+            .max_encoding_message_size(limits.chat);
 
         Ok(Self {
             transcription,
@@ -57,103 +497,308 @@ impl GrpcClients {
     }
 }
 
-pub async fn transcribe_video(file_id: String, audio_data: Vec<u8>) -> Result<String, String> {
-    let mut clients = GrpcClients::new().await.map_err(|e| e.to_string())?;
+/// One timed piece of a transcript, in seconds from the start of the recording.
+#[derive(Clone, Debug)]
+pub struct TranscriptSegment {
+    pub start: f64,
+    pub end: f64,
+    pub text: String,
+}
+
+/// Transcript text plus the language Whisper detected, when the agent reported one.
+/// `segments` is empty if the agent didn't report timings.
+#[derive(Clone, Debug, Default)]
+pub struct Transcript {
+    pub text: String,
+    pub language: Option<String>,
+    pub segments: Vec<TranscriptSegment>,
+}
+
+impl Transcript {
+    // Keeps `text` as the flat transcript: the segments joined in order
+    fn push_segment(&mut self, start: f64, end: f64, text: &str) {
+        let text = text.trim();
+        if text.is_empty() { return; }
+        if !self.text.is_empty() { self.text.push(' '); }
+        self.text.push_str(text);
+        self.segments.push(TranscriptSegment { start, end, text: text.to_string() });
+    }
+}
+
+fn detected_language(code: &str) -> Option<String> {
+    let code = code.trim();
+    if code.is_empty() || code.eq_ignore_ascii_case("unknown") { None } else { Some(code.to_lowercase()) }
+}
+
+impl From<auralink::TranscribeResponse> for Transcript {
+    fn from(response: auralink::TranscribeResponse) -> Self {
+        let mut transcript = Transcript { language: detected_language(&response.language), ..Default::default() };
+        for segment in &response.segments {
+            transcript.push_segment(segment.start_time, segment.end_time, &segment.text);
+        }
+        // Older agents only fill the flat text
+        if transcript.segments.is_empty() {
+            transcript.text = response.text;
+        }
+        transcript
+    }
+}
+
+pub async fn transcribe_video(file_id: String, audio_data: Vec<u8>, format: &str) -> Result<Transcript, String> {
+    let mut clients = GrpcClients::shared().await?;
     
-    let request = Request::new(auralink::TranscribeRequest {
+    let limit = RpcDeadlines::from_env().transcription;
+    let request = timed(auralink::TranscribeRequest {
         file_id,
         audio_data,
-        format: "mp4".to_string(),
-    });
+        format: format.to_string(),
+    }, limit);
     
-    let response = clients.transcription
-        .transcribe_video(request)
-        .await
-        .map_err(|e| e.to_string())?;
+    let response = with_deadline(limit, async {
+        clients.transcription.transcribe_video(request).await.map_err(rpc_err)
+    })
+    .await?;
     
-    Ok(response.into_inner().text)
+    Ok(response.into_inner().into())
+}
+
+// Fixed chunk size for client-streamed uploads; well under the per-message cap
+const UPLOAD_CHUNK_BYTES: usize = 4 * 1024 * 1024;
+
+fn upload_chunk(file_id: &str, format: &str, first: bool, data: Vec<u8>) -> auralink::UploadChunk {
+    // Only the first chunk carries the header fields
+    auralink::UploadChunk {
+        file_id: if first { file_id.to_string() } else { String::new() },
+        format: if first { format.to_string() } else { String::new() },
+        data,
+    }
+}
+
+// Ok(None) means the agent doesn't serve TranscribeUpload; callers fall back to the unary RPC.
+async fn send_upload(
+    chunks: tokio::sync::mpsc::Receiver<auralink::UploadChunk>,
+) -> Result<Option<Transcript>, String> {
+    let mut clients = GrpcClients::shared().await?;
+    let limit = RpcDeadlines::from_env().transcription;
+    let request = timed(tokio_stream::wrappers::ReceiverStream::new(chunks), limit);
+    with_deadline(limit, async {
+        match clients.transcription.transcribe_upload(request).await {
+            Ok(response) => Ok(Some(response.into_inner().into())),
+            Err(status) if status.code() == tonic::Code::Unimplemented => Ok(None),
+            Err(status) => Err(rpc_err(status)),
+        }
+    })
+    .await
+}
+
+/// Uploads a file to the transcription agent in fixed-size chunks. The file is read
+/// as the stream drains, so memory stays bounded regardless of its size.
+pub async fn transcribe_file_upload(
+    file_id: String,
+    path: &std::path::Path,
+    format: &str,
+) -> Result<Transcript, String> {
+    use tokio::io::AsyncReadExt;
+    let mut file = tokio::fs::File::open(path).await.map_err(|e| e.to_string())?;
+    // A small bound keeps the reader at most a couple of chunks ahead of the network
+    let (tx, rx) = tokio::sync::mpsc::channel(2);
+    let (fid, fmt) = (file_id.clone(), format.to_string());
+    let reader = tokio::spawn(async move {
+        let mut first = true;
+        loop {
+            let mut buf = vec![0u8; UPLOAD_CHUNK_BYTES];
+            let mut filled = 0;
+            while filled < buf.len() {
+                let n = file.read(&mut buf[filled..]).await?;
+                if n == 0 { break; }
+                filled += n;
+            }
+            if filled == 0 && !first { break; }
+            buf.truncate(filled);
+            // A closed channel means the call already failed; its error is reported there
+            if tx.send(upload_chunk(&fid, &fmt, first, buf)).await.is_err() { break; }
+            first = false;
+            if filled < UPLOAD_CHUNK_BYTES { break; }
+        }
+        Ok::<(), std::io::Error>(())
+    });
+    let result = send_upload(rx).await;
+    if let Ok(Err(e)) = reader.await {
+        return Err(format!("failed to read {}: {}", path.to_string_lossy(), e));
+    }
+    match result? {
+        Some(transcript) => Ok(transcript),
+        None => {
+            let bytes = tokio::fs::read(path).await.map_err(|e| e.to_string())?;
+            transcribe_video(file_id, bytes, format).await
+        }
+    }
 }
 
-pub async fn vision_detect_objects(image_data: Vec<u8>) -> Result<String, String> {
-    let mut clients = GrpcClients::new().await.map_err(|e| e.to_string())?;
+/// Streams transcript segments as the agent produces them, calling `on_chunk`
+/// for each one. Falls back to the one-shot RPC if streaming isn't served.
+pub async fn transcribe_video_streaming<F>(
+    file_id: String,
+    audio_data: Vec<u8>,
+    format: &str,
+    mut on_chunk: F,
+) -> Result<Transcript, String>
+where
+    F: FnMut(&auralink::TranscribeChunk) + Send,
+{
+    let mut clients = GrpcClients::shared().await?;
+
+    let limit = RpcDeadlines::from_env().transcription;
+    let until = tokio::time::Instant::now() + limit;
+    let request = timed(auralink::TranscribeRequest {
+        file_id: file_id.clone(),
+        audio_data: audio_data.clone(),
+        format: format.to_string(),
+    }, limit);
+
+    let opened = with_deadline(limit, async {
+        match clients.transcription.stream_transcription(request).await {
+            Ok(response) => Ok(Some(response.into_inner())),
+            Err(status) if status.code() == tonic::Code::Unimplemented => Ok(None),
+            Err(status) => Err(rpc_err(status)),
+        }
+    })
+    .await?;
+    let Some(mut stream) = opened else {
+        return transcribe_video(file_id, audio_data, format).await;
+    };
+
+    // Opening the stream and reading it share one deadline
+    let remaining = until.saturating_duration_since(tokio::time::Instant::now());
+    with_deadline(remaining, async {
+        let mut transcript = Transcript::default();
+        while let Some(chunk) = stream.message().await.map_err(rpc_err)? {
+            on_chunk(&chunk);
+            transcript.push_segment(chunk.start_time, chunk.end_time, &chunk.text);
+            if let Some(language) = detected_language(&chunk.language) {
+                transcript.language = Some(language);
+            }
+            if chunk.is_final { break; }
+        }
+        Ok(transcript)
+    })
+    .await
+}
+
+pub async fn vision_detect_objects_detailed(image_data: Vec<u8>) -> Result<auralink::ObjectDetectionResponse, String> {
+    let mut clients = GrpcClients::shared().await?;
 
-    let request = Request::new(auralink::ImageRequest {
+    let limit = RpcDeadlines::from_env().vision;
+    let request = timed(auralink::ImageRequest {
         file_id: "".to_string(),
         image_data,
         frame_number: 0,
-    });
+    }, limit);
 
-    let response = clients
-        .vision
-        .detect_objects(request)
-        .await
-        .map_err(|e| e.to_string())?;
+    let response = with_deadline(limit, async {
+        clients.vision.detect_objects(request).await.map_err(rpc_err)
+    })
+    .await?;
 
-    let inner = response.into_inner();
-    let caption = inner.caption;
+    Ok(response.into_inner())
+}
+
+// Rows shown in the chat table; the structured result still carries every object
+const MAX_TABLE_ROWS: usize = 10;
+
+/// Chat description of a detection result: a Label/Confidence markdown table sorted
+/// by confidence, or a single line when there are fewer than two objects.
+pub fn summarize_detections(inner: &auralink::ObjectDetectionResponse) -> String {
     let count = inner.objects.len();
-    let top = inner
-        .objects
-        .into_iter()
-        .take(5)
-        .map(|o| format!("{} ({:.2})", o.label, o.confidence))
+    let mut objects: Vec<&auralink::DetectedObject> = inner.objects.iter().collect();
+    objects.sort_by(|a, b| b.confidence.partial_cmp(&a.confidence).unwrap_or(std::cmp::Ordering::Equal));
+    let percent = |c: f64| format!("{:.0}%", c * 100.0);
+    if objects.len() <= 1 {
+        let top = objects
+            .first()
+            .map(|o| format!("{} ({})", o.label, percent(o.confidence)))
+            .unwrap_or_else(|| "none".to_string());
+        return format!("Detected {} object(s): {}. Caption: {}", count, top, inner.caption);
+    }
+    let rows = objects
+        .iter()
+        .take(MAX_TABLE_ROWS)
+        .map(|o| format!("| {} | {} |", crate::escape_markdown(&o.label), percent(o.confidence)))
         .collect::<Vec<_>>()
-        .join(", ");
-    Ok(format!(
-        "Detected {} object(s): {}. Caption: {}",
-        count, top, caption
-    ))
+        .join("\n");
+    format!(
+        "Detected {} object(s):\n\n| Label | Confidence |\n| --- | ---: |\n{}\n\nCaption: {}",
+        count, rows, inner.caption
+    )
 }
 
-pub async fn vision_identify_graphs(image_data: Vec<u8>) -> Result<String, String> {
-    let mut clients = GrpcClients::new().await.map_err(|e| e.to_string())?;
+pub fn detections_json(inner: &auralink::ObjectDetectionResponse) -> serde_json::Value {
+    let objects: Vec<serde_json::Value> = inner
+        .objects
+        .iter()
+        .map(|o| {
+            let bbox = o.bbox.clone().unwrap_or_default();
+            // 0-1 relative to the frame, for overlays at any display size; null from
+            // agents that don't report the frame size
+            let normalized = (inner.image_width > 0 && inner.image_height > 0).then(|| {
+                let (w, h) = (inner.image_width as f64, inner.image_height as f64);
+                let unit = |v: f64| v.clamp(0.0, 1.0);
+                serde_json::json!({
+                    "x": unit(bbox.x as f64 / w),
+                    "y": unit(bbox.y as f64 / h),
+                    "width": unit(bbox.width as f64 / w),
+                    "height": unit(bbox.height as f64 / h),
+                })
+            });
+            serde_json::json!({
+                "label": o.label,
+                "confidence": o.confidence,
+                "bbox": { "x": bbox.x, "y": bbox.y, "width": bbox.width, "height": bbox.height },
+                "normalizedBbox": normalized,
+            })
+        })
+        .collect();
+    serde_json::json!({ "objects": objects, "caption": inner.caption })
+}
+
+pub async fn vision_identify_graphs_detailed(image_data: Vec<u8>) -> Result<auralink::GraphIdentificationResponse, String> {
+    let mut clients = GrpcClients::shared().await?;
 
-    let request = Request::new(auralink::ImageRequest {
+    let limit = RpcDeadlines::from_env().vision;
+    let request = timed(auralink::ImageRequest {
         file_id: "".to_string(),
         image_data,
         frame_number: 0,
-    });
+    }, limit);
 
-    let response = clients
-        .vision
-        .identify_graphs(request)
-        .await
-        .map_err(|e| e.to_string())?;
+    let response = with_deadline(limit, async {
+        clients.vision.identify_graphs(request).await.map_err(rpc_err)
+    })
+    .await?;
 
-    let inner = response.into_inner();
-    if inner.graphs.is_empty() {
-        Ok(format!("No graphs detected. {}", inner.description))
-    } else {
-        let kinds = inner
-            .graphs
-            .into_iter()
-            .map(|g| g.r#type)
-            .collect::<Vec<_>>()
-            .join(", ");
-        Ok(format!("Graphs detected: {}. {}", kinds, inner.description))
-    }
+    Ok(response.into_inner())
 }
 
 pub async fn generation_generate_pdf(
     file_id: String,
     key_points: Vec<String>,
 ) -> Result<String, String> {
-    let mut clients = GrpcClients::new().await.map_err(|e| e.to_string())?;
+    let mut clients = GrpcClients::shared().await?;
 
-    let request = Request::new(auralink::GenerateRequest {
+    let limit = RpcDeadlines::from_env().generation;
+    let request = timed(auralink::GenerateRequest {
         file_id,
         key_points,
         output_format: "pdf".to_string(),
-    });
+    }, limit);
 
-    let response = clients
-        .generation
-        .generate_pdf(request)
-        .await
-        .map_err(|e| e.to_string())?;
+    let response = with_deadline(limit, async {
+        clients.generation.generate_pdf(request).await.map_err(rpc_err)
+    })
+    .await?;
     let inner = response.into_inner();
     if inner.success {
-        Ok(format!("PDF generated at {}", inner.output_file_path))
+        describe_generated("PDF", &inner.output_file_path)
     } else {
         Err(inner.error_message)
     }
@@ -163,80 +808,421 @@ pub async fn generation_generate_powerpoint(
     file_id: String,
     key_points: Vec<String>,
 ) -> Result<String, String> {
-    let mut clients = GrpcClients::new().await.map_err(|e| e.to_string())?;
+    let mut clients = GrpcClients::shared().await?;
 
-    let request = Request::new(auralink::GenerateRequest {
+    let limit = RpcDeadlines::from_env().generation;
+    let request = timed(auralink::GenerateRequest {
         file_id,
         key_points,
         output_format: "ppt".to_string(),
-    });
+    }, limit);
 
-    let response = clients
-        .generation
-        .generate_power_point(request)
-        .await
-        .map_err(|e| e.to_string())?;
+    let response = with_deadline(limit, async {
+        clients.generation.generate_power_point(request).await.map_err(rpc_err)
+    })
+    .await?;
     let inner = response.into_inner();
     if inner.success {
-        Ok(format!("PowerPoint generated at {}", inner.output_file_path))
+        describe_generated("PowerPoint", &inner.output_file_path)
     } else {
         Err(inner.error_message)
     }
 }
 
+pub async fn generation_generate_docx(
+    file_id: String,
+    key_points: Vec<String>,
+) -> Result<String, String> {
+    let mut clients = GrpcClients::shared().await?;
+
+    let limit = RpcDeadlines::from_env().generation;
+    let request = timed(auralink::GenerateRequest {
+        file_id,
+        key_points,
+        output_format: "docx".to_string(),
+    }, limit);
+
+    let response = with_deadline(limit, async {
+        clients.generation.generate_docx(request).await.map_err(rpc_err)
+    })
+    .await?;
+    let inner = response.into_inner();
+    if inner.success {
+        describe_generated("DOCX", &inner.output_file_path)
+    } else {
+        Err(inner.error_message)
+    }
+}
+
+/// Builds a document ("ppt", "pdf" or "docx") over GenerateStream, calling `on_progress`
+/// for each phase the agent reports. Agents without the streaming RPC get the
+/// one-shot call instead.
+pub async fn generation_generate_streaming<F>(
+    file_id: String,
+    key_points: Vec<String>,
+    output_format: &str,
+    mut on_progress: F,
+) -> Result<String, String>
+where
+    F: FnMut(&auralink::GenerateProgress) + Send,
+{
+    let label = match output_format {
+        "ppt" => "PowerPoint",
+        "pdf" => "PDF",
+        "docx" => "DOCX",
+        other => return Err(format!("Unknown document format: {}", other)),
+    };
+    let mut clients = GrpcClients::shared().await?;
+
+    let limit = RpcDeadlines::from_env().generation;
+    let until = tokio::time::Instant::now() + limit;
+    let request = timed(auralink::GenerateRequest {
+        file_id: file_id.clone(),
+        key_points: key_points.clone(),
+        output_format: output_format.to_string(),
+    }, limit);
+
+    let opened = with_deadline(limit, async {
+        match clients.generation.generate_stream(request).await {
+            Ok(response) => Ok(Some(response.into_inner())),
+            Err(status) if status.code() == tonic::Code::Unimplemented => Ok(None),
+            Err(status) => Err(rpc_err(status)),
+        }
+    })
+    .await?;
+    let Some(mut stream) = opened else {
+        return match output_format {
+            "ppt" => generation_generate_powerpoint(file_id, key_points).await,
+            "pdf" => generation_generate_pdf(file_id, key_points).await,
+            _ => generation_generate_docx(file_id, key_points).await,
+        };
+    };
+
+    let remaining = until.saturating_duration_since(tokio::time::Instant::now());
+    with_deadline(remaining, async {
+        while let Some(progress) = stream.message().await.map_err(rpc_err)? {
+            on_progress(&progress);
+            if let Some(result) = progress.result {
+                return if result.success {
+                    describe_generated(label, &result.output_file_path)
+                } else {
+                    Err(result.error_message)
+                };
+            }
+        }
+        Err("generation agent ended the stream without a result".to_string())
+    })
+    .await
+}
+
+/// Summarizes the conversation; `history` is its latest messages, oldest first.
 pub async fn generation_generate_summary(
     file_id: String,
     message_limit: i32,
+    history: Vec<(String, bool)>,
 ) -> Result<String, String> {
-    let mut clients = GrpcClients::new().await.map_err(|e| e.to_string())?;
+    let mut clients = GrpcClients::shared().await?;
 
-    let request = Request::new(auralink::ChatHistoryRequest { file_id, message_limit });
+    let limit = RpcDeadlines::from_env().generation;
+    let request = timed(auralink::ChatHistoryRequest { file_id, message_limit, history: chat_messages(history) }, limit);
 
-    let response = clients
-        .generation
-        .generate_summary(request)
-        .await
-        .map_err(|e| e.to_string())?;
+    let response = with_deadline(limit, async {
+        clients.generation.generate_summary(request).await.map_err(rpc_err)
+    })
+    .await?;
     let inner = response.into_inner();
     Ok(inner.summary)
 }
 
-#[allow(dead_code)]
-pub async fn get_file_messages(
+pub async fn translate_text(
     file_id: String,
-    limit: i32,
-    cursor: Option<String>,
-) -> Result<serde_json::Value, String> {
-    let mut clients = GrpcClients::new().await.map_err(|e| e.to_string())?;
-    
-    let request = Request::new(auralink::GetFileMessagesRequest {
-        file_id,
-        limit,
-        cursor,
-    });
-    
-    let response = clients
-        .chat
-        .get_file_messages(request)
-        .await
-        .map_err(|e| e.to_string())?;
-    
+    text: String,
+    target_language: String,
+) -> Result<String, String> {
+    let mut clients = GrpcClients::shared().await?;
+
+    let limit = RpcDeadlines::from_env().generation;
+    let request = timed(auralink::TranslateRequest { file_id, text, target_language }, limit);
+
+    let response = with_deadline(limit, async {
+        clients.generation.translate_text(request).await.map_err(rpc_err)
+    })
+    .await?;
     let inner = response.into_inner();
-    let messages: Vec<serde_json::Value> = inner
-        .messages
+    if inner.success {
+        Ok(inner.text)
+    } else {
+        Err(inner.error_message)
+    }
+}
+
+// (text, is_user) pairs as sent to the chat and summary agents
+fn chat_messages(history: Vec<(String, bool)>) -> Vec<auralink::ChatMessage> {
+    history
         .into_iter()
-        .map(|msg| {
-            serde_json::json!({
-                "id": msg.id,
-                "text": msg.text,
-                "isUserMessage": msg.is_user_message,
-                "createdAt": msg.created_at,
+        .map(|(text, is_user_message)| auralink::ChatMessage { text, is_user_message, ..Default::default() })
+        .collect()
+}
+
+/// Streams the chat agent's reply to `message`, passing each piece to `on_token`
+/// as it arrives, and returns the whole text. Ok(None) if the agent has no chat.
+pub async fn chat_streaming<F>(
+    file_id: String,
+    history: Vec<(String, bool)>,
+    message: String,
+    context: String,
+    mut on_token: F,
+) -> Result<Option<String>, String>
+where
+    F: FnMut(&str) + Send,
+{
+    let mut clients = GrpcClients::shared().await?;
+
+    let limit = RpcDeadlines::from_env().generation;
+    let until = tokio::time::Instant::now() + limit;
+    let request = timed(auralink::ChatRequest {
+        file_id,
+        history: chat_messages(history),
+        message,
+        context,
+    }, limit);
+
+    let opened = with_deadline(limit, async {
+        match clients.chat.chat(request).await {
+            Ok(response) => Ok(Some(response.into_inner())),
+            Err(status) if status.code() == tonic::Code::Unimplemented => Ok(None),
+            Err(status) => Err(rpc_err(status)),
+        }
+    })
+    .await?;
+    let Some(mut stream) = opened else { return Ok(None) };
+
+    let remaining = until.saturating_duration_since(tokio::time::Instant::now());
+    with_deadline(remaining, async {
+        let mut reply = String::new();
+        while let Some(token) = stream.message().await.map_err(rpc_err)? {
+            on_token(&token.text);
+            reply.push_str(&token.text);
+        }
+        Ok(Some(reply))
+    })
+    .await
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::atomic::{AtomicU32, Ordering};
+
+    use auralink::transcription_service_server::{TranscriptionService, TranscriptionServiceServer};
+
+    // Stand-in transcription agent; answers TranscribeVideo with "heard <file_id>"
+    struct FakeTranscriber;
+
+    #[tonic::async_trait]
+    impl TranscriptionService for FakeTranscriber {
+        async fn transcribe_video(
+            &self,
+            request: Request<auralink::TranscribeRequest>,
+        ) -> Result<tonic::Response<auralink::TranscribeResponse>, tonic::Status> {
+            let text = format!("heard {}", request.into_inner().file_id);
+            Ok(tonic::Response::new(auralink::TranscribeResponse { text, ..Default::default() }))
+        }
+
+        type StreamTranscriptionStream = tokio_stream::wrappers::ReceiverStream<Result<auralink::TranscribeChunk, tonic::Status>>;
+
+        async fn stream_transcription(
+            &self,
+            _request: Request<auralink::TranscribeRequest>,
+        ) -> Result<tonic::Response<Self::StreamTranscriptionStream>, tonic::Status> {
+            Err(tonic::Status::unimplemented("StreamTranscription"))
+        }
+
+        async fn transcribe_upload(
+            &self,
+            _request: Request<tonic::Streaming<auralink::UploadChunk>>,
+        ) -> Result<tonic::Response<auralink::TranscribeResponse>, tonic::Status> {
+            Err(tonic::Status::unimplemented("TranscribeUpload"))
+        }
+    }
+
+    // Serves FakeTranscriber on `listener` until the sender fires
+    fn serve(listener: tokio::net::TcpListener) -> (tokio::sync::oneshot::Sender<()>, tokio::task::JoinHandle<()>) {
+        let (stop, stopped) = tokio::sync::oneshot::channel::<()>();
+        let incoming = tonic::transport::server::TcpIncoming::from_listener(listener, true, None).unwrap();
+        let server = tokio::spawn(async move {
+            tonic::transport::Server::builder()
+                .add_service(TranscriptionServiceServer::new(FakeTranscriber))
+                .serve_with_incoming_shutdown(incoming, async {
+                    let _ = stopped.await;
+                })
+                .await
+                .unwrap();
+        });
+        (stop, server)
+    }
+
+    // An address nothing listens on, as for an agent switched off in settings
+    fn closed_addr() -> String {
+        let port = std::net::TcpListener::bind("127.0.0.1:0").unwrap().local_addr().unwrap().port();
+        format!("http://127.0.0.1:{}", port)
+    }
+
+    fn quick(attempts: u32) -> RetryPolicy {
+        RetryPolicy { attempts, base: Duration::from_millis(1), max: Duration::from_millis(4) }
+    }
+
+    #[tokio::test]
+    async fn run_retries_transient_failures_until_success() {
+        let calls = AtomicU32::new(0);
+        let result = quick(4)
+            .run(|| {
+                let n = calls.fetch_add(1, Ordering::SeqCst) + 1;
+                async move {
+                    if n < 3 { Err("transport error: connection refused".to_string()) } else { Ok(n) }
+                }
             })
-        })
-        .collect();
-    
-    Ok(serde_json::json!({
-        "messages": messages,
-        "nextCursor": inner.next_cursor,
-    }))
-}
\ No newline at end of file
+            .await;
+        assert_eq!(result, Ok(3));
+        assert_eq!(calls.load(Ordering::SeqCst), 3);
+    }
+
+    #[tokio::test]
+    async fn run_gives_up_after_the_last_attempt() {
+        let calls = AtomicU32::new(0);
+        let result: Result<(), RpcError> = quick(2)
+            .run(|| {
+                calls.fetch_add(1, Ordering::SeqCst);
+                async { Err("status: Unavailable".to_string()) }
+            })
+            .await;
+        assert_eq!(result, Err(RpcError::Transient));
+        assert_eq!(calls.load(Ordering::SeqCst), 2);
+    }
+
+    #[tokio::test]
+    async fn run_does_not_retry_permanent_errors() {
+        let calls = AtomicU32::new(0);
+        let result: Result<(), RpcError> = quick(4)
+            .run(|| {
+                calls.fetch_add(1, Ordering::SeqCst);
+                async { Err("status: InvalidArgument, message: \"no such file\"".to_string()) }
+            })
+            .await;
+        assert!(matches!(result, Err(RpcError::Permanent(_))));
+        assert_eq!(calls.load(Ordering::SeqCst), 1);
+
+        let calls = AtomicU32::new(0);
+        let result: Result<(), RpcError> = quick(4)
+            .run(|| {
+                calls.fetch_add(1, Ordering::SeqCst);
+                async { Err("agent timed out after 30s".to_string()) }
+            })
+            .await;
+        assert_eq!(result, Err(RpcError::TimedOut));
+        assert_eq!(calls.load(Ordering::SeqCst), 1);
+    }
+
+    #[test]
+    fn delay_doubles_up_to_max_with_bounded_jitter() {
+        let policy = RetryPolicy { attempts: 6, base: Duration::from_millis(200), max: Duration::from_secs(1) };
+        for (retry, backoff_ms) in [(1, 200), (2, 400), (3, 800), (4, 1000), (5, 1000), (40, 1000)] {
+            for _ in 0..20 {
+                let delay = policy.delay(retry).as_millis() as u64;
+                assert!(delay >= backoff_ms && delay <= backoff_ms + backoff_ms / 4, "retry {}: {}ms", retry, delay);
+            }
+        }
+        let none = RetryPolicy { attempts: 2, base: Duration::ZERO, max: Duration::ZERO };
+        assert_eq!(none.delay(3), Duration::ZERO);
+    }
+
+    #[test]
+    fn classify_sorts_errors_by_retryability() {
+        assert_eq!(RpcError::classify("agent timed out after 10s"), RpcError::TimedOut);
+        assert_eq!(RpcError::classify("transport error"), RpcError::Transient);
+        assert_eq!(RpcError::classify("status: Unavailable, message: \"connection refused\""), RpcError::Transient);
+        assert_eq!(RpcError::classify("Broken pipe (os error 32)"), RpcError::Transient);
+        assert_eq!(RpcError::classify("status: ResourceExhausted"), RpcError::TooLarge);
+        assert_eq!(RpcError::classify("message larger than max (5000000 vs. 4194304)"), RpcError::TooLarge);
+        assert_eq!(RpcError::classify("model failed"), RpcError::Permanent("model failed".to_string()));
+    }
+
+    #[test]
+    fn permanent_errors_show_the_agent_message() {
+        let e = RpcError::Permanent("status: Internal, message: \"bad frame\", metadata: {...}".to_string());
+        assert_eq!(e.to_string(), "bad frame");
+        for (status, shown) in [
+            (tonic::Status::invalid_argument("unsupported codec"), "unsupported codec"),
+            (tonic::Status::not_found("file not found: \"a.mp4\""), "file not found: \"a.mp4\""),
+            (tonic::Status::internal(""), "couldn’t complete this right now; please try again"),
+        ] {
+            assert_eq!(RpcError::classify(&rpc_err(status)).to_string(), shown);
+        }
+        // Errors raised on our side are shown as they are
+        assert_eq!(RpcError::Permanent("boom".to_string()).to_string(), "boom");
+        assert_eq!(RpcError::Permanent(" ".to_string()).to_string(), "couldn’t complete this right now; please try again");
+    }
+
+    #[tokio::test]
+    async fn deadline_cuts_off_a_stalled_call() {
+        let stalled = with_deadline(Duration::from_millis(20), std::future::pending::<Result<(), String>>()).await;
+        assert_eq!(stalled.unwrap_err(), "agent timed out after 0s");
+        let answered = with_deadline(Duration::from_secs(5), async { Ok::<_, String>(7) }).await;
+        assert_eq!(answered, Ok(7));
+        let failed = with_deadline(Duration::from_secs(5), async { Err::<(), _>("unavailable".to_string()) }).await;
+        assert_eq!(failed.unwrap_err(), "unavailable");
+    }
+
+    #[test]
+    fn tls_ca_only_upgrades_remote_agents() {
+        assert_eq!(tls_upgraded("http://10.0.0.5:50052".to_string(), true), "https://10.0.0.5:50052");
+        assert_eq!(tls_upgraded("http://gpu-box.lan:50052".to_string(), true), "https://gpu-box.lan:50052");
+        for local in ["http://127.0.0.1:50051", "http://localhost:50051", "http://[::1]:50051"] {
+            assert_eq!(tls_upgraded(local.to_string(), true), local);
+        }
+        assert_eq!(tls_upgraded("http://10.0.0.5:50052".to_string(), false), "http://10.0.0.5:50052");
+        assert_eq!(tls_upgraded("https://127.0.0.1:50051".to_string(), false), "https://127.0.0.1:50051");
+    }
+
+    #[tokio::test]
+    async fn disabled_agent_leaves_the_others_working() {
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let transcription = format!("http://{}", listener.local_addr().unwrap());
+        let (stop, server) = serve(listener);
+        let endpoints = GrpcEndpoints { transcription, vision: closed_addr(), generation: closed_addr() };
+        let mut clients = GrpcClients::connect(endpoints).unwrap();
+
+        let request = auralink::TranscribeRequest { file_id: "clip".to_string(), ..Default::default() };
+        let heard = clients.transcription.transcribe_video(request).await.unwrap();
+        assert_eq!(heard.into_inner().text, "heard clip");
+        let seen = clients.vision.detect_objects(auralink::ImageRequest::default()).await.map_err(rpc_err);
+        assert_eq!(RpcError::classify(&seen.unwrap_err()), RpcError::Transient);
+
+        let _ = stop.send(());
+        server.await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn next_call_recovers_after_an_agent_restart() {
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let local = listener.local_addr().unwrap();
+        override_endpoint("transcription", format!("http://{}", local));
+        GrpcClients::reset().await;
+        let call = || transcribe_video("clip".to_string(), Vec::new(), "wav");
+
+        let (stop, server) = serve(listener);
+        assert_eq!(quick(1).run(call).await.unwrap().text, "heard clip");
+
+        // The agent goes away mid-session: the stale channel fails as transient and is dropped
+        let _ = stop.send(());
+        server.await.unwrap();
+        assert_eq!(quick(1).run(call).await.unwrap_err(), RpcError::Transient);
+        assert!(SHARED.lock().await.is_none());
+
+        // Back on the same port, the next call dials a fresh channel
+        let (stop, server) = serve(tokio::net::TcpListener::bind(local).await.unwrap());
+        assert_eq!(quick(3).run(call).await.unwrap().text, "heard clip");
+        let _ = stop.send(());
+        server.await.unwrap();
+    }
+}
@@ -0,0 +1,696 @@
+use serde::Deserialize;
+use std::sync::{OnceLock, RwLock};
+
+/// One trigger rule: every phrase in `all` and at least one in `any` must appear.
+/// Rules marked `fuzzy` also match misspelled phrases, at one less weight.
+#[derive(Clone, Debug, Deserialize)]
+pub struct KeywordRule {
+    pub intent: String,
+    pub weight: u8,
+    #[serde(default)]
+    pub all: Vec<String>,
+    #[serde(default)]
+    pub any: Vec<String>,
+    // Only for phrases no everyday word is a typo away from ("chart" is one from "chat")
+    #[serde(default)]
+    pub fuzzy: bool,
+}
+
+impl KeywordRule {
+    fn matches(&self, lower: &str) -> bool {
+        self.all.iter().all(|p| lower.contains(p.as_str()))
+            && (self.any.is_empty() || self.any.iter().any(|p| lower.contains(p.as_str())))
+    }
+
+    fn matches_fuzzy(&self, words: &[&str]) -> bool {
+        self.all.iter().all(|p| fuzzy_contains(words, p))
+            && (self.any.is_empty() || self.any.iter().any(|p| fuzzy_contains(words, p)))
+    }
+
+    /// Full weight for an exact match; one less when a fuzzy rule only matches allowing typos.
+    fn score(&self, lower: &str, words: &[&str]) -> Option<u8> {
+        if self.matches(lower) {
+            Some(self.weight)
+        } else if self.fuzzy && self.matches_fuzzy(words) {
+            Some(self.weight.saturating_sub(1).max(1))
+        } else {
+            None
+        }
+    }
+}
+
+fn levenshtein(a: &str, b: &str) -> usize {
+    let b: Vec<char> = b.chars().collect();
+    let mut prev: Vec<usize> = (0..=b.len()).collect();
+    for (i, ca) in a.chars().enumerate() {
+        let mut row = vec![i + 1; b.len() + 1];
+        for (j, cb) in b.iter().enumerate() {
+            let cost = if ca == *cb { 0 } else { 1 };
+            row[j + 1] = (prev[j] + cost).min(prev[j + 1] + 1).min(row[j] + 1);
+        }
+        prev = row;
+    }
+    prev[b.len()]
+}
+
+// Typos tolerated per word: none for short words ("pdf", "the"), where one edit
+// turns them into other words, then one, then two from 8 letters up
+fn typo_allowance(word: &str) -> usize {
+    match word.chars().count() {
+        0..=4 => 0,
+        5..=7 => 1,
+        _ => 2,
+    }
+}
+
+fn words_close(said: &str, keyword: &str) -> bool {
+    said == keyword || levenshtein(said, keyword) <= typo_allowance(keyword)
+}
+
+// Whether the message words contain the phrase's words in a row, each within its typo allowance
+fn fuzzy_contains(words: &[&str], phrase: &str) -> bool {
+    let wanted: Vec<&str> = phrase.split_whitespace().collect();
+    if wanted.is_empty() || wanted.len() > words.len() {
+        return false;
+    }
+    words
+        .windows(wanted.len())
+        .any(|window| window.iter().zip(&wanted).all(|(said, keyword)| words_close(said, keyword)))
+}
+
+// Built-in phrases; users can override them with intent_keywords.json in the app data dir
+const DEFAULT_KEYWORDS: &str = include_str!("intent_keywords.json");
+
+static KEYWORDS: OnceLock<RwLock<Vec<KeywordRule>>> = OnceLock::new();
+
+pub fn keywords_path() -> std::path::PathBuf {
+    crate::db::db_path()
+        .parent()
+        .unwrap_or(std::path::Path::new("."))
+        .join("intent_keywords.json")
+}
+
+fn parse_rules(json: &str) -> Result<Vec<KeywordRule>, String> {
+    let mut rules: Vec<KeywordRule> = serde_json::from_str(json).map_err(|e| e.to_string())?;
+    for rule in rules.iter_mut() {
+        rule.all = rule.all.iter().map(|p| p.to_lowercase()).collect();
+        rule.any = rule.any.iter().map(|p| p.to_lowercase()).collect();
+    }
+    Ok(rules)
+}
+
+fn load_rules() -> Result<Vec<KeywordRule>, String> {
+    match std::fs::read_to_string(keywords_path()) {
+        Ok(json) => parse_rules(&json).map_err(|e| format!("invalid {}: {}", keywords_path().to_string_lossy(), e)),
+        Err(_) => parse_rules(DEFAULT_KEYWORDS),
+    }
+}
+
+fn keywords() -> &'static RwLock<Vec<KeywordRule>> {
+    KEYWORDS.get_or_init(|| {
+        let rules = load_rules().unwrap_or_else(|e| {
+            println!("[Tauri] Warning: {}; using built-in intent keywords", e);
+            parse_rules(DEFAULT_KEYWORDS).unwrap_or_default()
+        });
+        RwLock::new(rules)
+    })
+}
+
+/// Re-reads the keyword table; on error the previous table stays in effect.
+pub fn reload_keywords() -> Result<usize, String> {
+    let rules = load_rules()?;
+    let count = rules.len();
+    if let Ok(mut guard) = keywords().write() {
+        *guard = rules;
+    }
+    Ok(count)
+}
+
+/// A single analysis/generation step that send_message and pipelines can run.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Task {
+    Transcribe,
+    Objects,
+    Graphs,
+    Ppt,
+    Pdf,
+    Docx,
+    Summary,
+    SummaryPdf,
+    Translate,
+    KeyMoments,
+}
+
+impl Task {
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name.trim().to_lowercase().as_str() {
+            "transcribe" | "transcription" => Some(Task::Transcribe),
+            "objects" => Some(Task::Objects),
+            "graphs" => Some(Task::Graphs),
+            "ppt" | "powerpoint" => Some(Task::Ppt),
+            "pdf" => Some(Task::Pdf),
+            "docx" | "word" => Some(Task::Docx),
+            "summary" | "summarize" => Some(Task::Summary),
+            "summary_pdf" => Some(Task::SummaryPdf),
+            "translate" | "translation" => Some(Task::Translate),
+            "key_moments" | "moments" => Some(Task::KeyMoments),
+            _ => None,
+        }
+    }
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            Task::Transcribe => "transcribe",
+            Task::Objects => "objects",
+            Task::Graphs => "graphs",
+            Task::Ppt => "ppt",
+            Task::Pdf => "pdf",
+            Task::Docx => "docx",
+            Task::Summary => "summary",
+            Task::SummaryPdf => "summary_pdf",
+            Task::Translate => "translate",
+            Task::KeyMoments => "key_moments",
+        }
+    }
+
+    /// The agent that serves this step.
+    pub fn agent(&self) -> &'static str {
+        match self {
+            Task::Transcribe => "transcription",
+            // Key moments also read the transcript, but frames are what it can't do without
+            Task::Objects | Task::Graphs | Task::KeyMoments => "vision",
+            Task::Ppt | Task::Pdf | Task::Docx | Task::Summary | Task::SummaryPdf | Task::Translate => "generation",
+        }
+    }
+
+    /// Short progress text shown while the step runs.
+    pub fn label(&self) -> &'static str {
+        match self {
+            Task::Transcribe => "Transcribing…",
+            Task::Objects => "Detecting objects…",
+            Task::Graphs => "Looking for graphs…",
+            Task::Ppt => "Generating PowerPoint…",
+            Task::Pdf => "Generating PDF…",
+            Task::Docx => "Generating Word document…",
+            Task::Summary => "Summarizing…",
+            Task::SummaryPdf => "Summarizing and generating PDF…",
+            Task::Translate => "Translating transcript…",
+            Task::KeyMoments => "Finding key moments…",
+        }
+    }
+}
+
+const LANGUAGES: &[&str] = &[
+    "english", "spanish", "french", "german", "italian", "portuguese", "dutch", "russian",
+    "japanese", "chinese", "korean", "arabic", "hindi", "malay", "indonesian", "thai",
+    "vietnamese", "turkish", "polish", "swedish",
+];
+
+/// English name for a Whisper language code ("de" -> "German"), for the common ones.
+pub fn language_name(code: &str) -> Option<&'static str> {
+    let name = match code.to_lowercase().as_str() {
+        "en" => "English",
+        "es" => "Spanish",
+        "fr" => "French",
+        "de" => "German",
+        "it" => "Italian",
+        "pt" => "Portuguese",
+        "nl" => "Dutch",
+        "ru" => "Russian",
+        "ja" => "Japanese",
+        "zh" => "Chinese",
+        "ko" => "Korean",
+        "ar" => "Arabic",
+        "hi" => "Hindi",
+        "ms" => "Malay",
+        "id" => "Indonesian",
+        "th" => "Thai",
+        "vi" => "Vietnamese",
+        "tr" => "Turkish",
+        "pl" => "Polish",
+        "sv" => "Swedish",
+        _ => return None,
+    };
+    Some(name)
+}
+
+/// "show me moment 3" / "moment #3" -> 3
+pub fn moment_reference(msg: &str) -> Option<usize> {
+    let lower = msg.to_lowercase();
+    let words: Vec<&str> = lower
+        .split(|c: char| !c.is_alphanumeric())
+        .filter(|w| !w.is_empty())
+        .collect();
+    words
+        .windows(2)
+        .find(|pair| pair[0] == "moment")
+        .and_then(|pair| pair[1].parse::<usize>().ok())
+        .filter(|n| *n >= 1)
+}
+
+// First words of a question or of asking to talk something through
+const CONVERSATIONAL_OPENERS: &[&str] = &[
+    "what", "why", "how", "who", "when", "where", "which", "can", "could", "would", "should",
+    "is", "are", "does", "do", "did", "explain", "tell", "describe", "elaborate",
+];
+
+/// Whether a message reads as a question or conversation ("can you explain that chart
+/// more?") rather than a bare command or noise.
+pub fn is_conversational(msg: &str) -> bool {
+    let trimmed = msg.trim();
+    if trimmed.ends_with('?') {
+        return true;
+    }
+    let lower = trimmed.to_lowercase();
+    let words: Vec<&str> = lower
+        .split(|c: char| !c.is_alphanumeric())
+        .filter(|w| !w.is_empty())
+        .collect();
+    words.len() >= 3 && CONVERSATIONAL_OPENERS.contains(&words[0])
+}
+
+fn time_unit(word: &str) -> Option<f64> {
+    match word {
+        "h" | "hr" | "hrs" | "hour" | "hours" => Some(3600.0),
+        "m" | "min" | "mins" | "minute" | "minutes" => Some(60.0),
+        "s" | "sec" | "secs" | "second" | "seconds" => Some(1.0),
+        _ => None,
+    }
+}
+
+/// A point in the video the message asks about, in seconds: "what's shown at 2:30?",
+/// "around 1:02:03", "the 4:10 mark", "2 minutes 30 seconds in", "at 90 seconds".
+/// A bare number or clock time without such phrasing isn't taken as one.
+pub fn timestamp_reference(msg: &str) -> Option<f64> {
+    let lower = msg.to_lowercase();
+    let words: Vec<&str> = lower
+        .split_whitespace()
+        .map(|w| w.trim_matches(|c: char| !c.is_alphanumeric() && c != ':'))
+        .filter(|w| !w.is_empty())
+        .collect();
+    let anchored = |start: usize, end: usize| {
+        let before = start.checked_sub(1).map(|i| words[i]);
+        let after = words.get(end).copied();
+        matches!(before, Some("at" | "around" | "near")) || matches!(after, Some("in" | "mark"))
+    };
+    for (i, word) in words.iter().enumerate() {
+        if !word.contains(':') { continue; }
+        if let Some(at) = crate::media::parse_timestamp(word) {
+            if anchored(i, i + 1) { return Some(at); }
+        }
+    }
+    // Number-unit pairs, possibly several in a row
+    let mut i = 0;
+    while i < words.len() {
+        let (mut end, mut total) = (i, 0.0);
+        while end + 1 < words.len() {
+            match (words[end].parse::<f64>(), time_unit(words[end + 1])) {
+                (Ok(n), Some(unit)) if n.is_finite() && n >= 0.0 => { total += n * unit; end += 2; }
+                _ => break,
+            }
+        }
+        if end > i && anchored(i, end) {
+            return Some(total);
+        }
+        i = end.max(i + 1);
+    }
+    None
+}
+
+/// Target language named in a request ("translate to Spanish", "... in French"),
+/// capitalized for display.
+pub fn target_language(msg: &str) -> Option<String> {
+    let lower = msg.to_lowercase();
+    let words: Vec<&str> = lower
+        .split(|c: char| !c.is_alphanumeric())
+        .filter(|w| !w.is_empty())
+        .collect();
+    words.windows(2).find_map(|pair| {
+        let lang = LANGUAGES.iter().find(|l| **l == pair[1])?;
+        if !matches!(pair[0], "to" | "into" | "in") {
+            return None;
+        }
+        let mut chars = lang.chars();
+        chars.next().map(|first| first.to_uppercase().chain(chars).collect())
+    })
+}
+
+/// Score cut-offs for routing. A task scoring at least `execute` runs without
+/// asking; a signal above zero but below `clarify` is too weak to act on.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Thresholds {
+    pub execute: u8,
+    pub clarify: u8,
+}
+
+impl Default for Thresholds {
+    fn default() -> Self {
+        Self { execute: 7, clarify: 6 }
+    }
+}
+
+#[derive(Debug)]
+pub struct IntentScore {
+    pub transcribe: u8,
+    pub objects: u8,
+    pub graphs: u8,
+    pub ppt: u8,
+    pub pdf: u8,
+    pub docx: u8,
+    pub summary: u8,
+    pub translate: u8,
+    pub key_moments: u8,
+    // Meta requests; they don't map to tasks and don't count towards ambiguity
+    pub help: u8,
+    pub cancel: u8,
+    // Relating this file to another one; the other scores pick what to compare
+    pub compare: u8,
+}
+
+impl IntentScore {
+    pub fn from_message(msg: &str) -> Self {
+        let guard = keywords().read();
+        let rules: &[KeywordRule] = match &guard {
+            Ok(rules) => rules,
+            Err(_) => &[],
+        };
+        Self::from_rules(msg, rules)
+    }
+
+    fn from_rules(msg: &str, rules: &[KeywordRule]) -> Self {
+        let lower = msg.to_lowercase();
+        let mut score = IntentScore {
+            transcribe: 0,
+            objects: 0,
+            graphs: 0,
+            ppt: 0,
+            pdf: 0,
+            docx: 0,
+            summary: 0,
+            translate: 0,
+            key_moments: 0,
+            help: 0,
+            cancel: 0,
+            compare: 0,
+        };
+
+        // Each matching rule raises its intent to at least the rule's weight
+        let words: Vec<&str> = lower
+            .split(|c: char| !c.is_alphanumeric() && c != '\'')
+            .filter(|w| !w.is_empty())
+            .collect();
+        for rule in rules {
+            let Some(weight) = rule.score(&lower, &words) else { continue };
+            if let Some(slot) = score.slot(&rule.intent) {
+                *slot = (*slot).max(weight);
+            }
+        }
+
+        score
+    }
+
+    fn slot(&mut self, intent: &str) -> Option<&mut u8> {
+        match intent {
+            "transcribe" => Some(&mut self.transcribe),
+            "objects" => Some(&mut self.objects),
+            "graphs" => Some(&mut self.graphs),
+            "ppt" => Some(&mut self.ppt),
+            "pdf" => Some(&mut self.pdf),
+            "docx" => Some(&mut self.docx),
+            "summary" => Some(&mut self.summary),
+            "translate" => Some(&mut self.translate),
+            "key_moments" => Some(&mut self.key_moments),
+            "help" => Some(&mut self.help),
+            "cancel" => Some(&mut self.cancel),
+            "compare" => Some(&mut self.compare),
+            _ => None,
+        }
+    }
+
+    /// Raises each of `preferred` (task names) that the message already hints at
+    /// to at least `to`, so a file's usual requests win over asking. Returns
+    /// whether any score changed.
+    pub fn prefer(&mut self, preferred: &[String], to: u8) -> bool {
+        let mut changed = false;
+        for name in preferred {
+            let slots = match name.as_str() {
+                "summary_pdf" => vec!["summary", "pdf"],
+                other => vec![other],
+            };
+            for slot in slots {
+                if let Some(score) = self.slot(slot) {
+                    if *score > 0 && *score < to {
+                        *score = to;
+                        changed = true;
+                    }
+                }
+            }
+        }
+        changed
+    }
+
+    pub fn is_ambiguous(&self, low_threshold: u8) -> bool {
+        let active_count = [
+            self.transcribe > 0 && self.transcribe < low_threshold,
+            self.objects > 0 && self.objects < low_threshold,
+            self.graphs > 0 && self.graphs < low_threshold,
+            self.ppt > 0 && self.ppt < low_threshold,
+            self.pdf > 0 && self.pdf < low_threshold,
+            self.docx > 0 && self.docx < low_threshold,
+            self.summary > 0 && self.summary < low_threshold,
+            self.translate > 0 && self.translate < low_threshold,
+            self.key_moments > 0 && self.key_moments < low_threshold,
+        ]
+        .iter()
+        .filter(|&&x| x)
+        .count();
+
+        // Ambiguous if multiple weak signals or any signal is below threshold
+        active_count >= 2 || (active_count == 1 && self.max_score() < low_threshold)
+    }
+
+    /// Tasks that clear the auto-execution threshold, in the default order
+    pub fn tasks(&self, threshold: u8) -> Vec<Task> {
+        let wants_summary_pdf = self.summary >= threshold && self.pdf >= threshold;
+        let mut tasks = Vec::new();
+        if self.transcribe >= threshold { tasks.push(Task::Transcribe); }
+        if self.translate >= threshold { tasks.push(Task::Translate); }
+        if self.objects >= threshold { tasks.push(Task::Objects); }
+        if self.graphs >= threshold { tasks.push(Task::Graphs); }
+        if self.key_moments >= threshold { tasks.push(Task::KeyMoments); }
+        if self.ppt >= threshold { tasks.push(Task::Ppt); }
+        if wants_summary_pdf {
+            tasks.push(Task::SummaryPdf);
+        } else {
+            if self.pdf >= threshold { tasks.push(Task::Pdf); }
+            // A summary on its own is answered in chat, without a file
+            if self.summary >= threshold { tasks.push(Task::Summary); }
+        }
+        if self.docx >= threshold { tasks.push(Task::Docx); }
+        tasks
+    }
+
+    fn split_clauses(msg: &str) -> Vec<String> {
+        let mut lower = format!(" {} ", msg.to_lowercase());
+        for sep in [" and then ", " and also ", " and ", " then ", " also ", " plus ", ", "] {
+            lower = lower.replace(sep, " | ");
+        }
+        lower
+            .split('|')
+            .map(|c| c.trim().to_string())
+            .filter(|c| !c.is_empty())
+            .collect()
+    }
+
+    /// For explicit combined requests ("transcribe and detect objects") where at
+    /// least two clauses each carry a confident intent, returns every clause's
+    /// tasks in the order they were asked for. Otherwise None.
+    pub fn conjunctive_tasks(msg: &str, threshold: u8) -> Option<Vec<Task>> {
+        let guard = keywords().read();
+        let rules: &[KeywordRule] = match &guard {
+            Ok(rules) => rules,
+            Err(_) => &[],
+        };
+        Self::conjunctive_from_rules(msg, threshold, rules)
+    }
+
+    fn conjunctive_from_rules(msg: &str, threshold: u8, rules: &[KeywordRule]) -> Option<Vec<Task>> {
+        let clauses: Vec<IntentScore> = Self::split_clauses(msg)
+            .iter()
+            .map(|c| IntentScore::from_rules(c, rules))
+            .filter(|score| score.has_any_intent())
+            .collect();
+        if clauses.len() < 2 || clauses.iter().any(|score| score.max_score() < threshold) {
+            return None;
+        }
+        let mut tasks: Vec<Task> = Vec::new();
+        for task in clauses.iter().flat_map(|score| score.tasks(threshold)) {
+            // "summarize it into a PDF" after a plain PDF clause subsumes it
+            if task == Task::SummaryPdf { tasks.retain(|t| *t != Task::Pdf && *t != Task::Summary); }
+            if !tasks.contains(&task) { tasks.push(task); }
+        }
+        if tasks.is_empty() { None } else { Some(tasks) }
+    }
+
+    /// "cancel" / "never mind" with no confident task request alongside it
+    pub fn is_cancel(&self, threshold: u8) -> bool {
+        self.cancel >= threshold && self.max_score() < threshold
+    }
+
+    /// "help" / "what can you do" with no confident task request alongside it
+    pub fn is_help(&self, threshold: u8) -> bool {
+        self.help >= threshold && self.max_score() < threshold
+    }
+
+    /// What a compare request is about: objects, graphs or transcript, whichever
+    /// scored highest, or plain file metadata when none was mentioned.
+    pub fn compare_aspect(&self) -> &'static str {
+        let best = [(self.objects, "objects"), (self.graphs, "graphs"), (self.transcribe, "transcript")]
+            .into_iter()
+            .filter(|(score, _)| *score > 0)
+            .fold(None, |best: Option<(u8, &'static str)>, (score, aspect)| match best {
+                Some((top, _)) if top >= score => best,
+                _ => Some((score, aspect)),
+            });
+        best.map(|(_, aspect)| aspect).unwrap_or("metadata")
+    }
+
+    pub fn max_score(&self) -> u8 {
+        *[self.transcribe, self.objects, self.graphs, self.ppt, self.pdf, self.docx, self.summary, self.translate, self.key_moments]
+            .iter()
+            .max()
+            .unwrap_or(&0)
+    }
+
+    pub fn has_any_intent(&self) -> bool {
+        self.max_score() > 0
+    }
+
+    /// Options offered when clarifying, as (label shown, request it stands for)
+    pub fn clarification_options(&self) -> Vec<(&'static str, &'static str)> {
+        let mut options = Vec::new();
+
+        if self.transcribe > 0 {
+            options.push(("transcribe the audio", "transcribe the video"));
+        }
+        if self.translate > 0 {
+            options.push(("translate the transcript", "translate the transcript to english"));
+        }
+        if self.objects > 0 {
+            options.push(("detect objects in the video", "what objects are shown in the video"));
+        }
+        if self.graphs > 0 {
+            options.push(("identify charts or graphs", "are there any graphs or charts"));
+        }
+        if self.key_moments > 0 {
+            options.push(("list key moments with timestamps", "what are the key moments"));
+        }
+        if self.ppt > 0 {
+            options.push(("create a PowerPoint presentation", "create a powerpoint presentation"));
+        }
+        if self.pdf > 0 && self.summary == 0 {
+            options.push(("generate a PDF document", "generate pdf"));
+        }
+        if self.docx > 0 {
+            options.push(("create an editable Word document", "create a word document"));
+        }
+        if self.summary > 0 {
+            options.push(("summarize our conversation", "summarize our conversation"));
+        }
+        options
+    }
+
+    pub fn get_clarification_message(&self) -> String {
+        let options: Vec<&str> = self.clarification_options().into_iter().map(|(label, _)| label).collect();
+
+        if options.is_empty() {
+            return "I'm not sure what you'd like me to do. Could you clarify? For example:\n\
+                    - \"Transcribe the video\"\n\
+                    - \"What objects are shown?\"\n\
+                    - \"Create a PowerPoint\"\n\
+                    - \"Summarize our discussion\"".to_string();
+        }
+
+        if options.len() == 1 {
+            return format!("Did you mean: {}? If so, please confirm or provide more details.", options[0]);
+        }
+
+        let formatted = options
+            .iter()
+            .enumerate()
+            .map(|(i, opt)| format!("{}. {}", i + 1, opt))
+            .collect::<Vec<_>>()
+            .join("\n");
+
+        format!("I detected multiple possible actions. Which would you like me to do?\n{}\n\nPlease specify by number or rephrase your request.", formatted)
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn score(msg: &str) -> IntentScore {
+        IntentScore::from_rules(msg, &parse_rules(DEFAULT_KEYWORDS).unwrap())
+    }
+
+    #[test]
+    fn misspellings_match_fuzzy_rules_at_one_less() {
+        assert_eq!(score("transcibe this please").transcribe, 6);
+        assert_eq!(score("can you tranlsate it").translate, 9);
+        assert_eq!(score("summarise the talk").summary, 7);
+        assert_eq!(score("make a presentaton").ppt, 5);
+        assert_eq!(score("show me the higlights").key_moments, 9);
+        // Exact phrases keep their full weight
+        assert_eq!(score("transcribe this please").transcribe, 7);
+    }
+
+    #[test]
+    fn near_miss_words_do_not_trigger_other_intents() {
+        let chat = score("let's chat about this video");
+        assert_eq!(chat.graphs, 0);
+        let cancer = score("what does it say about cancer research");
+        assert_eq!(cancer.cancel, 0);
+        assert_eq!(score("a chat with the group").graphs, 0);
+        assert_eq!(score("charted a course").graphs, 7);
+    }
+
+    #[test]
+    fn rules_without_the_flag_stay_exact() {
+        let rules = parse_rules(r#"[{ "intent": "graphs", "weight": 7, "any": ["chart"] }]"#).unwrap();
+        assert!(!rules[0].fuzzy);
+        assert_eq!(IntentScore::from_rules("chat", &rules).graphs, 0);
+        assert_eq!(IntentScore::from_rules("chart", &rules).graphs, 7);
+    }
+
+    fn conjunctive(msg: &str) -> Option<Vec<Task>> {
+        IntentScore::conjunctive_from_rules(msg, 7, &parse_rules(DEFAULT_KEYWORDS).unwrap())
+    }
+
+    #[test]
+    fn combined_requests_run_every_clause_in_order() {
+        assert_eq!(conjunctive("transcribe and summarize"), Some(vec![Task::Transcribe, Task::Summary]));
+        assert_eq!(conjunctive("transcribe and detect objects"), Some(vec![Task::Transcribe, Task::Objects]));
+        assert_eq!(conjunctive("summarize and make a powerpoint"), Some(vec![Task::Summary, Task::Ppt]));
+        assert_eq!(conjunctive("summarize and make a presentation"), Some(vec![Task::Summary, Task::Ppt]));
+        assert_eq!(
+            conjunctive("detect objects, then translate it and also make a ppt"),
+            Some(vec![Task::Objects, Task::Translate, Task::Ppt])
+        );
+        // A weak clause ("presentation" alone is 6) keeps the whole request from running
+        assert_eq!(conjunctive("detect objects and a presentation"), None);
+        assert_eq!(conjunctive("transcribe the video and summarize it into a pdf"), Some(vec![Task::Transcribe, Task::SummaryPdf]));
+        // One request, or chatter around it, is not a combination
+        assert_eq!(conjunctive("transcribe the video"), None);
+        assert_eq!(conjunctive("thanks and transcribe"), None);
+    }
+
+    #[test]
+    fn summary_without_pdf_stays_in_chat() {
+        assert_eq!(score("summarize this video").tasks(7), vec![Task::Summary]);
+        assert_eq!(score("give me a recap").tasks(7), vec![Task::Summary]);
+        assert_eq!(score("summarize this as a pdf").tasks(7), vec![Task::SummaryPdf]);
+        assert_eq!(score("create pdf").tasks(7), vec![Task::Pdf]);
+    }
+}
@@ -1,212 +1,182 @@
+mod agents;
 mod grpc_client;
 mod db;
-use std::process::{Child, Command, Stdio};
-use std::time::{Duration, Instant};
-use std::net::{TcpStream, SocketAddr};
-use std::io::{BufRead, BufReader};
+mod intent;
+mod media;
+mod settings;
+mod error;
+use error::AppError;
+use std::time::Duration;
 use std::sync::{Arc, Mutex};
+use tauri::Emitter;
 use tauri::Manager;
 use tauri::WindowEvent;
+use agents::AgentHandles;
+use tokio_util::sync::CancellationToken;
+use intent::{IntentScore, Task};
+/// Byte offset of an ASCII `needle` in `haystack`, ignoring ASCII case.
+/// Matches always start on a char boundary since ASCII bytes never occur
+/// inside a multibyte sequence.
+fn find_ignore_ascii_case(haystack: &str, needle: &str) -> Option<usize> {
+    let (h, n) = (haystack.as_bytes(), needle.as_bytes());
+    if n.is_empty() || n.len() > h.len() { return None; }
+    (0..=h.len() - n.len()).find(|&i| h[i..i + n.len()].eq_ignore_ascii_case(n))
+}
+
+/// Backslash-escapes markdown in text we didn't write (captions, transcripts, agent
+/// errors) so it can't add links or emphasis, or break the reply's layout.
+/// Without `[`/`]` no link can form, so parentheses are left alone.
+pub(crate) fn escape_markdown(text: &str) -> String {
+    let mut out = String::with_capacity(text.len());
+    for c in text.chars() {
+        if matches!(c, '\\' | '`' | '*' | '_' | '[' | ']' | '<' | '>' | '|' | '~') {
+            out.push('\\');
+        }
+        out.push(c);
+    }
+    out
+}
+
+// file:// link target; characters that would end the link early or split it are percent-encoded
+fn file_link(path: &str) -> String {
+    let mut url = String::from("file://");
+    for c in path.chars() {
+        match c {
+            '%' => url.push_str("%25"),
+            ' ' => url.push_str("%20"),
+            '(' => url.push_str("%28"),
+            ')' => url.push_str("%29"),
+            '<' => url.push_str("%3C"),
+            '>' => url.push_str("%3E"),
+            '[' => url.push_str("%5B"),
+            ']' => url.push_str("%5D"),
+            '`' => url.push_str("%60"),
+            _ => url.push(c),
+        }
+    }
+    url
+}
+
+// Inline code that survives backticks in the content: the fence is one longer than
+// the longest backtick run, padded with spaces when the content touches a backtick
+fn code_span(text: &str) -> String {
+    let longest = text.split(|c| c != '`').map(str::len).max().unwrap_or(0);
+    let fence = "`".repeat(longest + 1);
+    if longest > 0 {
+        format!("{} {} {}", fence, text, fence)
+    } else {
+        format!("{}{}{}", fence, text, fence)
+    }
+}
 
-struct AgentHandles(pub Arc<Mutex<Vec<Child>>>);
 fn friendly_sentence(raw: &str) -> String {
     let lower = raw.to_lowercase();
     // Remove common labels and reformulate
     if lower.starts_with("transcription:") {
         let msg = raw.splitn(2, ':').nth(1).unwrap_or("").trim();
         if msg.is_empty() { return "I attempted transcription.".to_string(); }
-        return format!("Regarding transcription, {}.", msg);
+        return format!("Regarding transcription, {}.", escape_markdown(msg));
     }
     if lower.starts_with("objects:") {
         // Try to pull caption snippet
         let without = raw.splitn(2, ':').nth(1).unwrap_or("").trim();
         if let Some(idx) = without.find("Caption:") {
             let cap = without[idx+8..].trim();
-            if !cap.is_empty() { return format!("From a video frame, {}.", cap); }
+            if !cap.is_empty() { return format!("From a video frame, {}.", escape_markdown(cap)); }
+        }
+        // Detection tables are rendered separately by format_conversational_response
+        let prose = without
+            .lines()
+            .map(str::trim)
+            .filter(|l| !l.is_empty() && !l.starts_with('|') && !l.starts_with("Caption:"))
+            .collect::<Vec<_>>()
+            .join(" ");
+        return format!("From a video frame, {}.", escape_markdown(prose.trim_end_matches(':')));
+    }
+    if lower.starts_with("key moments:") && moments_list(raw).is_some() {
+        // The numbered list itself is rendered after the bullets
+        let without = raw.splitn(2, ':').nth(1).unwrap_or("").trim();
+        let heading = without.lines().next().unwrap_or("").trim().trim_end_matches(':');
+        return format!("I found {}; ask \"show me moment 2\" to jump to one.", heading);
+    }
+    if lower.starts_with("translation (") {
+        if let Some((lang, text)) = raw["translation (".len()..].split_once("):") {
+            return format!("Here’s the {} translation: {}", lang, escape_markdown(text.trim()));
         }
-        return format!("From a video frame, {}.", without);
     }
     if lower.starts_with("graphs:") {
         let without = raw.splitn(2, ':').nth(1).unwrap_or("").trim();
-        return format!("On charts and graphs, {}.", without);
+        return format!("On charts and graphs, {}.", escape_markdown(without));
     }
     if lower.contains("generated at ") {
         // Try to extract local file path and present a nice markdown link
         // Expected formats from generation client:
         //   "PowerPoint generated at {path}"
         //   "PDF generated at {path}"
+        //   "DOCX generated at {path}"
         let mut path_part: Option<&str> = None;
-        if let Some(idx) = lower.find("generated at ") {
+        // Search raw itself: to_lowercase() can change byte lengths, so offsets
+        // found in `lower` aren't valid indexes into `raw`
+        if let Some(idx) = find_ignore_ascii_case(raw, "generated at ") {
             // Use the original raw string to preserve exact path casing
-            let start = idx + "generated at ".len();
-            if start <= raw.len() {
-                let p = &raw[start..].trim();
-                if !p.is_empty() { path_part = Some(p); }
-            }
+            let p = raw[idx + "generated at ".len()..].trim();
+            if !p.is_empty() { path_part = Some(p); }
         }
         if lower.contains("powerpoint") {
             if let Some(path) = path_part {
                 // Render a clickable link with a visible path
                 // WebView usually supports file:// links
-                let link = format!("file://{}", path);
-                return format!("PowerPoint generated. [Open file]({})\nPath: `{}`", link, path);
+                return format!("PowerPoint generated. [Open file]({})\nPath: {}", file_link(path), code_span(path));
             }
             return "PowerPoint generated and saved locally.".to_string();
         }
+        if lower.contains("docx") {
+            if let Some(path) = path_part {
+                return format!("Word document generated. [Open file]({})\nPath: {}", file_link(path), code_span(path));
+            }
+            return "Word document generated and saved locally.".to_string();
+        }
         if lower.contains("pdf") {
             if let Some(path) = path_part {
-                let link = format!("file://{}", path);
-                return format!("PDF generated. [Open file]({})\nPath: `{}`", link, path);
+                return format!("PDF generated. [Open file]({})\nPath: {}", file_link(path), code_span(path));
             }
             return "PDF generated and saved locally.".to_string();
         }
     }
-    raw.to_string()
+    escape_markdown(raw)
 }
 
 fn clamp_len(s: String, max: usize) -> String {
     if s.len() <= max { return s; }
+    // Walk back to a char boundary so multibyte text doesn't panic truncate()
+    let mut cut = max;
+    while !s.is_char_boundary(cut) { cut -= 1; }
     let mut t = s;
-    t.truncate(max);
+    t.truncate(cut);
     t.push_str("…");
     t
 }
 
-#[derive(Debug)]
-struct IntentScore {
-    transcribe: u8,
-    objects: u8,
-    graphs: u8,
-    ppt: u8,
-    pdf: u8,
-    summary: u8,
-}
-
-impl IntentScore {
-    fn from_message(msg: &str) -> Self {
-        let lower = msg.to_lowercase();
-        let mut score = IntentScore {
-            transcribe: 0,
-            objects: 0,
-            graphs: 0,
-            ppt: 0,
-            pdf: 0,
-            summary: 0,
-        };
-
-        // Strong signals (high confidence)
-        if lower.contains("transcribe the video") || lower.contains("transcript of") {
-            score.transcribe = 10;
-        } else if lower.contains("transcribe") || lower.contains("what is said") || lower.contains("what they say") {
-            score.transcribe = 7;
-        }
-
-        if lower.contains("what objects") || lower.contains("detect objects") || lower.contains("identify objects") {
-            score.objects = 10;
-        } else if lower.contains("object") || lower.contains("what is shown") || lower.contains("what's in the") {
-            score.objects = 6;
-        }
-
-        if lower.contains("are there") && (lower.contains("graph") || lower.contains("chart")) {
-            score.graphs = 10;
-        } else if lower.contains("graph") || lower.contains("chart") || lower.contains("diagram") {
-            score.graphs = 7;
-        }
-
-        if lower.contains("create a powerpoint") || lower.contains("generate powerpoint") || lower.contains("make a ppt") {
-            score.ppt = 10;
-        } else if lower.contains("powerpoint") || lower.contains("ppt") || lower.contains("presentation") {
-            score.ppt = 6;
-        }
-
-        if (lower.contains("summarize") || lower.contains("summary")) && lower.contains("pdf") {
-            score.summary = 10;
-            score.pdf = 10;
-        } else if lower.contains("generate pdf") || lower.contains("create pdf") {
-            score.pdf = 10;
-        } else if lower.contains("pdf") {
-            score.pdf = 5;
-        }
-
-        if lower.contains("summarize") || lower.contains("summary of") || lower.contains("recap") {
-            score.summary = 8;
-        }
-
-        score
-    }
-
-    fn is_ambiguous(&self) -> bool {
-        let low_threshold = 6;
-        let active_count = [
-            self.transcribe > 0 && self.transcribe < low_threshold,
-            self.objects > 0 && self.objects < low_threshold,
-            self.graphs > 0 && self.graphs < low_threshold,
-            self.ppt > 0 && self.ppt < low_threshold,
-            self.pdf > 0 && self.pdf < low_threshold,
-            self.summary > 0 && self.summary < low_threshold,
-        ]
-        .iter()
-        .filter(|&&x| x)
-        .count();
-
-        // Ambiguous if multiple weak signals or any signal is below threshold
-        active_count >= 2 || (active_count == 1 && self.max_score() < low_threshold)
-    }
-
-    fn max_score(&self) -> u8 {
-        *[self.transcribe, self.objects, self.graphs, self.ppt, self.pdf, self.summary]
-            .iter()
-            .max()
-            .unwrap_or(&0)
+// "Detected N object(s):" plus its markdown table, if an objects part carries one
+fn detection_table(part: &str) -> Option<String> {
+    let body = part.splitn(2, ':').nth(1)?.trim();
+    let table: Vec<&str> = body.lines().map(str::trim).filter(|l| l.starts_with('|')).collect();
+    if table.is_empty() {
+        return None;
     }
+    let heading = body.lines().next().unwrap_or("").trim();
+    Some(format!("{}\n\n{}", heading, table.join("\n")))
+}
 
-    fn has_any_intent(&self) -> bool {
-        self.max_score() > 0
-    }
-
-    fn get_clarification_message(&self) -> String {
-        let mut options = Vec::new();
-
-        if self.transcribe > 0 {
-            options.push("transcribe the audio");
-        }
-        if self.objects > 0 {
-            options.push("detect objects in the video");
-        }
-        if self.graphs > 0 {
-            options.push("identify charts or graphs");
-        }
-        if self.ppt > 0 {
-            options.push("create a PowerPoint presentation");
-        }
-        if self.pdf > 0 && self.summary == 0 {
-            options.push("generate a PDF document");
-        }
-        if self.summary > 0 {
-            options.push("summarize our conversation");
-        }
-
-        if options.is_empty() {
-            return "I'm not sure what you'd like me to do. Could you clarify? For example:\n\
-                    - \"Transcribe the video\"\n\
-                    - \"What objects are shown?\"\n\
-                    - \"Create a PowerPoint\"\n\
-                    - \"Summarize our discussion\"".to_string();
-        }
-
-        if options.len() == 1 {
-            return format!("Did you mean: {}? If so, please confirm or provide more details.", options[0]);
-        }
-
-        let formatted = options
-            .iter()
-            .enumerate()
-            .map(|(i, opt)| format!("{}. {}", i + 1, opt))
-            .collect::<Vec<_>>()
-            .join("\n");
-
-        format!("I detected multiple possible actions. Which would you like me to do?\n{}\n\nPlease specify by number or rephrase your request.", formatted)
-    }
+// The numbered "1. 0:42 — ..." lines of a key moments part
+fn moments_list(part: &str) -> Option<String> {
+    let lines: Vec<&str> = part
+        .lines()
+        .skip(1)
+        .map(str::trim)
+        .filter(|l| l.chars().next().is_some_and(|c| c.is_ascii_digit()))
+        .collect();
+    if lines.is_empty() { None } else { Some(lines.join("\n")) }
 }
 
 fn format_conversational_response(_file_id: &str, _user_msg: &str, parts: &[String]) -> String {
@@ -218,7 +188,7 @@ fn format_conversational_response(_file_id: &str, _user_msg: &str, parts: &[Stri
     for p in parts {
         if p.to_lowercase().starts_with("objects:") {
             if let Some(idx) = p.find("Caption:") {
-                caption_line = Some(p[idx..].replacen("Caption:", "", 1).trim().to_string());
+                caption_line = Some(escape_markdown(p[idx..].replacen("Caption:", "", 1).trim()));
                 break;
             }
         }
@@ -234,498 +204,3013 @@ fn format_conversational_response(_file_id: &str, _user_msg: &str, parts: &[Stri
         .map(|p| friendly_sentence(p))
         .filter(|p| !p.to_lowercase().contains("unavailable"))
         .collect();
-    // Dynamic length: allocate budget by number of items (aim for ~600 chars total)
-    let max_total = 600usize;
-    let per_item = std::cmp::max(140usize, max_total.saturating_div(std::cmp::max(1, cleaned.len())));
+    let cleaned = dedup_bullets(cleaned);
+    // Dynamic length: allocate the configured budget by number of items
+    let settings = settings::get();
+    let per_item = if settings.verbose {
+        usize::MAX
+    } else {
+        std::cmp::max(settings.response_min_item_chars, settings.response_max_chars.saturating_div(std::cmp::max(1, cleaned.len())))
+    };
     let bullets = cleaned
         .into_iter()
         .map(|p| clamp_len(p, per_item))
         .map(|p| format!("- {}", p))
         .collect::<Vec<_>>()
         .join("\n");
+    // Tables can't live inside a bullet or be clamped, so they follow the list
+    let tables: Vec<String> = parts
+        .iter()
+        .filter(|p| p.to_lowercase().starts_with("objects:"))
+        .filter_map(|p| detection_table(p))
+        .collect();
+    let bullets = if tables.is_empty() { bullets } else { format!("{}\n\n{}\n", bullets, tables.join("\n\n")) };
+    let lists: Vec<String> = parts
+        .iter()
+        .filter(|p| p.to_lowercase().starts_with("key moments:"))
+        .filter_map(|p| moments_list(p))
+        .collect();
+    let bullets = if lists.is_empty() { bullets } else { format!("{}\n\n{}\n", bullets, lists.join("\n\n")) };
     let outro = "I can analyze more frames or generate materials if you’d like.";
     format!("{}\n{}\n{}", intro, bullets, outro)
 }
 
-fn resolve_script(rel: &str) -> std::path::PathBuf {
-    // Try current working directory first (dev usually runs from repo root)
-    let cwd = std::env::current_dir().unwrap_or_else(|_| std::path::PathBuf::from("."));
-    let p1 = cwd.join(rel);
-    if p1.exists() { return p1; }
+// Word overlap (Jaccard) at which two bullets count as saying the same thing
+const NEAR_DUPLICATE_OVERLAP: f64 = 0.8;
 
-    // Try project root calculated from src-tauri manifest dir
-    let tauri_dir = std::path::PathBuf::from(env!("CARGO_MANIFEST_DIR"));
-    let project_root = tauri_dir.parent().unwrap_or(&tauri_dir);
-    let p2 = project_root.join(rel);
-    if p2.exists() { return p2; }
-
-    // Fall back to returning first candidate (even if missing)
-    p1
+fn normalize_bullet(text: &str) -> String {
+    text.to_lowercase().split_whitespace().collect::<Vec<_>>().join(" ")
 }
 
-fn ensure_dir(path: &std::path::Path) {
-    let _ = std::fs::create_dir_all(path);
+fn near_duplicate(a: &str, b: &str) -> bool {
+    if a == b {
+        return true;
+    }
+    let words_a: std::collections::HashSet<&str> = a.split(' ').collect();
+    let words_b: std::collections::HashSet<&str> = b.split(' ').collect();
+    let union = words_a.union(&words_b).count();
+    union > 0 && words_a.intersection(&words_b).count() as f64 / union as f64 >= NEAR_DUPLICATE_OVERLAP
 }
 
-fn generate_python_protos() {
-    // Generate Python gRPC stubs into backend/generated
-    let cwd = std::env::current_dir().unwrap_or_else(|_| std::path::PathBuf::from("."));
-    let gen_dir = cwd.join("backend/generated");
-    ensure_dir(&gen_dir);
-    let proto_path = cwd.join("proto/audio_service.proto");
-    if !proto_path.exists() { return; }
-
-    let status = Command::new("python3")
-        .args([
-            "-m",
-            "grpc_tools.protoc",
-            "-Iproto",
-            "--python_out=backend/generated",
-            "--grpc_python_out=backend/generated",
-            "proto/audio_service.proto",
-        ])
-        .stdout(Stdio::piped())
-        .stderr(Stdio::piped())
-        .status();
-    if let Ok(s) = status {
-        if !s.success() {
-            println!("[Tauri] Warning: failed to generate Python gRPC stubs (grpc_tools not installed?)");
+/// Drops bullets that repeat an earlier one. Order follows first appearance, but
+/// the longest variant of each near-duplicate group is the one kept.
+fn dedup_bullets(items: Vec<String>) -> Vec<String> {
+    let mut kept: Vec<(String, String)> = Vec::new();
+    for item in items {
+        let norm = normalize_bullet(&item);
+        match kept.iter_mut().find(|(k, _)| near_duplicate(k, &norm)) {
+            Some(slot) => {
+                if item.chars().count() > slot.1.chars().count() {
+                    *slot = (norm, item);
+                }
+            }
+            None => kept.push((norm, item)),
         }
     }
+    kept.into_iter().map(|(_, item)| item).collect()
+}
+
+async fn try_with_retry<T, F, Fut>(f: F) -> Result<T, String>
+where
+    F: FnMut() -> Fut,
+    Fut: std::future::Future<Output = Result<T, String>>,
+{
+    // Only unreachable-agent errors are retried; the rest fail fast with a friendly message
+    grpc_client::RetryPolicy::from_env().run(f).await.map_err(|e| e.to_string())
+}
+
+const MAX_KEY_POINTS: usize = 8;
+
+fn split_sentences(text: &str) -> Vec<String> {
+    text.split_inclusive(|c: char| c == '.' || c == '?' || c == '!')
+        .map(|s| s.trim().to_string())
+        .filter(|s| s.chars().count() >= 12)
+        .collect()
 }
 
-fn wait_for_port(port: u16, timeout_secs: u64) -> bool {
-    let deadline = Instant::now() + Duration::from_secs(timeout_secs);
-    let addr: SocketAddr = format!("127.0.0.1:{}", port).parse().unwrap();
-    while Instant::now() < deadline {
-        if TcpStream::connect_timeout(&addr, Duration::from_millis(300)).is_ok() {
-            return true;
+// Pull key points out of assistant replies (the friendly_sentence forms)
+fn key_points_from_reply(text: &str, points: &mut Vec<String>) {
+    for line in text.lines() {
+        let line = line.trim().trim_start_matches("- ");
+        if let Some(caption) = line.strip_prefix("From a video frame, ") {
+            points.push(format!("Visual: {}", caption.trim_end_matches('.')));
+        } else if let Some(summary) = line.strip_prefix("Summary: ") {
+            points.extend(split_sentences(summary).into_iter().take(3));
         }
-        std::thread::sleep(Duration::from_millis(200));
     }
-    false
 }
 
-fn spawn_python_agent(script_rel: &str, args: &[&str]) -> Option<Child> {
-    let script = resolve_script(script_rel);
-    if !script.exists() { 
-        println!("[Tauri] Agent script not found: {}", script.to_string_lossy());
-        return None; 
-    }
-    let mut cmd = Command::new("python3");
-    // Ensure Python can import generated stubs and backend package
-    let cwd = std::env::current_dir().unwrap_or_else(|_| std::path::PathBuf::from("."));
-    let gen_dir = cwd.join("backend/generated");
-    let backend_dir = cwd.join("backend");
-    let existing_pythonpath = std::env::var("PYTHONPATH").unwrap_or_default();
-    let new_pythonpath = if existing_pythonpath.is_empty() {
-        format!("{}:{}", gen_dir.to_string_lossy(), backend_dir.to_string_lossy())
-    } else {
-        format!("{}:{}:{}", existing_pythonpath, gen_dir.to_string_lossy(), backend_dir.to_string_lossy())
-    };
-    cmd.env("PYTHONPATH", new_pythonpath);
-    let mut child = cmd
-        .arg(script)
-        .args(args)
-        .stdout(Stdio::piped())
-        .stderr(Stdio::piped())
-        .spawn()
-        .ok()?;
-
-    // Stream stdout/stderr to console for easier debugging
-    if let Some(out) = child.stdout.take() {
-        let reader = BufReader::new(out);
-        std::thread::spawn(move || {
-            for line in reader.lines() {
-                if let Ok(l) = line { println!("[agent stdout] {}", l); }
-            }
-        });
+/// Gathers what is already known about a file (cached transcript, previous
+/// detections and summaries) into a short list of key points for generation.
+fn collect_key_points(file_id: &str) -> Vec<String> {
+    let mut points: Vec<String> = Vec::new();
+    if let Ok(Some(t)) = db::get_transcript(file_id) {
+        points.extend(split_sentences(&t.text).into_iter().take(4));
     }
-    if let Some(err) = child.stderr.take() {
-        let reader = BufReader::new(err);
-        std::thread::spawn(move || {
-            for line in reader.lines() {
-                if let Ok(l) = line { eprintln!("[agent stderr] {}", l); }
-            }
-        });
+    if let Ok(messages) = db::recent_messages(file_id, 20) {
+        for (text, is_user) in messages {
+            if !is_user { key_points_from_reply(&text, &mut points); }
+        }
     }
+    let mut seen = std::collections::HashSet::new();
+    points.retain(|p| seen.insert(p.to_lowercase()));
+    points.truncate(MAX_KEY_POINTS);
+    points
+}
 
-    Some(child)
+/// Cancellation tokens for in-flight chat and pipeline runs, keyed by file id.
+#[derive(Default)]
+struct RunningTasks {
+    next_id: std::sync::atomic::AtomicU64,
+    tokens: Mutex<std::collections::HashMap<String, (u64, CancellationToken)>>,
 }
 
-fn start_agents() -> Vec<Child> {
-    let mut children = Vec::new();
-    // Generate stubs first so servers can import auralink_pb2*
-    generate_python_protos();
-    // Start transcription, vision, generation servers if scripts exist
-    // Models will load automatically on startup when servers are instantiated
-    if let Some(c) = spawn_python_agent("backend/mcp/transcription_server.py", &["--port", "50051", "--model", "base"]) { 
-        println!("[Tauri] Started transcription agent on port 50051");
-        children.push(c); 
-    }
-    if let Some(c) = spawn_python_agent("backend/mcp/vision_server.py", &["--port", "50052"]) { 
-        println!("[Tauri] Started vision agent on port 50052");
-        children.push(c); 
+impl RunningTasks {
+    fn register(&self, file_id: &str) -> (u64, CancellationToken) {
+        let id = self.next_id.fetch_add(1, std::sync::atomic::Ordering::Relaxed);
+        let token = CancellationToken::new();
+        if let Ok(mut tokens) = self.tokens.lock() {
+            tokens.insert(file_id.to_string(), (id, token.clone()));
+        }
+        (id, token)
     }
-    if let Some(c) = spawn_python_agent("backend/mcp/generation_server.py", &["--port", "50053"]) { 
-        println!("[Tauri] Started generation agent on port 50053");
-        children.push(c); 
+
+    // A newer run for the same file may have replaced the entry; leave that one alone
+    fn finish(&self, file_id: &str, id: u64) {
+        if let Ok(mut tokens) = self.tokens.lock() {
+            if tokens.get(file_id).map(|(current, _)| *current) == Some(id) {
+                tokens.remove(file_id);
+            }
+        }
     }
-    // Wait briefly for ports to be ready to avoid initial transport errors
-    let mut ready_count = 0usize;
-    for (name, port) in [("transcription",50051u16),("vision",50052u16),("generation",50053u16)] {
-        if wait_for_port(port, 20) {
-            println!("[Tauri] {} agent is accepting connections on {}", name, port);
-            ready_count += 1;
-        } else {
-            println!("[Tauri] Warning: {} agent did not open port {} in time", name, port);
+
+    fn cancel(&self, file_id: &str) -> bool {
+        match self.tokens.lock() {
+            Ok(tokens) => match tokens.get(file_id) {
+                Some((_, token)) => { token.cancel(); true }
+                None => false,
+            },
+            Err(_) => false,
         }
     }
-    println!("[Tauri] Launched {} process(es); {} ready", children.len(), ready_count);
-    children
-}
 
-#[tauri::command]
-async fn save_message(file_id: String, text: String, is_user: bool) -> Result<(), String> {
-    let id = uuid::Uuid::new_v4().to_string();
-    let now = chrono::Utc::now().to_rfc3339();
-    db::insert_message(&id, &file_id, &text, is_user, &now).map_err(|e| e.to_string())
+    fn is_idle(&self) -> bool {
+        self.tokens.lock().map(|tokens| tokens.is_empty()).unwrap_or(true)
+    }
 }
 
-#[tauri::command]
-async fn get_messages(file_id: String, limit: i32, cursor: Option<String>)
-  -> Result<serde_json::Value, String> {
-    let page = db::list_messages(&file_id, limit as i64, cursor.as_deref())
-        .map_err(|e| e.to_string())?;
-    Ok(serde_json::json!({
-      "messages": page.messages,
-      "nextCursor": page.next_cursor
-    }))
+// Above this the extracted audio is uploaded in chunks rather than as one message
+const LARGE_AUDIO_BYTES: u64 = 32 * 1024 * 1024;
+
+/// Runs tasks for one file, sharing the thumbnail between vision steps.
+struct TaskRunner {
+    file_id: String,
+    app: Option<tauri::AppHandle>,
+    // (seconds, jpeg) frames shared by every vision step in this run
+    frames: Option<Vec<(f64, Vec<u8>)>>,
+    // Successful results from earlier steps in this run; feeds key points for later steps
+    findings: Vec<String>,
+    // Typed outcome of each step, for clients that render results natively
+    structured: Vec<serde_json::Value>,
+    cancel: Option<(u64, CancellationToken)>,
+    // Set once a step was skipped or interrupted by cancel_task
+    cancelled: bool,
+    // Language code of the transcript used in this run, if known
+    language: Option<String>,
 }
 
-#[tauri::command]
-async fn send_message(file_id: String, message: String) -> Result<String, String> {
-    // persist user message
-    save_message(file_id.clone(), message.clone(), true).await?;
-    
-    // Check if user is responding to a clarification with a number
-    let trimmed = message.trim();
-    let is_numeric_response = trimmed.len() == 1 && trimmed.chars().all(|c| c.is_numeric());
-    
-    // Map numeric responses to explicit intents
-    let resolved_message = if is_numeric_response {
-        match trimmed {
-            "1" => "transcribe the video".to_string(),
-            "2" => "what objects are shown in the video".to_string(),
-            "3" => "are there any graphs or charts".to_string(),
-            "4" => "create a powerpoint presentation".to_string(),
-            "5" => "generate a pdf document".to_string(),
-            "6" => "summarize our conversation".to_string(),
-            _ => message.clone(),
+impl Drop for TaskRunner {
+    fn drop(&mut self) {
+        if let (Some(app), Some((id, _))) = (&self.app, &self.cancel) {
+            app.state::<RunningTasks>().finish(&self.file_id, *id);
         }
-    } else {
-        message.clone()
-    };
-    
-    // Score the intent with confidence levels
-    let intent = IntentScore::from_message(&resolved_message);
-    
-    // Check if the query is ambiguous or low-confidence
-    if intent.is_ambiguous() {
-        let clarification = intent.get_clarification_message();
-        save_message(file_id.clone(), clarification.clone(), false).await?;
-        return Ok(clarification);
-    }
-    
-    // If no clear intent detected, ask for clarification
-    if !intent.has_any_intent() {
-        let clarification = "I'm not sure what you'd like me to do with this video. Could you provide more details? For example:\n\
-            - \"Transcribe the video\"\n\
-            - \"What objects are shown in the video?\"\n\
-            - \"Are there any graphs?\"\n\
-            - \"Create a PowerPoint with key points\"\n\
-            - \"Summarize our discussion and generate a PDF\"".to_string();
-        save_message(file_id.clone(), clarification.clone(), false).await?;
-        return Ok(clarification);
-    }
-    
-    // High-confidence routing based on scores (threshold >= 7 for auto-execution)
-    let confidence_threshold = 7u8;
-    let wants_transcribe = intent.transcribe >= confidence_threshold;
-    let wants_objects = intent.objects >= confidence_threshold;
-    let wants_graphs = intent.graphs >= confidence_threshold;
-    let wants_ppt = intent.ppt >= confidence_threshold;
-    let wants_summary_pdf = intent.summary >= confidence_threshold && intent.pdf >= confidence_threshold;
-    let wants_pdf = !wants_summary_pdf && intent.pdf >= confidence_threshold;
-
-    // helper: small retry for transient transport errors
-    fn sanitize_err(err: String) -> String {
-        let lower = err.to_lowercase();
-        // Common connectivity failures
-        if lower.contains("transport") || lower.contains("unavailable") || lower.contains("deadline") {
-            return "agent unavailable".to_string();
-        }
-        // Message too large from gRPC (e.g., sending whole video bytes)
-        if lower.contains("resourceexhausted") || lower.contains("message larger than max") {
-            return "request too large for a single call; try a shorter clip or let me extract audio automatically".to_string();
-        }
-        // Hide verbose metadata noise if present
-        if let Some(idx) = lower.find("metadata:") {
-            let trimmed = &err[..idx];
-            return format!("{}", trimmed.trim());
-        }
-        // Generic friendly fallback
-        "couldn’t complete this right now; please try again".to_string()
-    }
-
-    async fn retry<F, Fut>(mut f: F) -> String
-    where
-        F: FnMut() -> Fut,
-        Fut: std::future::Future<Output = Result<String, String>>,
-    {
-        let mut last = String::new();
-        for _ in 0..2 {
-            match f().await {
-                Ok(s) => return s,
-                Err(e) => { last = sanitize_err(e); tokio::time::sleep(std::time::Duration::from_millis(350)).await; }
-            }
+    }
+}
+
+impl TaskRunner {
+    fn new(file_id: &str, app: Option<tauri::AppHandle>) -> Self {
+        let mut runner = Self::background(file_id, app);
+        runner.cancel = runner.app.as_ref().map(|a| a.state::<RunningTasks>().register(file_id));
+        runner
+    }
+
+    /// A runner for work the user didn't ask for in chat (eager or batch
+    /// transcription). It still reports progress but isn't registered in
+    /// RunningTasks, so cancel_task keeps reaching the chat or pipeline run.
+    fn background(file_id: &str, app: Option<tauri::AppHandle>) -> Self {
+        Self {
+            file_id: file_id.to_string(),
+            app,
+            frames: None,
+            findings: Vec::new(),
+            structured: Vec::new(),
+            cancel: None,
+            cancelled: false,
+            language: None,
         }
-        format!("{}", last)
     }
 
-    let mut parts: Vec<String> = Vec::new();
+    // Sample frames once for all vision requests; fall back to the single thumbnail
+    async fn frames(&mut self, parts: &mut Vec<String>) -> Vec<(f64, Vec<u8>)> {
+        if self.frames.is_none() {
+            let frames = match sample_vision_frames(&self.file_id).await {
+                Ok(frames) if !frames.is_empty() => frames,
+                _ => match generate_thumbnail(self.file_id.clone(), None, None).await {
+                    // A .png thumbnail is an audio waveform, not a frame worth analyzing
+                    Ok(thumb_path) if thumb_path.ends_with(".png") => {
+                        parts.push("No video stream in this file (audio only), so there are no frames to analyze".to_string());
+                        Vec::new()
+                    }
+                    Ok(thumb_path) => match std::fs::read(&thumb_path) {
+                        Ok(b) => vec![(1.0, b)],
+                        Err(e) => { parts.push(format!("Failed to read thumbnail: {}", e)); Vec::new() }
+                    },
+                    Err(e) => { parts.push(format!("Failed to generate thumbnail: {}", e)); Vec::new() }
+                },
+            };
+            self.frames = Some(frames);
+        }
+        self.frames.clone().unwrap_or_default()
+    }
 
-    // Transcription
-    if wants_transcribe {
-        let part = match db::get_file_path(&file_id) {
-            Ok(Some(path)) => {
-                match std::fs::read(&path) {
-                    Ok(bytes) => retry(|| grpc_client::transcribe_video(file_id.clone(), bytes.clone())).await,
-                    Err(e) => format!("Failed to read file: {}", e),
-                }
+    async fn transcribe(&mut self) -> Result<String, String> {
+        let file_id = self.file_id.clone();
+        let path = match db::get_file_path(&file_id) {
+            Ok(Some(path)) => path,
+            Ok(None) => return Err("File not found for transcription".to_string()),
+            Err(e) => return Err(format!("Lookup error: {}", e)),
+        };
+        // Reuse the stored transcript unless the source file changed since
+        let content_hash = media::hash_file(std::path::Path::new(&path)).ok();
+        if let (Some(hash), Ok(Some(cached))) = (&content_hash, db::get_transcript(&file_id)) {
+            if &cached.content_hash == hash {
+                self.language = cached.language;
+                return Ok(cached.text);
             }
-            Ok(None) => "File not found for transcription".to_string(),
-            Err(e) => format!("Lookup error: {}", e),
+        }
+        // Send only a compact audio track; raw video quickly exceeds the gRPC message cap
+        let audio_path = match media::extract_audio(std::path::Path::new(&path)) {
+            Ok(p) => p,
+            Err(e) => return Err(format!("Failed to extract audio: {}", e)),
+        };
+        // Long recordings don't fit in one message; stream them up in chunks instead
+        // (no progressive text in that case)
+        let audio_len = std::fs::metadata(&audio_path).map(|m| m.len()).unwrap_or(0);
+        if audio_len > LARGE_AUDIO_BYTES {
+            let result = try_with_retry(|| grpc_client::transcribe_file_upload(file_id.clone(), &audio_path, "wav")).await;
+            let _ = std::fs::remove_file(&audio_path);
+            return self.finish_transcript(&file_id, content_hash.as_deref(), result);
+        }
+        let bytes = std::fs::read(&audio_path);
+        let _ = std::fs::remove_file(&audio_path);
+        let bytes = match bytes {
+            Ok(b) => b,
+            Err(e) => return Err(format!("Failed to read extracted audio: {}", e)),
         };
-        parts.push(format!("Transcription: {}", part));
+        let app = self.app.clone();
+        let result = try_with_retry(|| {
+            let app = app.clone();
+            let fid = file_id.clone();
+            grpc_client::transcribe_video_streaming(file_id.clone(), bytes.clone(), "wav", move |chunk| {
+                // Forward partial segments so long recordings show text progressively
+                if let Some(app) = &app {
+                    let _ = app.emit("transcription_progress", serde_json::json!({
+                        "fileId": fid,
+                        "text": chunk.text,
+                        "isFinal": chunk.is_final,
+                        "startTime": chunk.start_time,
+                        "endTime": chunk.end_time,
+                    }));
+                }
+            })
+        }).await;
+        self.finish_transcript(&file_id, content_hash.as_deref(), result)
     }
 
-    // Prepare a single thumbnail for all vision requests
-    let mut thumb_bytes: Option<Vec<u8>> = None;
-    if wants_objects || wants_graphs {
-        match generate_thumbnail(file_id.clone()).await {
-            Ok(thumb_path) => match std::fs::read(&thumb_path) {
-                Ok(b) => { thumb_bytes = Some(b); }
-                Err(e) => parts.push(format!("Failed to read thumbnail: {}", e)),
-            },
-            Err(e) => parts.push(format!("Failed to generate thumbnail: {}", e)),
+    fn finish_transcript(&mut self, file_id: &str, content_hash: Option<&str>, result: Result<grpc_client::Transcript, String>) -> Result<String, String> {
+        let transcript = result?;
+        if let Some(hash) = content_hash {
+            if !transcript.text.trim().is_empty() {
+                let _ = db::set_transcript(file_id, hash, &transcript);
+            }
         }
+        self.language = transcript.language;
+        Ok(transcript.text)
     }
 
-    if wants_objects {
-        let part = if let Some(b) = &thumb_bytes { retry(|| grpc_client::vision_detect_objects(b.clone())).await } else { "Vision unavailable".to_string() };
-        parts.push(format!("Objects: {}", part));
+    // Transcript pieces with their start times, via the transcript cache
+    async fn transcript_segments(&mut self) -> Result<Vec<(f64, String)>, String> {
+        self.transcribe().await?;
+        let segments = db::get_transcript_segments(&self.file_id).map_err(|e| e.to_string())?;
+        Ok(segments.into_iter().map(|s| (s.start, s.text)).collect())
     }
 
-    if wants_graphs {
-        let part = if let Some(b) = &thumb_bytes { retry(|| grpc_client::vision_identify_graphs(b.clone())).await } else { "Vision unavailable".to_string() };
-        parts.push(format!("Graphs: {}", part));
+    // This run's findings take precedence over what is stored from earlier turns
+    fn key_points(&self) -> Vec<String> {
+        let mut points = Vec::new();
+        for part in &self.findings {
+            key_points_from_reply(&friendly_sentence(part), &mut points);
+        }
+        points.extend(collect_key_points(&self.file_id));
+        let mut seen = std::collections::HashSet::new();
+        points.retain(|p| seen.insert(p.to_lowercase()));
+        points.truncate(MAX_KEY_POINTS);
+        points
     }
 
-    // Generation flows
-    if wants_ppt {
-        let part = retry(|| grpc_client::generation_generate_powerpoint(file_id.clone(), vec![])).await;
-        parts.push(format!("PowerPoint: {}", part));
+    // PowerPoint/PDF/DOCX build with the agent's phases forwarded as generation_progress
+    async fn generate_document(&self, task: Task, output_format: &str) -> Result<String, String> {
+        let key_points = self.key_points();
+        let file_id = self.file_id.clone();
+        let app = self.app.clone();
+        try_with_retry(|| {
+            let (app, fid) = (app.clone(), file_id.clone());
+            grpc_client::generation_generate_streaming(file_id.clone(), key_points.clone(), output_format, move |p| {
+                if let Some(app) = &app {
+                    let _ = app.emit("generation_progress", serde_json::json!({
+                        "fileId": fid,
+                        "step": task.name(),
+                        "phase": p.phase,
+                        "fraction": p.fraction,
+                    }));
+                }
+            })
+        }).await
     }
-    if wants_summary_pdf {
-        let summary = retry(|| grpc_client::generation_generate_summary(file_id.clone(), 100)).await;
-        let pdf = retry(|| grpc_client::generation_generate_pdf(file_id.clone(), vec![])).await;
-        parts.push(format!("Summary: {}", summary));
-        parts.push(format!("PDF: {}", pdf));
-    } else if wants_pdf {
-        let pdf = retry(|| grpc_client::generation_generate_pdf(file_id.clone(), vec![])).await;
-        parts.push(format!("PDF: {}", pdf));
+
+    fn progress(&self, task: Task, status: &str) {
+        if let Some(app) = &self.app {
+            let _ = app.emit("task_progress", serde_json::json!({
+                "fileId": self.file_id,
+                "step": task.name(),
+                "label": task.label(),
+                "status": status,
+            }));
+        }
     }
 
-    let ai_text = format_conversational_response(&file_id, &message, &parts);
-    // persist AI reply
-    save_message(file_id.clone(), ai_text.to_string(), false).await?;
-    Ok(ai_text.to_string())
-}
+    async fn run(&mut self, task: Task, params: &serde_json::Map<String, serde_json::Value>) -> Vec<String> {
+        let token = self.cancel.as_ref().map(|(_, t)| t.clone());
+        if self.cancelled || token.as_ref().is_some_and(|t| t.is_cancelled()) {
+            self.cancelled = true;
+            return Vec::new();
+        }
+        if !settings::get().agent_enabled(task.agent()) {
+            let text = disabled_text(task);
+            // Objects and graphs share an agent; say it once per reply
+            let said = self.structured.iter().any(|s| s["data"]["text"] == text.as_str());
+            self.structured.push(serde_json::json!({
+                "type": task.name(), "ok": false, "data": { "text": text, "disabled": true }
+            }));
+            self.progress(task, "disabled");
+            return if said { Vec::new() } else { vec![text] };
+        }
+        self.progress(task, "started");
+        // Dropping the step future on cancel also drops any in-flight gRPC call
+        let parts = match token {
+            Some(token) => tokio::select! {
+                parts = self.run_step(task, params) => Some(parts),
+                _ = token.cancelled() => None,
+            },
+            None => Some(self.run_step(task, params).await),
+        };
+        match parts {
+            Some(parts) => {
+                self.progress(task, "completed");
+                parts
+            }
+            None => {
+                self.cancelled = true;
+                self.progress(task, "cancelled");
+                Vec::new()
+            }
+        }
+    }
 
-#[tauri::command]
-fn get_temp_path() -> Result<String, String> {
-    Ok(std::env::temp_dir()
-        .to_string_lossy()
-        .to_string())
-}
+    // Keeps a typed copy of a step's outcome and returns the text for the chat reply
+    fn record(&mut self, kind: &str, result: Result<String, String>, data: Option<serde_json::Value>) -> String {
+        let (ok, text) = match result {
+            Ok(text) => (true, text),
+            Err(e) => (false, e),
+        };
+        let mut data = data.unwrap_or_else(|| serde_json::json!({}));
+        data["text"] = serde_json::json!(text);
+        self.structured.push(serde_json::json!({ "type": kind, "ok": ok, "data": data }));
+        text
+    }
 
-#[tauri::command]
-async fn upload_video_bytes(file_id: String, bytes: Vec<u8>) -> Result<String, String> {
-    // Fire-and-forget transcription; do not fail UI if backend is down
-    let _ = grpc_client::transcribe_video(file_id.clone(), bytes).await;
-    Ok("ok".to_string())
-}
+    // Generation replies read "<Label> generated at <path>" for local files; remote
+    // agents report paths we can't open, so those aren't recorded. Local documents
+    // are moved under artifacts/<file_id>/ first, so cleanup knows which are ours.
+    fn save_artifact(&self, task: Task, result: Result<String, String>) -> Result<String, String> {
+        let text = result?;
+        let Some((lead, path)) = text.split_once("generated at ") else { return Ok(text) };
+        let path = match adopt_artifact(&self.file_id, std::path::Path::new(path.trim())) {
+            Ok(adopted) => adopted.to_string_lossy().to_string(),
+            Err(e) => {
+                println!("[Tauri] Warning: couldn't move {} into the library: {}", path.trim(), e);
+                path.trim().to_string()
+            }
+        };
+        if let Err(e) = db::insert_artifact(&self.file_id, task.name(), &path) {
+            println!("[Tauri] Warning: failed to record {} artifact: {}", task.name(), e);
+        }
+        Ok(format!("{}generated at {}", lead, path))
+    }
 
-#[tauri::command]
-async fn auth_callback() -> Result<serde_json::Value, String> {
-    // Placeholder: perform any local setup if needed (e.g., creating a user row)
-    Ok(serde_json::json!({ "success": true }))
+    async fn run_step(&mut self, task: Task, params: &serde_json::Map<String, serde_json::Value>) -> Vec<String> {
+        let file_id = self.file_id.clone();
+        let mut parts: Vec<String> = Vec::new();
+        match task {
+            Task::Transcribe => {
+                let result = self.transcribe().await;
+                let ok = result.is_ok();
+                let language = self.language.clone();
+                let name = language.as_deref().map(|code| intent::language_name(code).map(str::to_string).unwrap_or_else(|| code.to_uppercase()));
+                let data = serde_json::json!({ "language": language, "languageName": name });
+                let part = self.record("transcript", result, Some(data));
+                parts.push(format!("Transcription: {}", part));
+                if let (true, Some(name)) = (ok, name) {
+                    parts.push(format!("Detected language: {}", name));
+                }
+            }
+            Task::Objects => {
+                let frames = self.frames(&mut parts).await;
+                let part = match detect_objects_across(&frames).await {
+                    Ok(found) => {
+                        let summary = grpc_client::summarize_detections(&found);
+                        self.findings.push(format!("Objects: {}", summary));
+                        let mut data = grpc_client::detections_json(&found);
+                        data["framesAnalyzed"] = serde_json::json!(frames.len());
+                        self.record("objects", Ok(summary), Some(data))
+                    }
+                    Err(e) => self.record("objects", Err(e), None),
+                };
+                parts.push(format!("Objects: {}", part));
+            }
+            Task::Graphs => {
+                let frames = self.frames(&mut parts).await;
+                let part = match identify_graphs_across(&frames).await {
+                    Ok((text, data)) => self.record("graphs", Ok(text), Some(data)),
+                    Err(e) => self.record("graphs", Err(e), None),
+                };
+                parts.push(format!("Graphs: {}", part));
+            }
+            Task::Ppt => {
+                let result = self.generate_document(task, "ppt").await;
+                let result = self.save_artifact(task, result);
+                let part = self.record("powerpoint", result, None);
+                parts.push(format!("PowerPoint: {}", part));
+            }
+            Task::Pdf => {
+                let result = self.generate_document(task, "pdf").await;
+                let result = self.save_artifact(task, result);
+                let pdf = self.record("pdf", result, None);
+                parts.push(format!("PDF: {}", pdf));
+            }
+            Task::Docx => {
+                let result = self.generate_document(task, "docx").await;
+                let result = self.save_artifact(task, result);
+                let docx = self.record("docx", result, None);
+                parts.push(format!("DOCX: {}", docx));
+            }
+            Task::Summary => {
+                let limit = params.get("message_limit").and_then(|v| v.as_i64()).unwrap_or(100) as i32;
+                let history = conversation_history(&file_id, limit.max(0) as usize, None);
+                let result = try_with_retry(|| grpc_client::generation_generate_summary(file_id.clone(), limit, history.clone())).await;
+                if let Ok(summary) = &result {
+                    self.findings.push(format!("Summary: {}", summary));
+                }
+                let summary = self.record("summary", result, None);
+                parts.push(format!("Summary: {}", summary));
+            }
+            Task::SummaryPdf => {
+                let limit = params.get("message_limit").and_then(|v| v.as_i64()).unwrap_or(100) as i32;
+                let history = conversation_history(&file_id, limit.max(0) as usize, None);
+                let result = try_with_retry(|| grpc_client::generation_generate_summary(file_id.clone(), limit, history.clone())).await;
+                if let Ok(summary) = &result {
+                    self.findings.push(format!("Summary: {}", summary));
+                }
+                let summary = self.record("summary", result, None);
+                let result = self.generate_document(task, "pdf").await;
+                let result = self.save_artifact(task, result);
+                let pdf = self.record("pdf", result, None);
+                parts.push(format!("Summary: {}", summary));
+                parts.push(format!("PDF: {}", pdf));
+            }
+            Task::Translate => {
+                let Some(language) = params.get("target_language").and_then(|v| v.as_str()).map(str::to_string) else {
+                    let part = self.record("translation", Err("Which language should I translate into? For example: \"translate to Spanish\".".to_string()), None);
+                    parts.push(part);
+                    return parts;
+                };
+                // The cached transcript is the source; transcribe first if there isn't one yet
+                let result = match self.transcribe().await {
+                    Ok(source) => try_with_retry(|| grpc_client::translate_text(file_id.clone(), source.clone(), language.clone())).await,
+                    Err(e) => Err(format!("No transcript to translate: {}", e)),
+                };
+                let part = self.record("translation", result, Some(serde_json::json!({ "language": language })));
+                parts.push(format!("Translation ({}): {}", language, part));
+            }
+            Task::KeyMoments => {
+                let frames = self.frames(&mut parts).await;
+                // Either source alone still gives a usable (if thinner) list
+                let (segments, captions) = tokio::join!(self.transcript_segments(), frame_captions(&frames));
+                let moments = build_key_moments(&segments.unwrap_or_default(), &captions.unwrap_or_default());
+                let part = if moments.is_empty() {
+                    self.record("key_moments", Err("Couldn't find any key moments; the transcript and frame analysis both came back empty.".to_string()), None)
+                } else {
+                    if let Err(e) = db::set_key_moments(&file_id, &moments) {
+                        println!("[Tauri] Warning: failed to store key moments: {}", e);
+                    }
+                    let list = moments
+                        .iter()
+                        .enumerate()
+                        .map(|(i, (at, label))| format!("{}. {} — {}", i + 1, media::format_timestamp(*at), escape_markdown(label)))
+                        .collect::<Vec<_>>()
+                        .join("\n");
+                    let data = serde_json::json!({
+                        "moments": moments
+                            .iter()
+                            .enumerate()
+                            .map(|(i, (at, label))| serde_json::json!({ "index": i + 1, "at": at, "timestamp": media::format_timestamp(*at), "label": label }))
+                            .collect::<Vec<_>>(),
+                    });
+                    self.record("key_moments", Ok(format!("{} key moment(s):\n{}", moments.len(), list)), Some(data))
+                };
+                parts.push(format!("Key moments: {}", part));
+            }
+        }
+        parts
+    }
 }
 
-#[tauri::command]
-async fn get_app_data_dir() -> Result<String, String> {
-    Ok(db::db_path()
-        .parent()
-        .unwrap_or(std::path::Path::new("."))
-        .to_string_lossy()
-        .to_string())
+fn disabled_text(task: Task) -> String {
+    let feature = match task.agent() {
+        "transcription" => "Transcription",
+        "vision" => "Video analysis (objects and graphs)",
+        _ => "Document generation",
+    };
+    format!("{} is disabled in settings; turn on enable_{} to use it.", feature, task.agent())
 }
 
-#[tauri::command]
-async fn register_file(file_id: String, path: String) -> Result<(), String> {
-    let now = chrono::Utc::now().to_rfc3339();
-    let name = std::path::Path::new(&path)
-        .file_name()
-        .unwrap_or_default()
-        .to_string_lossy()
-        .to_string();
-    db::insert_file(&file_id, &name, &path, &now).map_err(|e| e.to_string())
-}
+const MAX_KEY_MOMENTS: usize = 8;
+const MOMENT_TEXT_CHARS: usize = 90;
 
-#[tauri::command]
-async fn get_file_path(file_id: String) -> Result<Option<String>, String> {
-    db::get_file_path(&file_id).map_err(|e| e.to_string())
+// One caption per sampled frame, skipping frames the vision agent couldn't read
+async fn frame_captions(frames: &[(f64, Vec<u8>)]) -> Result<Vec<(f64, String)>, String> {
+    let mut captions = Vec::new();
+    let mut last_err = None;
+    for (at, bytes) in frames {
+        match try_with_retry(|| grpc_client::vision_detect_objects_detailed(bytes.clone())).await {
+            Ok(found) if !found.caption.trim().is_empty() => captions.push((*at, found.caption.trim().to_string())),
+            Ok(_) => {}
+            Err(e) => last_err = Some(e),
+        }
+    }
+    match (captions.is_empty(), last_err) {
+        (true, Some(e)) => Err(e),
+        _ => Ok(captions),
+    }
 }
 
-#[allow(non_snake_case)]
-#[derive(serde::Serialize)]
-struct FileItem { id: String, name: String, path: String, thumbPath: Option<String>, createdAt: String }
-
-#[tauri::command]
-async fn list_files() -> Result<Vec<FileItem>, String> {
-    let rows = db::list_files().map_err(|e| e.to_string())?;
-    let items = rows
-        .into_iter()
-        .map(|r| FileItem {
-            id: r.id.clone(),
-            name: r.name.unwrap_or_else(|| std::path::Path::new(&r.path)
-                .file_name()
-                .unwrap_or_default()
-                .to_string_lossy()
-                .to_string()),
-            path: r.path,
-            thumbPath: r.thumb_path,
-            createdAt: r.created_at,
-        })
-        .collect();
-    Ok(items)
+/// Pairs each sampled frame's caption with what was being said at that time. Without
+/// frames, evenly spaced transcript segments stand in.
+fn build_key_moments(segments: &[(f64, String)], captions: &[(f64, String)]) -> Vec<(f64, String)> {
+    let mut moments: Vec<(f64, String)> = Vec::new();
+    if captions.is_empty() {
+        let step = segments.len().div_ceil(MAX_KEY_MOMENTS).max(1);
+        for (at, text) in segments.iter().step_by(step) {
+            moments.push((*at, clamp_len(text.clone(), MOMENT_TEXT_CHARS)));
+        }
+    } else {
+        for (at, caption) in captions.iter().take(MAX_KEY_MOMENTS) {
+            // The segment being spoken at `at`: the last one that started by then
+            let said = segments.iter().take_while(|(start, _)| start <= at).last().or(segments.first());
+            let label = match said {
+                Some((_, text)) => format!("{} (“{}”)", caption, clamp_len(text.clone(), MOMENT_TEXT_CHARS)),
+                None => caption.clone(),
+            };
+            moments.push((*at, label));
+        }
+    }
+    moments.dedup_by(|a, b| a.1 == b.1);
+    moments
 }
 
-#[tauri::command]
-async fn delete_file(id: String) -> Result<(), String> {
-    // try to remove the actual file if it exists
-    if let Ok(Some(path)) = db::get_file_path(&id) { let _ = std::fs::remove_file(path); }
-    db::delete_file(&id).map_err(|e| e.to_string())
-}
+const MAX_VISION_FRAMES: u32 = 12;
 
-#[tauri::command]
-async fn save_file_bytes(file_id: String, ext: String, bytes: Vec<u8>, name: Option<String>) -> Result<String, String> {
-    // Determine app data directory (same as DB)
-    let dir = db::db_path()
-        .parent()
-        .unwrap_or(std::path::Path::new("."))
-        .to_path_buf();
-    let _ = std::fs::create_dir_all(&dir);
-    let path = dir.join(format!("{}.{}", file_id, ext));
-    std::fs::write(&path, &bytes).map_err(|e| e.to_string())?;
-    let now = chrono::Utc::now().to_rfc3339();
-    let file_name = name.unwrap_or_else(|| format!("{}.{}", file_id, ext));
-    db::insert_file(&file_id, &file_name, &path.to_string_lossy(), &now).map_err(|e| e.to_string())?;
-    // Try to generate a thumbnail immediately (best effort)
-    if let Ok(p) = generate_thumbnail(file_id.clone()).await { let _ = db::set_file_thumb(&file_id, &p); }
-    Ok(path.to_string_lossy().to_string())
+async fn sample_vision_frames(file_id: &str) -> Result<Vec<(f64, Vec<u8>)>, String> {
+    let in_path = db::get_file_path(file_id).map_err(|e| e.to_string())?
+        .ok_or_else(|| "File not found".to_string())?;
+    let count = settings::get().vision_frames.clamp(1, MAX_VISION_FRAMES);
+    let dir = std::env::temp_dir().join(format!("auralink-frames-{}", uuid::Uuid::new_v4()));
+    tokio::task::spawn_blocking(move || {
+        let sampled = media::sample_frames(std::path::Path::new(&in_path), count, 640, &dir);
+        let frames = sampled.and_then(|frames| {
+            frames
+                .into_iter()
+                .map(|(at, path)| std::fs::read(&path).map(|b| (at, b)).map_err(|e| e.to_string()))
+                .collect::<Result<Vec<_>, String>>()
+        });
+        let _ = std::fs::remove_dir_all(&dir);
+        frames
+    })
+    .await
+    .map_err(|e| e.to_string())?
 }
 
-#[tauri::command]
-async fn read_file_bytes(file_id: String) -> Result<Vec<u8>, String> {
-    match db::get_file_path(&file_id) {
-        Ok(Some(path)) => std::fs::read(path).map_err(|e| e.to_string()),
-        Ok(None) => Err("File not found".to_string()),
-        Err(e) => Err(e.to_string()),
+// The frame at `at` seconds through object and graph detection. Errors are
+// worded for the chat, since they go straight into the reply.
+async fn describe_frame_at(file_id: &str, at: f64) -> Result<(String, serde_json::Value), String> {
+    if !settings::get().agent_enabled("vision") {
+        return Err(disabled_text(Task::Objects));
     }
-}
-
-#[tauri::command]
-async fn generate_thumbnail(file_id: String) -> Result<String, String> {
-    // Find input path
-    let in_path = db::get_file_path(&file_id).map_err(|e| e.to_string())?
+    let path = db::get_file_path(file_id).map_err(|e| e.to_string())?
         .ok_or_else(|| "File not found".to_string())?;
-
-    // Ensure output directory
-    let db_path = db::db_path();
-    let base_dir = db_path.parent().unwrap_or(std::path::Path::new(".")).to_path_buf();
-    let thumbs_dir = base_dir.join("thumbs");
-    std::fs::create_dir_all(&thumbs_dir).map_err(|e| e.to_string())?;
-    let out_path = thumbs_dir.join(format!("{}.jpg", file_id));
-
-    // Build ffmpeg command: capture at 1s and scale
-    let output = Command::new("ffmpeg")
-        .args(["-y", "-ss", "00:00:01", "-i", &in_path, "-frames:v", "1", "-vf", "scale=320:-1", out_path.to_string_lossy().as_ref()])
-        .output()
-        .map_err(|e| format!("Failed to run ffmpeg: {}", e))?;
-
-    if !output.status.success() {
-        let mut msg = String::from("ffmpeg failed to generate thumbnail");
-        if !output.stderr.is_empty() {
-            msg.push_str(": ");
-            msg.push_str(&String::from_utf8_lossy(&output.stderr));
+    let out = std::env::temp_dir().join(format!("auralink-frame-{}.jpg", uuid::Uuid::new_v4()));
+    let (at, frame) = tokio::task::spawn_blocking(move || {
+        let video = std::path::Path::new(&path);
+        if !media::has_video_stream(video)? {
+            return Err("This file is audio only, so there's no picture to look at.".to_string());
         }
-        return Err(msg);
-    }
-
-    Ok(out_path.to_string_lossy().to_string())
-}
+        let duration = media::probe_duration(video)?;
+        if at > duration + 0.5 {
+            return Err(format!(
+                "The video is only {} long, so there's nothing at {}.",
+                media::format_timestamp(duration),
+                media::format_timestamp(at)
+            ));
+        }
+        // Seeking to the very end yields no frame; step back slightly
+        let at = at.min((duration - 0.1).max(0.0));
+        media::extract_frame(video, at, 640, &out)?;
+        let bytes = std::fs::read(&out).map_err(|e| e.to_string());
+        let _ = std::fs::remove_file(&out);
+        bytes.map(|b| (at, b))
+    })
+    .await
+    .map_err(|e| e.to_string())??;
 
-#[tauri::command]
-async fn backfill_thumbnails() -> Result<usize, String> {
-    let rows = db::list_files().map_err(|e| e.to_string())?;
-    let mut updated = 0usize;
-    for r in rows {
-        if r.thumb_path.is_some() { continue; }
-        // Skip if source file is missing
-        if !std::path::Path::new(&r.path).exists() { continue; }
-        match generate_thumbnail(r.id.clone()).await {
-            Ok(p) => {
-                let _ = db::set_file_thumb(&r.id, &p);
-                updated += 1;
-            }
-            Err(_) => {
-                // ignore and continue; we want best-effort
+    let frames = vec![(at, frame)];
+    let (objects, graphs) = tokio::join!(
+        try_with_retry(|| grpc_client::vision_detect_objects_detailed(frames[0].1.clone())),
+        identify_graphs_across(&frames),
+    );
+    if let (Err(e), Err(_)) = (&objects, &graphs) {
+        return Err(e.clone());
+    }
+    let timestamp = media::format_timestamp(at);
+    let mut lines = vec![format!("At {}:", timestamp)];
+    let mut data = serde_json::json!({ "at": at, "timestamp": timestamp });
+    match &objects {
+        Ok(found) => {
+            if !found.caption.trim().is_empty() {
+                lines.push(escape_markdown(found.caption.trim()));
             }
+            lines.push(format!("Objects: {}", grpc_client::summarize_detections(found)));
+            data["objects"] = grpc_client::detections_json(found);
         }
+        Err(e) => lines.push(format!("Objects: {}", e)),
     }
-    Ok(updated)
+    match graphs {
+        Ok((text, found)) => {
+            lines.push(text);
+            data["graphs"] = found;
+        }
+        Err(e) => lines.push(format!("Graphs: {}", e)),
+    }
+    Ok((lines.join("\n"), data))
+}
+
+// Runs detection on every frame and merges by label, keeping each label's most
+// confident sighting. The caption comes from the frame with the most detections.
+async fn detect_objects_across(frames: &[(f64, Vec<u8>)]) -> Result<grpc_client::auralink::ObjectDetectionResponse, String> {
+    if frames.is_empty() {
+        return Err("Vision unavailable".to_string());
+    }
+    let mut merged: Vec<grpc_client::auralink::DetectedObject> = Vec::new();
+    let mut caption = (0usize, String::new());
+    // Frames are sampled at one size, so any reported frame size applies to every box
+    let mut frame_size = (0, 0);
+    let mut last_err = None;
+    let mut succeeded = 0usize;
+    for (_, bytes) in frames {
+        match try_with_retry(|| grpc_client::vision_detect_objects_detailed(bytes.clone())).await {
+            Ok(found) => {
+                succeeded += 1;
+                if found.image_width > 0 && found.image_height > 0 {
+                    frame_size = (found.image_width, found.image_height);
+                }
+                if caption.1.is_empty() || found.objects.len() > caption.0 {
+                    caption = (found.objects.len(), found.caption.clone());
+                }
+                for object in found.objects {
+                    match merged.iter_mut().find(|o| o.label.eq_ignore_ascii_case(&object.label)) {
+                        Some(existing) if existing.confidence >= object.confidence => {}
+                        Some(existing) => *existing = object,
+                        None => merged.push(object),
+                    }
+                }
+            }
+            Err(e) => last_err = Some(e),
+        }
+    }
+    if succeeded == 0 {
+        return Err(last_err.unwrap_or_else(|| "Vision unavailable".to_string()));
+    }
+    Ok(grpc_client::auralink::ObjectDetectionResponse {
+        objects: merged,
+        caption: caption.1,
+        image_width: frame_size.0,
+        image_height: frame_size.1,
+    })
+}
+
+// Scans every frame for charts and reports when each one was seen
+async fn identify_graphs_across(frames: &[(f64, Vec<u8>)]) -> Result<(String, serde_json::Value), String> {
+    if frames.is_empty() {
+        return Err("Vision unavailable".to_string());
+    }
+    let mut sightings: Vec<(String, f64)> = Vec::new();
+    let mut description = String::new();
+    let mut last_err = None;
+    let mut succeeded = 0usize;
+    for (at, bytes) in frames {
+        match try_with_retry(|| grpc_client::vision_identify_graphs_detailed(bytes.clone())).await {
+            Ok(found) => {
+                succeeded += 1;
+                // Prefer the description of a frame that actually had a chart
+                if description.is_empty() || (sightings.is_empty() && !found.graphs.is_empty()) {
+                    description = found.description.clone();
+                }
+                for graph in found.graphs {
+                    sightings.push((graph.r#type, *at));
+                }
+            }
+            Err(e) => last_err = Some(e),
+        }
+    }
+    if succeeded == 0 {
+        return Err(last_err.unwrap_or_else(|| "Vision unavailable".to_string()));
+    }
+    let data = serde_json::json!({
+        "graphs": sightings
+            .iter()
+            .map(|(kind, at)| serde_json::json!({ "type": kind, "at": at, "timestamp": media::format_timestamp(*at) }))
+            .collect::<Vec<_>>(),
+        "description": description,
+        "framesAnalyzed": frames.len(),
+    });
+    if sightings.is_empty() {
+        return Ok((format!("No graphs detected in {} frame(s). {}", frames.len(), description), data));
+    }
+    let kinds = sightings
+        .iter()
+        .map(|(kind, at)| format!("{} at {}", kind, media::format_timestamp(*at)))
+        .collect::<Vec<_>>()
+        .join(", ");
+    Ok((format!("Graphs detected: {}. {}", kinds, description), data))
+}
+
+async fn probe_agent(addr: &str) -> &'static str {
+    let target = grpc_client::socket_target(addr);
+    match tokio::time::timeout(Duration::from_millis(400), tokio::net::TcpStream::connect(target)).await {
+        Ok(Ok(_)) => "ready",
+        _ => "down",
+    }
+}
+
+#[tauri::command]
+async fn agent_status() -> Result<serde_json::Value, AppError> {
+    // Probes run concurrently so the whole check stays well under a second
+    let endpoints = grpc_client::GrpcEndpoints::current();
+    let (transcription, vision, generation) = tokio::join!(
+        probe_agent(&endpoints.transcription),
+        probe_agent(&endpoints.vision),
+        probe_agent(&endpoints.generation),
+    );
+    Ok(serde_json::json!({
+        "transcription": transcription,
+        "vision": vision,
+        "generation": generation,
+    }))
+}
+
+// First line `tool <flag>` prints (Python 2 writes its version to stderr)
+fn tool_version(tool: &str, flag: &str) -> Result<String, String> {
+    let out = std::process::Command::new(tool).arg(flag).output().map_err(|e| e.to_string())?;
+    let text = if out.stdout.is_empty() { out.stderr } else { out.stdout };
+    String::from_utf8_lossy(&text)
+        .lines()
+        .next()
+        .map(|l| l.trim().to_string())
+        .filter(|l| !l.is_empty())
+        .ok_or_else(|| format!("{} printed no version", tool))
+}
+
+// Environment overrides in effect; anything that looks like a credential is masked
+fn auralink_env() -> serde_json::Map<String, serde_json::Value> {
+    let mut vars: Vec<(String, String)> = std::env::vars().filter(|(k, _)| k.starts_with("AURALINK_")).collect();
+    vars.sort();
+    vars.into_iter()
+        .map(|(k, v)| {
+            let secret = ["TOKEN", "SECRET", "KEY", "PASSWORD"].iter().any(|w| k.contains(w));
+            let v = if secret { "<redacted>".to_string() } else { v };
+            (k, serde_json::json!(v))
+        })
+        .collect()
+}
+
+/// Environment and state for bug reports: app version, platform, Python and
+/// ffmpeg versions, agents, database and the configuration in effect. Each probe
+/// reports its own error instead of failing the whole bundle.
+#[tauri::command]
+async fn diagnostics(app: tauri::AppHandle) -> serde_json::Value {
+    let versions = tokio::task::spawn_blocking(|| {
+        let report = |r: Result<String, String>| match r {
+            Ok(v) => serde_json::json!({ "version": v }),
+            Err(e) => serde_json::json!({ "error": e }),
+        };
+        let python = agents::resolve_python();
+        serde_json::json!({
+            "python": { "interpreter": python, "detected": report(tool_version(python, "--version")) },
+            "ffmpeg": report(tool_version("ffmpeg", "-version")),
+            "ffprobe": report(tool_version("ffprobe", "-version")),
+        })
+    })
+    .await
+    .unwrap_or_else(|e| serde_json::json!({ "error": e.to_string() }));
+
+    let status = agent_status().await.unwrap_or_else(|e| serde_json::json!({ "error": e.message() }));
+    let endpoints = grpc_client::GrpcEndpoints::current();
+    let processes: Vec<serde_json::Value> = match app.state::<AgentHandles>().0.lock() {
+        Ok(mut agents) => agents
+            .iter_mut()
+            .map(|a| {
+                let running = a.child.as_mut().is_some_and(|c| matches!(c.try_wait(), Ok(None)));
+                serde_json::json!({
+                    "name": a.name,
+                    "addr": a.addr,
+                    "running": running,
+                    "pid": a.child.as_ref().map(|c| c.id()),
+                    "restarts": a.restarts,
+                })
+            })
+            .collect(),
+        Err(_) => Vec::new(),
+    };
+
+    let db_path = db::db_path();
+    let database = match tokio::task::spawn_blocking(db::library_counts).await {
+        Ok(Ok(counts)) => serde_json::json!({
+            "path": db_path.to_string_lossy(),
+            "bytes": db::size_on_disk(),
+            "files": counts.files,
+            "messages": counts.messages,
+            "artifacts": counts.artifacts,
+        }),
+        Ok(Err(e)) => serde_json::json!({ "path": db_path.to_string_lossy(), "bytes": db::size_on_disk(), "error": e.to_string() }),
+        Err(e) => serde_json::json!({ "path": db_path.to_string_lossy(), "error": e.to_string() }),
+    };
+
+    serde_json::json!({
+        "app": { "name": app.package_info().name, "version": app.package_info().version.to_string() },
+        "platform": { "os": std::env::consts::OS, "arch": std::env::consts::ARCH, "family": std::env::consts::FAMILY },
+        "tools": versions,
+        "agents": {
+            "status": status,
+            "endpoints": {
+                "transcription": endpoints.transcription,
+                "vision": endpoints.vision,
+                "generation": endpoints.generation,
+            },
+            "processes": processes,
+        },
+        "database": database,
+        "settings": serde_json::to_value(settings::get()).unwrap_or_default(),
+        "environment": auralink_env(),
+    })
+}
+
+/// Tail of an agent's log file (default 200 lines), for bug reports after a crash.
+#[tauri::command]
+async fn get_agent_log(name: String, lines: Option<usize>) -> Result<String, AppError> {
+    agents::tail_log(name.trim(), lines.unwrap_or(200).clamp(1, 5000)).map_err(AppError::Validation)
+}
+
+#[tauri::command]
+async fn save_message(file_id: String, text: String, is_user: bool) -> Result<(), AppError> {
+    let id = uuid::Uuid::new_v4().to_string();
+    let now = chrono::Utc::now().to_rfc3339();
+    db::insert_message(&id, &file_id, &text, is_user, &now).map_err(AppError::from)
+}
+
+/// A single message by id, or None when there is no such message.
+#[tauri::command]
+async fn get_message(id: String) -> Result<Option<serde_json::Value>, AppError> {
+    db::get_message(&id).map_err(AppError::from)
+}
+
+#[tauri::command]
+async fn delete_message(id: String) -> Result<(), AppError> {
+    match db::delete_message(&id)? {
+        true => Ok(()),
+        false => Err(AppError::NotFound(format!("Message not found: {}", id))),
+    }
+}
+
+/// Documents generated for a file (newest first), skipping any since removed from disk.
+#[tauri::command]
+async fn list_artifacts(file_id: String) -> Result<Vec<serde_json::Value>, AppError> {
+    let rows = db::list_artifacts(&file_id)?;
+    Ok(rows
+        .into_iter()
+        .filter(|a| std::path::Path::new(&a.path).exists())
+        .map(|a| serde_json::json!({
+          "id": a.id,
+          "fileId": file_id,
+          "kind": a.kind,
+          "path": a.path,
+          "name": std::path::Path::new(&a.path).file_name().map(|n| n.to_string_lossy().to_string()),
+          "createdAt": a.created_at
+        }))
+        .collect())
+}
+
+/// Totals for an overview screen. Sizes are best effort: files missing from disk
+/// count as zero.
+#[tauri::command]
+async fn library_stats() -> Result<serde_json::Value, AppError> {
+    let counts = db::library_counts()?;
+    let files = db::list_files(None)?;
+    let db_path = db::db_path();
+    let base_dir = db_path.parent().unwrap_or(std::path::Path::new(".")).to_path_buf();
+    let (media_bytes, thumb_bytes) = tokio::task::spawn_blocking(move || {
+        let media: u64 = files
+            .iter()
+            .filter_map(|f| std::fs::metadata(&f.path).ok())
+            .map(|m| m.len())
+            .sum();
+        (media, dir_size(&base_dir.join("thumbs")))
+    })
+    .await?;
+    Ok(serde_json::json!({
+      "files": counts.files,
+      "messages": counts.messages,
+      "artifacts": counts.artifacts,
+      "mediaBytes": media_bytes,
+      "thumbnailBytes": thumb_bytes
+    }))
+}
+
+/// Checkpoints the WAL and vacuums the database. Returns the size on disk before
+/// and after, in bytes.
+#[tauri::command]
+async fn compact_database() -> Result<serde_json::Value, AppError> {
+    let (before, after) = tokio::task::spawn_blocking(db::compact).await??;
+    Ok(serde_json::json!({
+      "beforeBytes": before,
+      "afterBytes": after,
+      "reclaimedBytes": before.saturating_sub(after)
+    }))
+}
+
+// Total size of regular files under `dir`; unreadable entries are skipped
+fn dir_size(dir: &std::path::Path) -> u64 {
+    let Ok(entries) = std::fs::read_dir(dir) else { return 0 };
+    entries
+        .flatten()
+        .map(|e| match e.file_type() {
+            Ok(t) if t.is_dir() => dir_size(&e.path()),
+            Ok(t) if t.is_file() => e.metadata().map(|m| m.len()).unwrap_or(0),
+            _ => 0,
+        })
+        .sum()
+}
+
+#[tauri::command]
+async fn clear_messages(file_id: String) -> Result<usize, AppError> {
+    db::clear_messages(&file_id).map_err(AppError::from)
+}
+
+#[tauri::command]
+async fn get_messages(file_id: String, limit: i32, cursor: Option<String>, direction: Option<String>)
+  -> Result<serde_json::Value, AppError> {
+    // Oldest-first unless asked otherwise; nextCursor continues in the same direction
+    let direction = match direction.as_deref().map(|d| d.trim().to_ascii_lowercase()) {
+        None => db::Direction::Asc,
+        Some(d) if d == "asc" => db::Direction::Asc,
+        Some(d) if d == "desc" => db::Direction::Desc,
+        Some(d) => return Err(AppError::Validation(format!("Invalid direction: {} (use \"asc\" or \"desc\")", d))),
+    };
+    let page = db::list_messages(&file_id, limit as i64, cursor.as_deref(), direction)?;
+    Ok(serde_json::json!({
+      "messages": page.messages,
+      "nextCursor": page.next_cursor
+    }))
+}
+
+#[tauri::command]
+async fn search_messages(query: String, file_id: Option<String>, limit: i32)
+  -> Result<serde_json::Value, AppError> {
+    let hits = db::search_messages(&query, file_id.as_deref(), limit as i64)?;
+    let results: Vec<serde_json::Value> = hits
+        .into_iter()
+        .map(|h| serde_json::json!({
+          "id": h.id,
+          "fileId": h.file_id,
+          "text": h.text,
+          "isUserMessage": h.is_user,
+          "createdAt": h.created_at,
+          "snippet": h.snippet
+        }))
+        .collect();
+    Ok(serde_json::json!({ "results": results }))
+}
+
+// Every message for a file, oldest first, paged through list_messages
+fn all_messages(file_id: &str) -> rusqlite::Result<Vec<serde_json::Value>> {
+    let mut messages = Vec::new();
+    let mut cursor: Option<String> = None;
+    loop {
+        let page = db::list_messages(file_id, 500, cursor.as_deref(), db::Direction::Asc)?;
+        messages.extend(page.messages);
+        match page.next_cursor {
+            Some(next) => cursor = Some(next),
+            None => return Ok(messages),
+        }
+    }
+}
+
+/// Writes the whole conversation for a file to exports/ in the app data dir as
+/// "json" (array of messages) or "markdown"/"md", and returns the path.
+#[tauri::command]
+async fn export_conversation(file_id: String, format: String) -> Result<String, AppError> {
+    let format = format.trim().to_ascii_lowercase();
+    let ext = match format.as_str() {
+        "json" => "json",
+        "markdown" | "md" => "md",
+        other => return Err(AppError::Validation(format!("Unknown export format: {} (use \"json\" or \"markdown\")", other))),
+    };
+    let path = db::get_file_path(&file_id)?
+        .ok_or_else(|| AppError::NotFound("File not found".to_string()))?;
+    let messages = all_messages(&file_id)?;
+
+    let contents = if ext == "json" {
+        serde_json::to_string_pretty(&messages).map_err(|e| AppError::Io(e.to_string()))?
+    } else {
+        let title = std::path::Path::new(&path)
+            .file_name()
+            .map(|n| n.to_string_lossy().to_string())
+            .unwrap_or_else(|| file_id.clone());
+        let mut md = format!("# Conversation: {}\n", title);
+        for m in &messages {
+            let who = if m["isUserMessage"].as_bool().unwrap_or(false) { "User" } else { "Assistant" };
+            // Message text is already markdown (links, `paths`, tables); keep it verbatim
+            md.push_str(&format!(
+                "\n## {} — {}\n\n{}\n",
+                who,
+                m["createdAt"].as_str().unwrap_or_default(),
+                m["text"].as_str().unwrap_or_default()
+            ));
+        }
+        md
+    };
+
+    let dir = db::db_path()
+        .parent()
+        .unwrap_or(std::path::Path::new("."))
+        .join("exports");
+    std::fs::create_dir_all(&dir)?;
+    let stamp = chrono::Utc::now().format("%Y%m%d-%H%M%S");
+    let out = dir.join(format!("{}-{}.{}", file_id, stamp, ext));
+    std::fs::write(&out, contents)?;
+    Ok(out.to_string_lossy().to_string())
+}
+
+fn extract_artifact_paths(text: &str) -> Vec<String> {
+    let mut paths: Vec<String> = Vec::new();
+    // Markdown links produced by friendly_sentence: [Open file](file:///abs/path),
+    // with spaces, parentheses and the like percent-encoded by file_link
+    let mut rest = text;
+    while let Some(idx) = rest.find("file://") {
+        let after = &rest[idx + "file://".len()..];
+        let end = after.find(|c: char| c == ')' || c.is_whitespace()).unwrap_or(after.len());
+        let p = percent_decode(after[..end].trim());
+        if !p.is_empty() && !paths.contains(&p) { paths.push(p); }
+        rest = &after[end..];
+    }
+    // Code-formatted paths from code_span: Path: `/abs/path`, or with a longer,
+    // space-padded fence when the path has backticks of its own
+    let mut rest = text;
+    while let Some(idx) = rest.find("Path: `") {
+        let after = &rest[idx + "Path: ".len()..];
+        let Some((p, used)) = parse_code_span(after) else { break };
+        let p = p.to_string();
+        if !p.is_empty() && !paths.contains(&p) { paths.push(p); }
+        rest = &after[used..];
+    }
+    paths
+}
+
+// Reverses file_link's %XX escapes; anything malformed is kept as written
+fn percent_decode(text: &str) -> String {
+    let bytes = text.as_bytes();
+    let mut out = Vec::with_capacity(bytes.len());
+    let mut i = 0;
+    while i < bytes.len() {
+        let hex = bytes.get(i + 1..i + 3).and_then(|h| std::str::from_utf8(h).ok());
+        match (bytes[i], hex.and_then(|h| u8::from_str_radix(h, 16).ok())) {
+            (b'%', Some(b)) => { out.push(b); i += 3; }
+            (b, _) => { out.push(b); i += 1; }
+        }
+    }
+    String::from_utf8_lossy(&out).into_owned()
+}
+
+// Content of the inline code span `text` starts with, and the bytes it took up.
+// The span closes at the next backtick run as long as the opening one; one space
+// of padding on each side is dropped, as markdown does.
+fn parse_code_span(text: &str) -> Option<(&str, usize)> {
+    let fence = text.len() - text.trim_start_matches('`').len();
+    if fence == 0 { return None; }
+    let body = &text[fence..];
+    let mut from = 0;
+    while let Some(start) = body[from..].find('`').map(|i| from + i) {
+        let run = body[start..].len() - body[start..].trim_start_matches('`').len();
+        if run == fence {
+            let inner = &body[..start];
+            let padded = inner.len() >= 2 && inner.starts_with(' ') && inner.ends_with(' ') && !inner.trim().is_empty();
+            let inner = if padded { &inner[1..inner.len() - 1] } else { inner };
+            return Some((inner, fence + start + run));
+        }
+        from = start + run;
+    }
+    None
+}
+
+fn extract_timestamps(text: &str) -> Vec<String> {
+    // Matches m:ss, mm:ss and h:mm:ss tokens
+    let mut out: Vec<String> = Vec::new();
+    for token in text.split_whitespace() {
+        let t = token.trim_matches(|c: char| !c.is_ascii_digit());
+        let groups: Vec<&str> = t.split(':').collect();
+        if groups.len() < 2 || groups.len() > 3 { continue; }
+        let valid = groups.iter().enumerate().all(|(i, g)| {
+            let digits = !g.is_empty() && g.chars().all(|c| c.is_ascii_digit());
+            if i == 0 { digits && g.len() <= 2 } else { digits && g.len() == 2 && g.as_bytes()[0] <= b'5' }
+        });
+        if valid && !out.iter().any(|x| x == t) { out.push(t.to_string()); }
+    }
+    out
+}
+
+fn message_status(text: &str, is_user: bool) -> &'static str {
+    if is_user { return "prompt"; }
+    let lower = text.to_lowercase();
+    if lower.starts_with("i detected multiple possible actions")
+        || lower.starts_with("did you mean:")
+        || lower.starts_with("i'm not sure what you'd like")
+    {
+        return "clarification";
+    }
+    "result"
+}
+
+#[tauri::command]
+async fn get_conversation_view(file_id: String, limit: Option<i32>, cursor: Option<String>)
+  -> Result<serde_json::Value, AppError> {
+    let limit = limit.unwrap_or(200).clamp(1, 1000);
+    let page = db::list_messages(&file_id, limit as i64, cursor.as_deref(), db::Direction::Asc)?;
+    let messages: Vec<serde_json::Value> = page
+        .messages
+        .into_iter()
+        .map(|m| {
+            let text = m["text"].as_str().unwrap_or_default().to_string();
+            let is_user = m["isUserMessage"].as_bool().unwrap_or(false);
+            let role = if is_user { "user" } else { "assistant" };
+            let mut artifacts = Vec::new();
+            let mut frames = Vec::new();
+            for path in extract_artifact_paths(&text) {
+                let ext = std::path::Path::new(&path)
+                    .extension()
+                    .map(|e| e.to_string_lossy().to_lowercase())
+                    .unwrap_or_default();
+                let status = if std::path::Path::new(&path).exists() { "exists" } else { "missing" };
+                match ext.as_str() {
+                    "jpg" | "jpeg" | "png" => frames.push(serde_json::json!({ "path": path, "status": status })),
+                    _ => {
+                        let kind = match ext.as_str() {
+                            "pdf" => "pdf",
+                            "doc" | "docx" => "docx",
+                            "ppt" | "pptx" => "powerpoint",
+                            _ => "file",
+                        };
+                        artifacts.push(serde_json::json!({ "path": path, "kind": kind, "status": status }));
+                    }
+                }
+            }
+            serde_json::json!({
+                "id": m["id"],
+                "text": text,
+                "role": role,
+                "status": message_status(&text, is_user),
+                "createdAt": m["createdAt"],
+                "artifacts": artifacts,
+                "frames": frames,
+                "timestamps": extract_timestamps(&text),
+            })
+        })
+        .collect();
+    Ok(serde_json::json!({
+      "fileId": file_id,
+      "messages": messages,
+      "nextCursor": page.next_cursor
+    }))
+}
+
+/// What a chat turn produced: the reply text plus enough structure for the UI to
+/// render it without parsing the text.
+struct MessageOutcome {
+    kind: &'static str,
+    tasks: Vec<Task>,
+    parts: Vec<serde_json::Value>,
+    text: String,
+    // Requests a clarification offered, for resolving a numeric reply next turn
+    offered: Vec<String>,
+}
+
+impl MessageOutcome {
+    fn new(kind: &'static str, tasks: Vec<Task>, parts: Vec<serde_json::Value>, text: String) -> Self {
+        Self { kind, tasks, parts, text, offered: Vec::new() }
+    }
+
+    fn clarification(text: String, offered: Vec<String>) -> Self {
+        Self { kind: "clarification", tasks: Vec::new(), parts: Vec::new(), text, offered }
+    }
+
+    fn to_json(&self) -> serde_json::Value {
+        serde_json::json!({
+            "kind": self.kind,
+            "intents": self.tasks.iter().map(|t| t.name()).collect::<Vec<_>>(),
+            "parts": self.parts,
+            "text": self.text,
+        })
+    }
+}
+
+async fn handle_message(app: tauri::AppHandle, file_id: String, message: String) -> Result<MessageOutcome, AppError> {
+    // persist user message
+    let id = uuid::Uuid::new_v4().to_string();
+    db::insert_message(&id, &file_id, &message, true, &chrono::Utc::now().to_rfc3339())?;
+    // Check if user is responding to a clarification with a number. Options are
+    // resolved against what was actually offered last time for this file; without
+    // a pending clarification a lone number is just text.
+    let pending = db::take_pending_clarification(&file_id)?;
+    let resolved = match numeric_reply(&message, pending.as_deref()) {
+        Some((_, request)) => {
+            db::set_clarification_answer(&id, &file_id, &request)?;
+            request
+        }
+        None => message.clone(),
+    };
+    let outcome = route_message(app, file_id.clone(), message, resolved).await?;
+    // persist AI reply
+    save_message(file_id.clone(), outcome.text.clone(), false).await?;
+    if !outcome.offered.is_empty() {
+        db::set_pending_clarification(&file_id, &outcome.offered)?;
+    }
+    Ok(outcome)
+}
+
+// Help, cancel and compare are recognized the same way whatever the intent mode
+const META_THRESHOLD: u8 = 7;
+
+// "2" in reply to a clarification, as (choice, request it stands for)
+fn numeric_reply(message: &str, options: Option<&[String]>) -> Option<(usize, String)> {
+    let n = message.trim().parse::<usize>().ok()?;
+    let options = options?;
+    (n >= 1 && n <= options.len()).then(|| (n, options[n - 1].clone()))
+}
+
+/// Where route_message sends a message.
+enum Route {
+    Moment { index: usize, at: f64, label: String },
+    Frame(f64),
+    Cancel,
+    Help,
+    Compare,
+    Chat,
+    Clarify,
+    Tasks(Vec<Task>),
+}
+
+impl Route {
+    fn kind(&self) -> &'static str {
+        match self {
+            Route::Moment { .. } => "moment",
+            Route::Frame(_) => "frame",
+            Route::Cancel => "cancel",
+            Route::Help => "help",
+            Route::Compare => "compare",
+            Route::Chat => "chat",
+            Route::Clarify => "clarification",
+            Route::Tasks(_) => "tasks",
+        }
+    }
+}
+
+/// The routing decision and what it was based on; shared by route_message and
+/// preview_intent so the preview can't drift from what a send does.
+struct Routing {
+    route: Route,
+    intent: IntentScore,
+    thresholds: intent::Thresholds,
+    conjunctive: Option<Vec<Task>>,
+    ambiguous: bool,
+    // Would be offered to the chat agent; without chat it is settled like the rest
+    conversational: bool,
+    used_preferences: bool,
+    moment: Option<usize>,
+    timestamp: Option<f64>,
+}
+
+impl Routing {
+    // Where a message goes when it isn't (or can't be) answered by the chat agent
+    fn settled(&self) -> Route {
+        if self.ambiguous || !self.intent.has_any_intent() {
+            Route::Clarify
+        } else {
+            Route::Tasks(self.conjunctive.clone().unwrap_or_else(|| self.intent.tasks(self.thresholds.execute)))
+        }
+    }
+}
+
+// With a file, its stored key moments and preferred requests count, as they do
+// when the message is sent; without one neither applies.
+fn decide_route(file_id: Option<&str>, message: &str) -> rusqlite::Result<Routing> {
+    let moment = intent::moment_reference(message);
+    let timestamp = intent::timestamp_reference(message);
+    // A moment number only means something once a key-moments list was stored
+    let stored_moment = match (moment, file_id) {
+        (Some(index), Some(id)) => db::get_key_moment(id, index)?.map(|(at, label)| Route::Moment { index, at, label }),
+        _ => None,
+    };
+
+    // Score the intent with confidence levels
+    let thresholds = settings::get().intent_thresholds();
+    let mut intent = IntentScore::from_message(message);
+    // "transcribe the video and then summarize it" is a request for both, not ambiguity
+    let conjunctive = IntentScore::conjunctive_tasks(message, thresholds.execute);
+
+    // Meta requests come before any ambiguity handling
+    let meta = stored_moment
+        .or_else(|| timestamp.map(Route::Frame))
+        .or_else(|| intent.is_cancel(META_THRESHOLD).then_some(Route::Cancel))
+        .or_else(|| intent.is_help(META_THRESHOLD).then_some(Route::Help))
+        .or_else(|| (intent.compare >= META_THRESHOLD).then_some(Route::Compare));
+
+    // The file's preferred requests settle a weak one before asking
+    let mut used_preferences = false;
+    if let (None, None, Some(id)) = (&meta, &conjunctive, file_id) {
+        if intent.is_ambiguous(thresholds.clarify) {
+            if let Some(prefs) = db::get_file_settings(id)? {
+                used_preferences = intent.prefer(&prefs.preferred_intents, thresholds.execute);
+            }
+        }
+    }
+    let ambiguous = conjunctive.is_none() && intent.is_ambiguous(thresholds.clarify);
+    // Questions with no task behind them are for the chat agent, when it has chat
+    let conversational = conjunctive.is_none() && intent.tasks(thresholds.execute).is_empty() && intent::is_conversational(message);
+
+    let mut routing = Routing {
+        route: Route::Chat,
+        intent,
+        thresholds,
+        conjunctive,
+        ambiguous,
+        conversational,
+        used_preferences,
+        moment,
+        timestamp,
+    };
+    routing.route = match meta {
+        Some(route) => route,
+        None if conversational => Route::Chat,
+        None => routing.settled(),
+    };
+    Ok(routing)
+}
+
+// Everything after persisting the user's message: routing and running tasks. The
+// reply is left to the caller to save, so regenerate can swap it in atomically.
+// `resolved_message` is `message` with a numeric clarification reply spelled out.
+async fn route_message(app: tauri::AppHandle, file_id: String, message: String, resolved_message: String) -> Result<MessageOutcome, AppError> {
+    let mut routing = decide_route(Some(&file_id), &resolved_message)?;
+    // Without a chat reply the question is handled like any other request
+    if matches!(routing.route, Route::Chat) {
+        if let Some(text) = chat_reply(&app, &file_id, &resolved_message).await {
+            return Ok(MessageOutcome::new("chat", Vec::new(), Vec::new(), text));
+        }
+        routing.route = routing.settled();
+    }
+
+    let tasks = match routing.route {
+        // "show me moment 3" refers back to the last key-moments list
+        Route::Moment { index, at, label } => {
+            let _ = app.emit("seek_to", serde_json::json!({ "fileId": file_id, "time": at }));
+            let text = format!("Moment {} is at {}: {}", index, media::format_timestamp(at), escape_markdown(&label));
+            let part = serde_json::json!({
+                "type": "moment", "ok": true,
+                "data": { "index": index, "at": at, "timestamp": media::format_timestamp(at), "label": label }
+            });
+            return Ok(MessageOutcome::new("moment", Vec::new(), vec![part], text));
+        }
+        // "what's shown at 2:30?" looks at that one frame
+        Route::Frame(at) => {
+            let (text, part) = match describe_frame_at(&file_id, at).await {
+                Ok((text, data)) => {
+                    let _ = app.emit("seek_to", serde_json::json!({ "fileId": file_id, "time": data["at"] }));
+                    (text, serde_json::json!({ "type": "frame", "ok": true, "data": data }))
+                }
+                Err(e) => (e.clone(), serde_json::json!({ "type": "frame", "ok": false, "data": { "text": e, "at": at } })),
+            };
+            return Ok(MessageOutcome::new("frame", Vec::new(), vec![part], text));
+        }
+        Route::Cancel => {
+            // The caller already consumed the pending clarification; also stop any running analysis
+            let stopped = app.state::<RunningTasks>().cancel(&file_id);
+            let text = if stopped {
+                "Okay, I stopped the analysis that was running.".to_string()
+            } else {
+                "Okay, never mind. What would you like to do instead?".to_string()
+            };
+            return Ok(MessageOutcome::new("cancel", Vec::new(), Vec::new(), text));
+        }
+        Route::Help => {
+            let text = help_text(&settings::get());
+            return Ok(MessageOutcome::new("help", Vec::new(), Vec::new(), text));
+        }
+        Route::Compare => {
+            let aspect = routing.intent.compare_aspect();
+            let (text, parts) = match mentioned_file(&file_id, &resolved_message)? {
+                Some(other) => {
+                    let result = compare(&file_id, &other, aspect).await?;
+                    let text = result["text"].as_str().unwrap_or_default().to_string();
+                    (text, vec![serde_json::json!({ "type": "compare", "ok": true, "data": result })])
+                }
+                None => (
+                    "Which file should I compare this one with? Mention it by name, for example \"compare the objects with lecture-2.mp4\".".to_string(),
+                    Vec::new(),
+                ),
+            };
+            return Ok(MessageOutcome::new("compare", Vec::new(), parts, text));
+        }
+        // Ambiguous or low-confidence: offer the likely options
+        Route::Clarify if routing.ambiguous => {
+            let clarification = routing.intent.get_clarification_message();
+            let offered: Vec<String> = routing.intent.clarification_options().into_iter().map(|(_, request)| request.to_string()).collect();
+            return Ok(MessageOutcome::clarification(clarification, offered));
+        }
+        // No clear intent detected
+        Route::Chat | Route::Clarify => {
+            let clarification = "I'm not sure what you'd like me to do with this video. Could you provide more details? For example:\n\
+                - \"Transcribe the video\"\n\
+                - \"What objects are shown in the video?\"\n\
+                - \"Are there any graphs?\"\n\
+                - \"Create a PowerPoint with key points\"\n\
+                - \"Summarize our discussion and generate a PDF\"".to_string();
+            return Ok(MessageOutcome::clarification(clarification, Vec::new()));
+        }
+        Route::Tasks(tasks) => tasks,
+    };
+
+    let mut runner = TaskRunner::new(&file_id, Some(app));
+    let mut params = serde_json::Map::new();
+    if let Some(language) = intent::target_language(&resolved_message) {
+        params.insert("target_language".to_string(), serde_json::json!(language));
+    }
+    let mut parts: Vec<String> = Vec::new();
+    for task in &tasks {
+        parts.extend(runner.run(*task, &params).await);
+    }
+    // A cancel that lands after the last step finished is ignored; the result stands
+    if runner.cancelled {
+        let text = CANCELLED_TEXT.to_string();
+        return Ok(MessageOutcome::new("cancelled", tasks, Vec::new(), text));
+    }
+
+    let ai_text = format_conversational_response(&file_id, &message, &parts);
+    let all_failed = !runner.structured.is_empty() && runner.structured.iter().all(|p| p["ok"] == false);
+    let kind = if all_failed { "error" } else { "result" };
+    Ok(MessageOutcome::new(kind, tasks, std::mem::take(&mut runner.structured), ai_text))
+}
+
+/// Up to `limit` of a file's latest messages, oldest first, as context for the chat
+/// and summary agents. `answering`, if it's the newest user message, is left out
+/// along with any replies after it (the ones a regenerate is replacing).
+fn conversation_history(file_id: &str, limit: usize, answering: Option<&str>) -> Vec<(String, bool)> {
+    let mut history = match db::recent_messages(file_id, limit as i64 + 1) {
+        Ok(history) => history,
+        Err(e) => {
+            println!("[Tauri] Warning: no conversation history for {}: {}", file_id, e);
+            return Vec::new();
+        }
+    };
+    if let Some(newest_user) = history.iter().position(|(_, is_user)| *is_user) {
+        if Some(history[newest_user].0.as_str()) == answering {
+            history.drain(..=newest_user);
+        }
+    }
+    history.truncate(limit);
+    history.reverse();
+    history
+}
+
+// Earlier turns sent along with a chat message
+const CHAT_HISTORY_MESSAGES: usize = 20;
+
+/// Streams the chat agent's reply to a conversational message as `chat_token` events.
+/// None if the agent doesn't do chat or fails, so routing carries on as before.
+async fn chat_reply(app: &tauri::AppHandle, file_id: &str, message: &str) -> Option<String> {
+    let history = conversation_history(file_id, CHAT_HISTORY_MESSAGES, Some(message));
+    let context = db::get_transcript(file_id).ok().flatten().map(|t| t.text).unwrap_or_default();
+    let (app, fid) = (app.clone(), file_id.to_string());
+    let result = grpc_client::chat_streaming(file_id.to_string(), history, message.to_string(), context, move |token| {
+        let _ = app.emit("chat_token", serde_json::json!({ "fileId": fid, "token": token }));
+    })
+    .await;
+    match result {
+        Ok(Some(text)) if !text.trim().is_empty() => Some(text),
+        Ok(_) => None,
+        Err(e) => {
+            println!("[Tauri] chat reply for {} failed, asking instead: {}", file_id, e);
+            None
+        }
+    }
+}
+
+// Capabilities overview listing only what the enabled agents can do
+fn help_text(settings: &settings::Settings) -> String {
+    let mut lines: Vec<&str> = Vec::new();
+    if settings.enable_transcription {
+        lines.push("- \"Transcribe the video\" – get a transcript of what is said");
+    }
+    if settings.enable_vision {
+        lines.push("- \"What objects are shown?\" – detect objects across sampled frames");
+        lines.push("- \"Are there any graphs?\" – find charts and when they appear");
+        lines.push("- \"What are the key moments?\" – a timestamped list; then \"show me moment 2\"");
+        lines.push("- \"What's shown at 2:30?\" – objects and charts in that frame");
+        lines.push("- \"Compare the objects with other.mp4\" – side by side with another file");
+    }
+    if settings.enable_generation {
+        lines.push("- \"Create a PowerPoint\", \"generate pdf\" or \"create a word document\" – documents from what we found");
+        lines.push("- \"Summarize our conversation into a PDF\"");
+        lines.push("- \"Translate to Spanish\" – translate the transcript");
+    }
+    if lines.is_empty() {
+        return "All features are currently disabled in settings. Turn on enable_transcription, enable_vision or enable_generation to get started.".to_string();
+    }
+    format!(
+        "Here’s what I can do with this video:\n{}\n\nYou can combine requests (\"transcribe the video and detect objects\"), or say \"cancel\" to stop.",
+        lines.join("\n")
+    )
+}
+
+/// How send_message would read `message`, without running anything: per-intent
+/// scores, whether it would ask for clarification, and the tasks that would run.
+/// With `file_id`, the file's state is read the way a send reads it: a numeric
+/// reply is resolved against its pending clarification (read, not consumed), and
+/// its stored key moments and preferred requests are taken into account.
+#[tauri::command]
+fn preview_intent(message: String, file_id: Option<String>) -> Result<serde_json::Value, AppError> {
+    let pending = match &file_id {
+        Some(id) => db::get_pending_clarification(id)?,
+        None => None,
+    };
+    let numeric = numeric_reply(&message, pending.as_deref());
+    let resolved = numeric.as_ref().map(|(_, r)| r.clone()).unwrap_or_else(|| message.clone());
+
+    let routing = decide_route(file_id.as_deref(), &resolved)?;
+    let tasks = match &routing.route {
+        Route::Tasks(tasks) => tasks.clone(),
+        _ => Vec::new(),
+    };
+    let intent = &routing.intent;
+    Ok(serde_json::json!({
+        "message": message,
+        "resolvedMessage": resolved,
+        "numericReply": numeric.map(|(choice, request)| serde_json::json!({ "choice": choice, "request": request })),
+        "scores": {
+            "transcribe": intent.transcribe,
+            "objects": intent.objects,
+            "graphs": intent.graphs,
+            "ppt": intent.ppt,
+            "pdf": intent.pdf,
+            "docx": intent.docx,
+            "summary": intent.summary,
+            "translate": intent.translate,
+            "keyMoments": intent.key_moments,
+            "help": intent.help,
+            "cancel": intent.cancel,
+            "compare": intent.compare,
+        },
+        "threshold": routing.thresholds.execute,
+        "clarifyThreshold": routing.thresholds.clarify,
+        "ambiguous": routing.ambiguous,
+        "conjunctive": routing.conjunctive.is_some(),
+        "conversational": routing.conversational,
+        "usedFilePreferences": routing.used_preferences,
+        "momentReference": routing.moment,
+        "timestampReference": routing.timestamp,
+        "kind": routing.route.kind(),
+        "tasks": tasks
+            .iter()
+            .map(|t| serde_json::json!({ "name": t.name(), "label": t.label(), "agent": t.agent() }))
+            .collect::<Vec<_>>(),
+    }))
+}
+
+const COMPARE_ASPECTS: &[&str] = &["objects", "graphs", "transcript", "metadata"];
+
+// Another library file named in `message` (by title, name or file stem); the
+// longest match wins so "talk-2" isn't taken for "talk"
+fn mentioned_file(file_id: &str, message: &str) -> rusqlite::Result<Option<String>> {
+    let lower = message.to_lowercase();
+    let files = db::list_files(None)?;
+    let mut best: Option<(usize, String)> = None;
+    for f in files.into_iter().filter(|f| f.id != file_id) {
+        let path = std::path::Path::new(&f.path);
+        let stem = path.file_stem().map(|s| s.to_string_lossy().to_string());
+        let file_name = path.file_name().map(|s| s.to_string_lossy().to_string());
+        for candidate in [f.title.clone(), f.name.clone(), file_name, stem].into_iter().flatten() {
+            let candidate = candidate.trim().to_lowercase();
+            if candidate.chars().count() < 3 || !lower.contains(&candidate) { continue; }
+            if !matches!(&best, Some((len, _)) if candidate.len() <= *len) {
+                best = Some((candidate.len(), f.id.clone()));
+            }
+        }
+    }
+    Ok(best.map(|(_, id)| id))
+}
+
+// One file's side of a comparison, shaped like the matching send_message part
+async fn compare_side(file_id: &str, aspect: &str) -> serde_json::Value {
+    let task = match aspect {
+        "objects" => Task::Objects,
+        "graphs" => Task::Graphs,
+        "transcript" => Task::Transcribe,
+        _ => {
+            return match get_video_metadata(file_id.to_string()).await {
+                Ok(data) => serde_json::json!({ "type": "metadata", "ok": true, "data": data }),
+                Err(e) => serde_json::json!({ "type": "metadata", "ok": false, "data": { "text": e.message() } }),
+            };
+        }
+    };
+    let mut runner = TaskRunner::new(file_id, None);
+    runner.run(task, &serde_json::Map::new()).await;
+    runner.structured.pop().unwrap_or_else(|| {
+        serde_json::json!({ "type": task.name(), "ok": false, "data": { "text": "No result" } })
+    })
+}
+
+// How often each `field` value occurs in the `list` array of a step's data
+fn value_counts(data: &serde_json::Value, list: &str, field: &str) -> std::collections::BTreeMap<String, u64> {
+    let mut counts = std::collections::BTreeMap::new();
+    for item in data[list].as_array().into_iter().flatten() {
+        if let Some(value) = item[field].as_str() {
+            *counts.entry(value.to_lowercase()).or_insert(0) += 1;
+        }
+    }
+    counts
+}
+
+// Rows of (label, value for a, value for b) for the two-column view
+fn compare_rows(aspect: &str, a: &serde_json::Value, b: &serde_json::Value) -> Vec<(String, serde_json::Value, serde_json::Value)> {
+    let counted = |list: &str, field: &str, total: &str| {
+        let (ca, cb) = (value_counts(a, list, field), value_counts(b, list, field));
+        let mut labels: Vec<&String> = ca.keys().chain(cb.keys()).collect();
+        labels.sort();
+        labels.dedup();
+        let get = |c: &std::collections::BTreeMap<String, u64>, l: &str| c.get(l).copied().unwrap_or(0);
+        // Most frequent overall first
+        labels.sort_by_key(|l| std::cmp::Reverse(get(&ca, l) + get(&cb, l)));
+        let mut rows = vec![(
+            total.to_string(),
+            serde_json::json!(ca.values().sum::<u64>()),
+            serde_json::json!(cb.values().sum::<u64>()),
+        )];
+        rows.extend(labels.into_iter().map(|l| (l.clone(), serde_json::json!(get(&ca, l)), serde_json::json!(get(&cb, l)))));
+        rows
+    };
+    match aspect {
+        "objects" => counted("objects", "label", "objects detected"),
+        "graphs" => counted("graphs", "type", "graphs seen"),
+        "transcript" => {
+            let words = |d: &serde_json::Value| serde_json::json!(d["text"].as_str().map(|t| t.split_whitespace().count()));
+            vec![
+                ("words".to_string(), words(a), words(b)),
+                ("language".to_string(), a["languageName"].clone(), b["languageName"].clone()),
+            ]
+        }
+        _ => ["duration", "width", "height", "videoCodec", "audioCodec", "frameRate", "bitrate"]
+            .iter()
+            .map(|key| (key.to_string(), a[*key].clone(), b[*key].clone()))
+            .collect(),
+    }
+}
+
+fn compare_cell(value: &serde_json::Value) -> String {
+    match value {
+        serde_json::Value::Null => "—".to_string(),
+        serde_json::Value::String(s) => escape_markdown(s),
+        serde_json::Value::Number(n) => match n.as_f64() {
+            Some(f) if f.fract() != 0.0 => format!("{:.2}", f),
+            _ => n.to_string(),
+        },
+        other => other.to_string(),
+    }
+}
+
+// Runs the same analysis on both files (transcripts and metadata come from cache
+// when present) and lines the results up side by side
+async fn compare(a: &str, b: &str, aspect: &str) -> Result<serde_json::Value, AppError> {
+    let files = db::list_files(None)?;
+    let label = |id: &str| {
+        files.iter().find(|f| f.id == id).map(|f| {
+            f.title.clone().or_else(|| f.name.clone()).unwrap_or_else(|| {
+                std::path::Path::new(&f.path).file_name().unwrap_or_default().to_string_lossy().to_string()
+            })
+        })
+    };
+    let (Some(name_a), Some(name_b)) = (label(a), label(b)) else {
+        return Err(AppError::NotFound("File not found".to_string()));
+    };
+    let (side_a, side_b) = tokio::join!(compare_side(a, aspect), compare_side(b, aspect));
+    let rows = compare_rows(aspect, &side_a["data"], &side_b["data"]);
+
+    let mut text = format!("Comparing {} of **{}** and **{}**:\n\n| | {} | {} |\n|---|---|---|\n",
+        aspect, escape_markdown(&name_a), escape_markdown(&name_b), escape_markdown(&name_a), escape_markdown(&name_b));
+    for (row, va, vb) in &rows {
+        text.push_str(&format!("| {} | {} | {} |\n", escape_markdown(row), compare_cell(va), compare_cell(vb)));
+    }
+    for (name, side) in [(&name_a, &side_a), (&name_b, &side_b)] {
+        if side["ok"] == false {
+            let reason = side["data"]["text"].as_str().unwrap_or("analysis failed");
+            text.push_str(&format!("\nCouldn't analyze {}: {}", escape_markdown(name), reason));
+        }
+    }
+    let side = |id: &str, name: &str, part: serde_json::Value| serde_json::json!({
+        "fileId": id, "name": name, "ok": part["ok"], "data": part["data"],
+    });
+    Ok(serde_json::json!({
+        "aspect": aspect,
+        "a": side(a, &name_a, side_a),
+        "b": side(b, &name_b, side_b),
+        "rows": rows
+            .iter()
+            .map(|(row, va, vb)| serde_json::json!({ "label": row, "a": va, "b": vb }))
+            .collect::<Vec<_>>(),
+        "text": text.trim_end(),
+    }))
+}
+
+/// Side-by-side comparison of two files. `aspect` is objects, graphs, transcript
+/// or metadata (the default). Returns both results plus `rows` of
+/// `{ label, a, b }` for a two-column view.
+#[tauri::command]
+async fn compare_files(a: String, b: String, aspect: Option<String>) -> Result<serde_json::Value, AppError> {
+    let aspect = aspect.unwrap_or_else(|| "metadata".to_string()).trim().to_lowercase();
+    if !COMPARE_ASPECTS.contains(&aspect.as_str()) {
+        return Err(AppError::Validation(format!("Unknown aspect '{}'; expected one of {}", aspect, COMPARE_ASPECTS.join(", "))));
+    }
+    if a == b {
+        return Err(AppError::Validation("Pick two different files to compare".to_string()));
+    }
+    compare(&a, &b, &aspect).await
+}
+
+const CANCELLED_TEXT: &str = "Cancelled by user.";
+
+/// Cancels the running send_message/run_pipeline for a file. Returns false when
+/// nothing was running (e.g. it already finished).
+#[tauri::command]
+fn cancel_task(state: tauri::State<'_, RunningTasks>, file_id: String) -> bool {
+    state.cancel(&file_id)
+}
+
+#[tauri::command]
+async fn send_message(app: tauri::AppHandle, file_id: String, message: String) -> Result<String, AppError> {
+    handle_message(app, file_id, message).await.map(|outcome| outcome.text)
+}
+
+/// Replaces the reply to the file's latest user message: the assistant messages after
+/// it are deleted and the message is routed again. The user message itself is kept.
+#[tauri::command]
+async fn regenerate(app: tauri::AppHandle, file_id: String) -> Result<String, AppError> {
+    let (id, text, created_at) = db::last_user_message(&file_id)?
+        .ok_or_else(|| AppError::NotFound("There's no message to regenerate a reply for yet".to_string()))?;
+    // A numeric reply to a clarification picks the same request it did the first time
+    let resolved = db::clarification_answer(&id)?.unwrap_or_else(|| text.clone());
+    // The old replies stay until the new one is ready
+    let outcome = route_message(app, file_id.clone(), text, resolved).await?;
+    let reply_id = uuid::Uuid::new_v4().to_string();
+    let now = chrono::Utc::now().to_rfc3339();
+    db::replace_replies_after(&file_id, &created_at, &id, &reply_id, &outcome.text, &now)?;
+    // Only the new reply's clarification, if it asks one, is left to answer
+    if outcome.offered.is_empty() {
+        db::take_pending_clarification(&file_id)?;
+    } else {
+        db::set_pending_clarification(&file_id, &outcome.offered)?;
+    }
+    Ok(outcome.text)
+}
+
+/// Like send_message, but returns `{ kind, intents, parts, text }` so the UI can tell
+/// clarifications from results and render detections as data.
+#[tauri::command]
+async fn send_message_structured(app: tauri::AppHandle, file_id: String, message: String) -> Result<serde_json::Value, AppError> {
+    match handle_message(app, file_id, message).await {
+        Ok(outcome) => Ok(outcome.to_json()),
+        Err(e) => Ok(serde_json::json!({ "kind": "error", "intents": [], "parts": [], "text": e.to_string() })),
+    }
+}
+
+// Steps of the named pipeline; also how set_settings checks analyze_on_drop
+fn pipeline_steps(settings: &settings::Settings, name: &str) -> Result<Vec<settings::PipelineStep>, AppError> {
+    settings
+        .pipelines
+        .get(name)
+        .cloned()
+        .ok_or_else(|| AppError::Validation(format!("Unknown pipeline: {}", name)))
+}
+
+#[tauri::command]
+async fn run_pipeline(app: tauri::AppHandle, file_id: String, pipeline_name: String) -> Result<String, AppError> {
+    let steps = pipeline_steps(&settings::get(), &pipeline_name)?;
+    let mut runner = TaskRunner::new(&file_id, Some(app));
+    let mut parts: Vec<String> = Vec::new();
+    for step in &steps {
+        match Task::from_name(&step.intent) {
+            Some(task) => parts.extend(runner.run(task, &step.params).await),
+            None => parts.push(format!("Skipped unknown step: {}", step.intent)),
+        }
+    }
+    if runner.cancelled {
+        save_message(file_id, CANCELLED_TEXT.to_string(), false).await?;
+        return Ok(CANCELLED_TEXT.to_string());
+    }
+    let ai_text = format_conversational_response(&file_id, &pipeline_name, &parts);
+    save_message(file_id, ai_text.clone(), false).await?;
+    Ok(ai_text)
+}
+
+#[tauri::command]
+fn reload_intent_keywords() -> Result<usize, AppError> {
+    intent::reload_keywords().map_err(AppError::Validation)
+}
+
+#[tauri::command]
+fn get_settings() -> Result<settings::Settings, AppError> {
+    Ok(settings::get())
+}
+
+// Push new settings to the subsystems that cache configuration and notify the UI.
+// Every settings change goes through here, so agents switched on or off, or given
+// another Whisper model, are restarted to match; returns the agents that were.
+async fn apply_settings(
+    app: &tauri::AppHandle,
+    previous: &settings::Settings,
+    settings: &settings::Settings,
+) -> Result<Vec<&'static str>, AppError> {
+    let affected = agents::affected_agents(previous, settings);
+    let mut restarted = Vec::new();
+    if !affected.is_empty() {
+        let handles = app.state::<AgentHandles>().0.clone();
+        let target = settings.clone();
+        restarted = tauri::async_runtime::spawn_blocking(move || {
+            let mut agents = handles.lock().map_err(|e| AppError::Io(e.to_string()))?;
+            agents::apply_settings(&mut agents, &affected, &target, Duration::from_secs(2)).map_err(AppError::Agent)
+        })
+        .await??;
+    }
+    grpc_client::GrpcClients::reset().await;
+    let _ = app.emit("settings-changed", settings);
+    Ok(restarted)
+}
+
+// Overrides are checked together with the mode's values they combine with: clarifying
+// above the execute cut-off would never act, and a 0 would never ask
+fn check_thresholds(settings: &settings::Settings) -> Result<(), AppError> {
+    if [settings.auto_execute_threshold, settings.clarify_threshold].iter().flatten().any(|t| !(1..=10).contains(t)) {
+        return Err(AppError::Validation("auto_execute_threshold and clarify_threshold must be between 1 and 10".to_string()));
+    }
+    let thresholds = settings.intent_thresholds();
+    if thresholds.clarify > thresholds.execute {
+        return Err(AppError::Validation(format!(
+            "clarify_threshold ({}) can't be above auto_execute_threshold ({})",
+            thresholds.clarify, thresholds.execute
+        )));
+    }
+    Ok(())
+}
+
+#[tauri::command]
+async fn set_settings(app: tauri::AppHandle, settings: settings::Settings) -> Result<settings::Settings, AppError> {
+    if let Some(name) = &settings.analyze_on_drop {
+        pipeline_steps(&settings, name)?;
+    }
+    if settings.response_max_chars == 0 || settings.response_min_item_chars == 0 {
+        return Err(AppError::Validation("response_max_chars and response_min_item_chars must be greater than 0".to_string()));
+    }
+    check_thresholds(&settings)?;
+    // Moving the library goes through set_storage_dir
+    let previous = settings::get();
+    let saved = settings::save(settings::Settings { storage_dir: previous.storage_dir.clone(), ..settings }).map_err(AppError::Io)?;
+    apply_settings(&app, &previous, &saved).await?;
+    Ok(saved)
+}
+
+#[tauri::command]
+fn get_transcription_config() -> serde_json::Value {
+    serde_json::json!({
+      "model": settings::get().whisper_model(),
+      "models": agents::WHISPER_MODELS,
+    })
+}
+
+/// Switches the Whisper model. This restarts the transcription agent, which then
+/// reloads (and may first download) the model before it can transcribe again.
+#[tauri::command]
+async fn set_transcription_config(app: tauri::AppHandle, model: String) -> Result<serde_json::Value, AppError> {
+    let model = model.trim().to_lowercase();
+    if !agents::WHISPER_MODELS.contains(&model.as_str()) {
+        return Err(AppError::Validation(format!("Unknown Whisper model: {} (expected one of: {})", model, agents::WHISPER_MODELS.join(", "))));
+    }
+    let previous = settings::get();
+    let saved = settings::save(settings::Settings { whisper_model: Some(model), ..previous.clone() }).map_err(AppError::Io)?;
+    let restarted = apply_settings(&app, &previous, &saved).await?;
+    Ok(serde_json::json!({ "model": saved.whisper_model(), "restarted": restarted.contains(&"transcription") }))
+}
+
+#[tauri::command]
+async fn reset_settings(app: tauri::AppHandle) -> Result<settings::Settings, AppError> {
+    let previous = settings::get();
+    let defaults = settings::reset().map_err(AppError::Io)?;
+    apply_settings(&app, &previous, &defaults).await?;
+    Ok(defaults)
+}
+
+#[tauri::command]
+fn get_temp_path() -> Result<String, AppError> {
+    Ok(std::env::temp_dir()
+        .to_string_lossy()
+        .to_string())
+}
+
+// Background transcription through the usual audio-extraction path; the result
+// lands in the transcript cache for the first request that needs it
+fn spawn_eager_transcription(app: tauri::AppHandle, file_id: String) {
+    if !settings::get().agent_enabled("transcription") {
+        return;
+    }
+    tauri::async_runtime::spawn(async move {
+        let mut runner = TaskRunner::background(&file_id, Some(app));
+        if let Err(e) = runner.transcribe().await {
+            println!("[Tauri] eager transcription of {} failed: {}", file_id, e);
+        }
+    });
+}
+
+fn file_settings_json(file_id: &str, settings: &db::FileSettingsRow) -> serde_json::Value {
+    serde_json::json!({
+      "fileId": file_id,
+      "autoTranscribe": settings.auto_transcribe,
+      "preferredIntents": settings.preferred_intents,
+    })
+}
+
+/// Per-file defaults; unset files get `autoTranscribe: false` and no preferred intents.
+#[tauri::command]
+async fn get_file_settings(file_id: String) -> Result<serde_json::Value, AppError> {
+    if db::get_file_path(&file_id)?.is_none() {
+        return Err(AppError::NotFound("File not found".to_string()));
+    }
+    let settings = db::get_file_settings(&file_id)?.unwrap_or_default();
+    Ok(file_settings_json(&file_id, &settings))
+}
+
+/// Updates the given per-file defaults, leaving the others as they were.
+/// `preferred_intents` takes task names (transcribe, objects, graphs, …) and is
+/// used to settle ambiguous chat requests. Turning `auto_transcribe` on starts
+/// transcription if there is no transcript yet; changed files are re-transcribed
+/// on upload.
+#[tauri::command]
+async fn set_file_settings(
+    app: tauri::AppHandle,
+    file_id: String,
+    auto_transcribe: Option<bool>,
+    preferred_intents: Option<Vec<String>>,
+) -> Result<serde_json::Value, AppError> {
+    if db::get_file_path(&file_id)?.is_none() {
+        return Err(AppError::NotFound("File not found".to_string()));
+    }
+    let mut settings = db::get_file_settings(&file_id)?.unwrap_or_default();
+    if let Some(intents) = preferred_intents {
+        let mut names: Vec<String> = Vec::new();
+        for name in &intents {
+            let task = Task::from_name(name.trim())
+                .ok_or_else(|| AppError::Validation(format!("Unknown intent: {}", name)))?;
+            if !names.iter().any(|n| n == task.name()) {
+                names.push(task.name().to_string());
+            }
+        }
+        settings.preferred_intents = names;
+    }
+    let turned_on = auto_transcribe == Some(true) && !settings.auto_transcribe;
+    if let Some(auto) = auto_transcribe {
+        settings.auto_transcribe = auto;
+    }
+    db::set_file_settings(&file_id, &settings)?;
+    if turned_on && db::get_transcript(&file_id)?.is_none() {
+        spawn_eager_transcription(app, file_id.clone());
+    }
+    Ok(file_settings_json(&file_id, &settings))
+}
+
+#[tauri::command]
+async fn auth_callback() -> Result<serde_json::Value, AppError> {
+    // Placeholder: perform any local setup if needed (e.g., creating a user row)
+    Ok(serde_json::json!({ "success": true }))
+}
+
+#[tauri::command]
+async fn get_app_data_dir() -> Result<String, AppError> {
+    Ok(db::db_path()
+        .parent()
+        .unwrap_or(std::path::Path::new("."))
+        .to_string_lossy()
+        .to_string())
+}
+
+fn app_data_dir() -> std::path::PathBuf {
+    db::db_path().parent().unwrap_or(std::path::Path::new(".")).to_path_buf()
+}
+
+// Room a storage location needs beyond whatever is copied into it
+const MIN_FREE_STORAGE_BYTES: u64 = 512 * 1024 * 1024;
+
+/// Free bytes on the volume holding `dir`, if df reports it.
+#[cfg(not(target_os = "windows"))]
+fn free_space(dir: &std::path::Path) -> Option<u64> {
+    let output = std::process::Command::new("df").arg("-Pk").arg(dir).output().ok()?;
+    // Header, then "filesystem blocks used available capacity mount" in 1K blocks
+    let text = String::from_utf8_lossy(&output.stdout);
+    let available: u64 = text.lines().nth(1)?.split_whitespace().nth(3)?.parse().ok()?;
+    Some(available * 1024)
+}
+
+/// Free bytes on the volume holding `dir`, if PowerShell reports it.
+#[cfg(target_os = "windows")]
+fn free_space(dir: &std::path::Path) -> Option<u64> {
+    let script = format!("(Get-Item -LiteralPath '{}').PSDrive.Free", dir.to_string_lossy().replace('\'', "''"));
+    let output = std::process::Command::new("powershell").args(["-NoProfile", "-Command", &script]).output().ok()?;
+    String::from_utf8_lossy(&output.stdout).trim().parse().ok()
+}
+
+/// Where the library is stored, and whether AURALINK_DATA_DIR decides that.
+#[tauri::command]
+fn get_storage_dir() -> serde_json::Value {
+    serde_json::json!({
+      "path": db::data_dir().to_string_lossy(),
+      "defaultPath": db::default_data_dir().to_string_lossy(),
+      "fromEnv": db::data_dir_from_env().is_some(),
+    })
+}
+
+/// Keeps the library under `path` from now on (None for the default location) and
+/// returns the resolved directory. With `migrate` (the default) the database, media,
+/// thumbnails and exports are moved there; without it only the location changes,
+/// e.g. to reattach a library on an external drive.
+#[tauri::command]
+async fn set_storage_dir(app: tauri::AppHandle, path: Option<String>, migrate: Option<bool>) -> Result<String, AppError> {
+    if db::data_dir_from_env().is_some() {
+        return Err(AppError::Validation("The storage location is set by AURALINK_DATA_DIR; unset it to choose one here".to_string()));
+    }
+    let target = match &path {
+        Some(p) => std::path::PathBuf::from(p.trim()),
+        None => db::default_data_dir(),
+    };
+    if !target.is_absolute() {
+        return Err(AppError::Validation("Storage directory must be an absolute path".to_string()));
+    }
+    std::fs::create_dir_all(&target)?;
+    let target = target.canonicalize()?;
+    let current = db::data_dir();
+    let current = current.canonicalize().unwrap_or(current);
+    if current == target {
+        return Ok(target.to_string_lossy().to_string());
+    }
+
+    let migrate = migrate.unwrap_or(true);
+    if migrate {
+        if target.starts_with(&current) {
+            return Err(AppError::Validation("Storage directory must not be inside the current one".to_string()));
+        }
+        if target.join("auralink.db").exists() {
+            return Err(AppError::Validation(format!(
+                "{} already holds a library; switch to it without migrating, or pick an empty folder",
+                target.display()
+            )));
+        }
+        if !app.state::<PendingUploads>().0.is_idle() || !app.state::<RunningTasks>().is_idle() {
+            return Err(AppError::Validation("Wait for uploads and analyses to finish before moving the library".to_string()));
+        }
+    }
+    let probe = target.join(format!(".write-test-{}", uuid::Uuid::new_v4()));
+    std::fs::write(&probe, b"ok")
+        .map_err(|e| AppError::Validation(format!("Storage directory must be writable: {}", e)))?;
+    let _ = std::fs::remove_file(&probe);
+    let needed = MIN_FREE_STORAGE_BYTES + if migrate { dir_size(&current) } else { 0 };
+    if let Some(free) = free_space(&target).filter(|free| *free < needed) {
+        return Err(AppError::Validation(format!(
+            "Not enough free space in {}: about {} MB needed, {} MB available",
+            target.display(),
+            needed / (1024 * 1024),
+            free / (1024 * 1024)
+        )));
+    }
+
+    let storage_dir = path.map(|_| target.to_string_lossy().to_string());
+    let switch = move || {
+        settings::save(settings::Settings { storage_dir, ..settings::get() }).map(|_| ())
+    };
+    if migrate {
+        let to = target.clone();
+        let originals = tokio::task::spawn_blocking(move || db::copy_library(&to, switch))
+            .await?
+            .map_err(AppError::Io)?;
+        // Everything now opens the copies
+        for original in originals.iter().chain([current.join("auralink.db-wal"), current.join("auralink.db-shm")].iter()) {
+            let _ = if original.is_dir() { std::fs::remove_dir_all(original) } else { std::fs::remove_file(original) };
+        }
+    } else {
+        switch().map_err(AppError::Io)?;
+    }
+    Ok(target.to_string_lossy().to_string())
+}
+
+/// Whether a path lies in the app data dir or the configured output dir, i.e.
+/// somewhere we write to. Paths that don't exist are never ours.
+fn app_owned(path: &std::path::Path) -> bool {
+    let Ok(target) = path.canonicalize() else { return false };
+    let mut roots = vec![app_data_dir()];
+    if let Some(dir) = settings::get().output_dir {
+        roots.push(std::path::PathBuf::from(dir));
+    }
+    roots
+        .iter()
+        .filter_map(|r| r.canonicalize().ok())
+        .any(|r| target.starts_with(&r))
+}
+
+/// Shows a file in Finder/Explorer (or opens its folder on Linux). A fallback for
+/// file:// links, which not every WebView will follow.
+#[tauri::command]
+async fn reveal_in_folder(path: String) -> Result<(), AppError> {
+    let target = std::path::Path::new(&path)
+        .canonicalize()
+        .map_err(|_| AppError::NotFound(format!("File not found: {}", path)))?;
+    // Only reveal things we produced
+    if !app_owned(&target) {
+        return Err(AppError::Validation("Refusing to reveal a path outside the app data directory".to_string()));
+    }
+
+    #[cfg(target_os = "macos")]
+    let status = std::process::Command::new("open").arg("-R").arg(&target).status();
+    // explorer wants "/select,<path>" as one argument
+    #[cfg(target_os = "windows")]
+    let status = std::process::Command::new("explorer")
+        .arg(format!("/select,{}", target.to_string_lossy()))
+        .status();
+    #[cfg(not(any(target_os = "macos", target_os = "windows")))]
+    let status = std::process::Command::new("xdg-open")
+        .arg(target.parent().unwrap_or(&target))
+        .status();
+
+    match status {
+        // explorer exits 1 even when it opened the window
+        Ok(s) if s.success() || cfg!(target_os = "windows") => Ok(()),
+        Ok(s) => Err(AppError::Io(format!("File manager exited with {}", s))),
+        Err(e) => Err(AppError::Io(format!("Failed to open file manager: {}", e))),
+    }
+}
+
+#[tauri::command]
+async fn register_file(file_id: String, path: String) -> Result<(), AppError> {
+    let now = chrono::Utc::now().to_rfc3339();
+    let name = std::path::Path::new(&path)
+        .file_name()
+        .unwrap_or_default()
+        .to_string_lossy()
+        .to_string();
+    db::insert_file(&file_id, &name, &path, &now).map_err(AppError::from)
+}
+
+#[tauri::command]
+async fn get_file_path(file_id: String) -> Result<Option<String>, AppError> {
+    db::get_file_path(&file_id).map_err(AppError::from)
+}
+
+#[allow(non_snake_case)]
+#[derive(serde::Serialize)]
+struct FileItem { id: String, name: String, title: String, path: String, thumbPath: Option<String>, createdAt: String, updatedAt: String, duration: Option<f64>, tags: Vec<String> }
+
+/// Library files, newest first. With `tag`, only files carrying that tag. `sort`
+/// is "created" (default) or "updated" for most recently active first.
+#[tauri::command]
+async fn list_files(tag: Option<String>, sort: Option<String>) -> Result<Vec<FileItem>, AppError> {
+    let tag = tag.map(|t| normalize_tag(&t)).transpose()?;
+    let sort = match sort.as_deref().map(str::trim) {
+        None | Some("") | Some("created") => db::FileSort::Created,
+        Some("updated") => db::FileSort::Updated,
+        Some(other) => return Err(AppError::Validation(format!("Unknown sort '{}'; expected created or updated", other))),
+    };
+    let rows = db::list_files_sorted(tag.as_deref(), sort)?;
+    let items = rows
+        .into_iter()
+        .map(|r| {
+            let name = r.name.unwrap_or_else(|| std::path::Path::new(&r.path)
+                .file_name()
+                .unwrap_or_default()
+                .to_string_lossy()
+                .to_string());
+            FileItem {
+                id: r.id.clone(),
+                title: r.title.unwrap_or_else(|| name.clone()),
+                name,
+                path: r.path,
+                thumbPath: r.thumb_path,
+                createdAt: r.created_at,
+                updatedAt: r.updated_at,
+                duration: r.duration,
+                tags: r.tags,
+            }
+        })
+        .collect();
+    Ok(items)
+}
+
+const MAX_TAG_CHARS: usize = 40;
+
+// Tags compare trimmed and lowercased, so "Lecture " and "lecture" are one tag
+fn normalize_tag(tag: &str) -> Result<String, AppError> {
+    let tag = tag.split_whitespace().collect::<Vec<_>>().join(" ").to_lowercase();
+    if tag.is_empty() {
+        return Err(AppError::Validation("Tag can't be empty".to_string()));
+    }
+    if tag.chars().count() > MAX_TAG_CHARS {
+        return Err(AppError::Validation(format!("Tags are limited to {} characters", MAX_TAG_CHARS)));
+    }
+    Ok(tag)
+}
+
+/// Tags a file. Returns the normalized tag.
+#[tauri::command]
+async fn add_tag(file_id: String, tag: String) -> Result<String, AppError> {
+    let tag = normalize_tag(&tag)?;
+    if db::get_file_path(&file_id)?.is_none() {
+        return Err(AppError::NotFound(format!("File not found: {}", file_id)));
+    }
+    db::add_tag(&file_id, &tag)?;
+    Ok(tag)
+}
+
+/// Untags a file. Returns false when the file didn't have the tag.
+#[tauri::command]
+async fn remove_tag(file_id: String, tag: String) -> Result<bool, AppError> {
+    let tag = normalize_tag(&tag)?;
+    db::remove_tag(&file_id, &tag).map_err(AppError::from)
+}
+
+/// Every tag in use as `{ tag, count }`, most used first.
+#[tauri::command]
+async fn list_tags() -> Result<Vec<serde_json::Value>, AppError> {
+    Ok(db::list_tags()?
+        .into_iter()
+        .map(|(tag, count)| serde_json::json!({ "tag": tag, "count": count }))
+        .collect())
+}
+
+const CONVERSATION_SNIPPET_CHARS: usize = 120;
+
+/// Files with chat history, most recently active first, for the chat list.
+#[tauri::command]
+async fn list_conversations() -> Result<Vec<serde_json::Value>, AppError> {
+    let rows = db::list_files_with_messages()?;
+    Ok(rows
+        .into_iter()
+        .map(|c| {
+            let name = c.file.name.unwrap_or_else(|| std::path::Path::new(&c.file.path)
+                .file_name()
+                .unwrap_or_default()
+                .to_string_lossy()
+                .to_string());
+            serde_json::json!({
+              "fileId": c.file.id,
+              "title": c.file.title.unwrap_or_else(|| name.clone()),
+              "name": name,
+              "path": c.file.path,
+              "thumbPath": c.file.thumb_path,
+              "duration": c.file.duration,
+              "createdAt": c.file.created_at,
+              "updatedAt": c.file.updated_at,
+              "tags": c.file.tags,
+              "messageCount": c.message_count,
+              "lastMessageAt": c.last_message_at,
+              "lastMessage": clamp_len(c.last_message, CONVERSATION_SNIPPET_CHARS),
+              "lastIsUser": c.last_is_user
+            })
+        })
+        .collect())
+}
+
+/// The cached transcript for a file, or None if it hasn't been transcribed yet.
+#[tauri::command]
+async fn get_transcript(file_id: String) -> Result<Option<String>, AppError> {
+    if db::get_file_path(&file_id)?.is_none() {
+        return Err(AppError::NotFound("File not found".to_string()));
+    }
+    Ok(db::get_transcript(&file_id)?.map(|t| t.text))
+}
+
+/// Timed segments of the cached transcript as `{ start, end, timestamp, text }`,
+/// or None if the file hasn't been transcribed yet. Empty when the agent reported
+/// no timings.
+#[tauri::command]
+async fn get_transcript_segments(file_id: String) -> Result<Option<Vec<serde_json::Value>>, AppError> {
+    if db::get_file_path(&file_id)?.is_none() {
+        return Err(AppError::NotFound("File not found".to_string()));
+    }
+    if db::get_transcript(&file_id)?.is_none() {
+        return Ok(None);
+    }
+    let segments = db::get_transcript_segments(&file_id)?;
+    Ok(Some(
+        segments
+            .into_iter()
+            .map(|s| serde_json::json!({
+                "start": s.start,
+                "end": s.end,
+                "timestamp": media::format_timestamp(s.start),
+                "text": s.text,
+            }))
+            .collect(),
+    ))
+}
+
+const MAX_TITLE_WORDS: usize = 8;
+// Enough of the conversation to say what it's about
+const TITLE_MESSAGE_LIMIT: i32 = 20;
+
+// First sentence of `text`, cut to a few words, as a list label
+fn short_title(text: &str) -> Option<String> {
+    let sentence = text.trim().split(['.', '!', '?', '\n']).next()?.trim();
+    let words: Vec<&str> = sentence.split_whitespace().collect();
+    if words.is_empty() { return None; }
+    let mut title = words[..words.len().min(MAX_TITLE_WORDS)].join(" ");
+    if words.len() > MAX_TITLE_WORDS { title.push('…'); }
+    Some(title)
+}
+
+/// Generates a short title for a file from a summary of its conversation, or from
+/// the cached transcript when there is nothing to summarize, and stores it.
+#[tauri::command]
+async fn generate_title(file_id: String) -> Result<String, AppError> {
+    if db::get_file_path(&file_id)?.is_none() {
+        return Err(AppError::NotFound("File not found".to_string()));
+    }
+    let history = conversation_history(&file_id, TITLE_MESSAGE_LIMIT as usize, None);
+    let summary = try_with_retry(|| grpc_client::generation_generate_summary(file_id.clone(), TITLE_MESSAGE_LIMIT, history.clone())).await;
+    let from_summary = summary.as_deref().ok().and_then(short_title);
+    let title = match from_summary {
+        Some(title) => title,
+        None => {
+            let transcript = db::get_transcript(&file_id)?.and_then(|t| short_title(&t.text));
+            match (transcript, summary) {
+                (Some(title), _) => title,
+                (None, Err(e)) => return Err(AppError::Agent(e)),
+                (None, Ok(_)) => return Err(AppError::Validation(
+                    "Nothing to title yet; transcribe the file or chat about it first.".to_string(),
+                )),
+            }
+        }
+    };
+    db::set_file_title(&file_id, &title)?;
+    Ok(title)
+}
+
+/// Duration, resolution, codecs, bitrate and stream presence for a file. Probed
+/// once with ffprobe and cached in file_metadata afterwards.
+#[tauri::command]
+async fn get_video_metadata(file_id: String) -> Result<serde_json::Value, AppError> {
+    let meta = match db::get_file_metadata(&file_id)? {
+        Some(meta) => meta,
+        None => {
+            let path = db::get_file_path(&file_id)?
+                .ok_or_else(|| AppError::NotFound("File not found".to_string()))?;
+            let meta = tokio::task::spawn_blocking(move || media::probe_metadata(std::path::Path::new(&path)))
+                .await?
+                .map_err(AppError::Io)?;
+            db::set_file_metadata(&file_id, &meta)?;
+            meta
+        }
+    };
+    Ok(serde_json::json!({
+      "fileId": file_id,
+      "duration": meta.duration,
+      "width": meta.width,
+      "height": meta.height,
+      "videoCodec": meta.video_codec,
+      "audioCodec": meta.audio_codec,
+      "bitrate": meta.bitrate,
+      "frameRate": meta.frame_rate,
+      "hasVideo": meta.has_video,
+      "hasAudio": meta.has_audio
+    }))
+}
+
+#[tauri::command]
+async fn delete_file(id: String) -> Result<(), AppError> {
+    // try to remove the actual file if it exists
+    if let Ok(Some(path)) = db::get_file_path(&id) { let _ = std::fs::remove_file(path); }
+    // along with its thumbnail, storyboard frames and generated documents
+    let thumbs_dir = app_data_dir().join("thumbs");
+    let mut generated = vec![thumbs_dir.join(format!("{}.jpg", id)), thumbs_dir.join(format!("{}.png", id))];
+    generated.extend(db::list_artifacts(&id)?.into_iter().map(|a| std::path::PathBuf::from(a.path)));
+    for path in generated.iter().filter(|p| app_owned(p)) {
+        let _ = std::fs::remove_file(path);
+    }
+    let _ = std::fs::remove_dir_all(thumbs_dir.join(&id));
+    let _ = std::fs::remove_dir_all(app_data_dir().join("artifacts").join(&id));
+    db::delete_file(&id).map_err(AppError::from)
+}
+
+/// Moves a generated document into artifacts/<file_id>/ in the app data dir and
+/// returns its new path. Already there, it stays put.
+fn adopt_artifact(file_id: &str, path: &std::path::Path) -> Result<std::path::PathBuf, String> {
+    let name = path.file_name().ok_or_else(|| format!("Invalid artifact path: {}", path.display()))?;
+    let dir = app_data_dir().join("artifacts").join(file_id);
+    std::fs::create_dir_all(&dir).map_err(|e| e.to_string())?;
+    let dir = dir.canonicalize().unwrap_or(dir);
+    if path.parent().is_some_and(|p| p == dir) {
+        return Ok(path.to_path_buf());
+    }
+    let dest = dir.join(name);
+    // A rename can't cross drives; copy and remove instead
+    if std::fs::rename(path, &dest).is_err() {
+        std::fs::copy(path, &dest).map_err(|e| e.to_string())?;
+        let _ = std::fs::remove_file(path);
+    }
+    Ok(dest)
+}
+
+/// Entries under `data_dir` the library no longer refers to, as (path, is_dir):
+/// thumbs/<id>.jpg, thumbs/<id>.png and thumbs/<id>/ of removed files, and anything
+/// in artifacts/ without an artifact row. Nothing outside `data_dir` is looked at.
+fn orphan_paths(
+    data_dir: &std::path::Path,
+    ids: &std::collections::HashSet<String>,
+    artifacts: &std::collections::HashSet<std::path::PathBuf>,
+) -> Vec<(std::path::PathBuf, bool)> {
+    let mut orphans = Vec::new();
+    for e in std::fs::read_dir(data_dir.join("thumbs")).into_iter().flatten().flatten() {
+        let path = e.path();
+        let is_dir = e.file_type().is_ok_and(|t| t.is_dir());
+        let id = if is_dir { path.file_name() } else { path.file_stem() };
+        if !id.is_some_and(|id| ids.contains(id.to_string_lossy().as_ref())) {
+            orphans.push((path, is_dir));
+        }
+    }
+    for e in std::fs::read_dir(data_dir.join("artifacts")).into_iter().flatten().flatten() {
+        let dir = e.path();
+        if !ids.contains(e.file_name().to_string_lossy().as_ref()) {
+            orphans.push((dir, true));
+            continue;
+        }
+        for f in std::fs::read_dir(&dir).into_iter().flatten().flatten() {
+            let path = f.path();
+            if !artifacts.contains(&path.canonicalize().unwrap_or_else(|_| path.clone())) {
+                let is_dir = f.file_type().is_ok_and(|t| t.is_dir());
+                orphans.push((path, is_dir));
+            }
+        }
+    }
+    orphans
+}
+
+/// Deletes thumbnails and generated documents in the app data dir that no file or
+/// artifact refers to any more. Returns the count and bytes reclaimed.
+#[tauri::command]
+async fn cleanup_orphans() -> Result<serde_json::Value, AppError> {
+    let ids: std::collections::HashSet<String> = db::list_files(None)?.into_iter().map(|f| f.id).collect();
+    let artifacts: std::collections::HashSet<std::path::PathBuf> = db::list_artifact_paths()?
+        .into_iter()
+        .map(|p| { let p = std::path::PathBuf::from(p); p.canonicalize().unwrap_or(p) })
+        .collect();
+    let (removed, bytes) = tokio::task::spawn_blocking(move || {
+        let orphans = orphan_paths(&app_data_dir(), &ids, &artifacts);
+        let (mut removed, mut bytes) = (0u64, 0u64);
+        for (path, is_dir) in orphans {
+            let size = if is_dir { dir_size(&path) } else { std::fs::metadata(&path).map(|m| m.len()).unwrap_or(0) };
+            let res = if is_dir { std::fs::remove_dir_all(&path) } else { std::fs::remove_file(&path) };
+            match res {
+                Ok(()) => { removed += 1; bytes += size; }
+                Err(e) => println!("[Tauri] Warning: couldn't remove orphan {}: {}", path.display(), e),
+            }
+        }
+        (removed, bytes)
+    })
+    .await?;
+    Ok(serde_json::json!({ "removed": removed, "bytesReclaimed": bytes }))
+}
+
+/// Points a file at its new location after it was moved on disk, then refreshes
+/// the thumbnail. If the contents differ from what was there before, the cached
+/// transcript and metadata are dropped as well.
+#[tauri::command]
+async fn relocate_file(id: String, new_path: String) -> Result<String, AppError> {
+    let path = std::path::PathBuf::from(&new_path);
+    if !path.is_file() {
+        return Err(AppError::NotFound(format!("No file at {}", new_path)));
+    }
+    let previous_hash = db::get_file_hash(&id)?;
+    if !db::set_file_path(&id, &new_path)? {
+        return Err(AppError::NotFound("File not found".to_string()));
+    }
+    let content_hash = tokio::task::spawn_blocking(move || media::hash_file(&path))
+        .await?
+        .map_err(AppError::Io)?;
+    db::set_file_hash(&id, &content_hash)?;
+    if previous_hash.is_some_and(|h| h != content_hash) {
+        return invalidate_cache(id).await;
+    }
+    let thumb = generate_thumbnail(id.clone(), None, None).await?;
+    db::set_file_thumb(&id, &thumb)?;
+    Ok(thumb)
+}
+
+/// Library files whose path no longer exists, so the UI can offer to relocate or remove them.
+#[tauri::command]
+async fn verify_files() -> Result<Vec<serde_json::Value>, AppError> {
+    let rows = db::list_files(None)?;
+    let missing = tokio::task::spawn_blocking(move || {
+        rows.into_iter()
+            .filter(|r| !std::path::Path::new(&r.path).is_file())
+            .map(|r| serde_json::json!({ "fileId": r.id, "name": r.name, "path": r.path }))
+            .collect::<Vec<_>>()
+    })
+    .await?;
+    Ok(missing)
+}
+
+const MEDIA_EXTENSIONS: &[&str] = &[
+    "mp4", "mov", "mkv", "avi", "webm", "m4v", "wav", "mp3", "m4a", "aac", "flac", "ogg",
+];
+
+// file_id and ext come straight from the frontend and end up in a filesystem path;
+// only accept a UUID and a known media extension, and keep the result inside `dir`.
+fn media_upload_path(dir: &std::path::Path, file_id: &str, ext: &str) -> Result<(std::path::PathBuf, String), AppError> {
+    // parse_str also takes braced/urn forms; require the plain hyphenated one
+    let valid = uuid::Uuid::parse_str(file_id)
+        .map(|id| id.hyphenated().to_string().eq_ignore_ascii_case(file_id))
+        .unwrap_or(false);
+    if !valid {
+        return Err(AppError::Validation(format!("Invalid file id: {}", file_id)));
+    }
+    let ext = ext.trim().trim_start_matches('.').to_ascii_lowercase();
+    if !MEDIA_EXTENSIONS.contains(&ext.as_str()) {
+        return Err(AppError::Validation(format!("Unsupported file type: .{} (expected one of: {})", ext, MEDIA_EXTENSIONS.join(", "))));
+    }
+    let dir = dir.canonicalize()?;
+    let path = dir.join(format!("{}.{}", file_id, ext));
+    if path.parent() != Some(dir.as_path()) {
+        return Err(AppError::Validation("Refusing to write outside the app data directory".to_string()));
+    }
+    Ok((path, ext))
+}
+
+/// Saves in progress, keyed by file id, so cancel_upload can stop them.
+#[derive(Default)]
+struct PendingUploads(RunningTasks);
+
+const WRITE_CHUNK_BYTES: usize = 8 * 1024 * 1024;
+
+fn upload_cancelled() -> std::io::Error {
+    std::io::Error::new(std::io::ErrorKind::Interrupted, "Upload cancelled")
+}
+
+// Where an upload is written before it's renamed into place
+fn partial_upload_path(path: &std::path::Path) -> std::path::PathBuf {
+    let mut part = path.as_os_str().to_owned();
+    part.push(".part");
+    part.into()
+}
+
+// Write in chunks and fsync, so ffmpeg launched right after sees the complete file.
+// Stops with Interrupted once `cancel` fires.
+fn write_synced(path: &std::path::Path, bytes: &[u8], cancel: &CancellationToken) -> std::io::Result<()> {
+    use std::io::Write;
+    let mut file = std::fs::File::create(path)?;
+    for chunk in bytes.chunks(WRITE_CHUNK_BYTES) {
+        if cancel.is_cancelled() {
+            return Err(upload_cancelled());
+        }
+        file.write_all(chunk)?;
+    }
+    file.flush()?;
+    file.sync_all()
+}
+
+// Writes to `<path>.part` and renames it over `path` only once complete; the
+// partial file is removed on any failure or cancel
+fn write_upload(path: &std::path::Path, bytes: &[u8], cancel: &CancellationToken) -> std::io::Result<()> {
+    let part = partial_upload_path(path);
+    let result = write_synced(&part, bytes, cancel).and_then(|_| {
+        if cancel.is_cancelled() { Err(upload_cancelled()) } else { std::fs::rename(&part, path) }
+    });
+    if result.is_err() {
+        let _ = std::fs::remove_file(&part);
+    }
+    result
+}
+
+/// Stores an upload and returns `{fileId, path, duplicate}`. Bytes identical to a file
+/// already in the library aren't stored again; that file's id comes back with
+/// `duplicate: true`. Content that isn't a recognized audio/video container is
+/// rejected unless `skip_format_check` is set.
+#[tauri::command]
+async fn save_file_bytes(
+    app: tauri::AppHandle,
+    file_id: String,
+    ext: String,
+    bytes: Vec<u8>,
+    name: Option<String>,
+    skip_format_check: Option<bool>,
+) -> Result<serde_json::Value, AppError> {
+    // Determine app data directory (same as DB)
+    let dir = db::db_path()
+        .parent()
+        .unwrap_or(std::path::Path::new("."))
+        .to_path_buf();
+    let _ = std::fs::create_dir_all(&dir);
+    let (path, ext) = media_upload_path(&dir, &file_id, &ext)?;
+    if !skip_format_check.unwrap_or(false) {
+        match media::sniff_container(&bytes[..bytes.len().min(64)]) {
+            None => return Err(AppError::Validation(format!(
+                "This doesn't look like a .{} file (or any supported audio/video format); it may be corrupt",
+                ext
+            ))),
+            Some(found) if !media::container_matches(&ext, found) => return Err(AppError::Validation(format!(
+                "This looks like {} data, not a .{} file; it may be mislabeled",
+                found, ext
+            ))),
+            Some(_) => {}
+        }
+    }
+    // Re-uploading under an existing id only invalidates caches when the bytes differ
+    let known = db::get_file_path(&file_id)?.is_some();
+    let content_hash = media::hash_bytes(&bytes);
+    if !known {
+        if let Some((existing_id, existing_path)) = db::find_file_by_hash(&content_hash)? {
+            // Only a copy that is still on disk counts; otherwise store this one
+            if std::path::Path::new(&existing_path).exists() {
+                return Ok(serde_json::json!({ "fileId": existing_id, "path": existing_path, "duplicate": true }));
+            }
+        }
+    }
+    let changed = known && !matches!(media::hash_file(&path), Ok(old) if old == content_hash);
+    let uploads = app.state::<PendingUploads>();
+    let (upload_id, cancel) = uploads.0.register(&file_id);
+    let target = path.clone();
+    let written = tokio::task::spawn_blocking(move || write_upload(&target, &bytes, &cancel)).await;
+    uploads.0.finish(&file_id, upload_id);
+    written??;
+    if known {
+        let _ = db::set_file_hash(&file_id, &content_hash);
+        if changed {
+            if let Err(e) = invalidate_cache(file_id.clone()).await {
+                println!("[Tauri] cache invalidation for {} failed: {}", file_id, e);
+            }
+        }
+    } else {
+        let now = chrono::Utc::now().to_rfc3339();
+        let file_name = name.unwrap_or_else(|| format!("{}.{}", file_id, ext));
+        // The row only goes in once the file is complete; without a row the file would be orphaned
+        if let Err(e) = db::insert_file(&file_id, &file_name, &path.to_string_lossy(), &now) {
+            let _ = std::fs::remove_file(&path);
+            return Err(e.into());
+        }
+        let _ = db::set_file_hash(&file_id, &content_hash);
+        // Try to generate a thumbnail immediately (best effort). A scanner holding the new
+        // file can make the first ffmpeg run fail, so give it one more go shortly after.
+        let mut thumb = generate_thumbnail(file_id.clone(), None, None).await;
+        if let Err(e) = &thumb {
+            println!("[Tauri] thumbnail for new upload {} failed, retrying: {}", file_id, e);
+            tokio::time::sleep(Duration::from_millis(750)).await;
+            thumb = generate_thumbnail(file_id.clone(), None, None).await;
+        }
+        match thumb {
+            Ok(p) => { let _ = db::set_file_thumb(&file_id, &p); }
+            Err(e) => println!("[Tauri] no thumbnail for {} (antivirus or file locking may be interfering): {}", file_id, e),
+        }
+    }
+    let auto_transcribe = db::get_file_settings(&file_id).ok().flatten().is_some_and(|f| f.auto_transcribe);
+    if (settings::get().eager_transcription || auto_transcribe) && (!known || changed) {
+        spawn_eager_transcription(app.clone(), file_id.clone());
+    }
+    // Kick off the user's preset pipeline without holding up the upload
+    if let Some(pipeline) = settings::get().analyze_on_drop {
+        let fid = file_id.clone();
+        tauri::async_runtime::spawn(async move {
+            if let Err(e) = run_pipeline(app, fid, pipeline).await {
+                println!("[Tauri] analyze-on-drop pipeline failed: {}", e);
+            }
+        });
+    }
+    Ok(serde_json::json!({ "fileId": file_id, "path": path.to_string_lossy(), "duplicate": false }))
+}
+
+/// Stops an in-progress save_file_bytes for `file_id` (its partial file is removed
+/// and no library entry is created) and any analysis running for it. Returns
+/// whether there was anything to stop or clean up.
+#[tauri::command]
+async fn cancel_upload(app: tauri::AppHandle, file_id: String) -> Result<bool, AppError> {
+    if uuid::Uuid::parse_str(&file_id).is_err() {
+        return Err(AppError::Validation(format!("Invalid file id: {}", file_id)));
+    }
+    let stopped = app.state::<PendingUploads>().0.cancel(&file_id);
+    let analysis = app.state::<RunningTasks>().cancel(&file_id);
+    // Leftovers from a save that was interrupted before it could clean up
+    let dir = db::db_path().parent().unwrap_or(std::path::Path::new(".")).to_path_buf();
+    let prefix = format!("{}.", file_id);
+    let mut removed = 0;
+    if let Ok(entries) = std::fs::read_dir(&dir) {
+        for entry in entries.flatten() {
+            let name = entry.file_name().to_string_lossy().to_string();
+            if name.starts_with(&prefix) && name.ends_with(".part") && std::fs::remove_file(entry.path()).is_ok() {
+                removed += 1;
+            }
+        }
+    }
+    Ok(stopped || analysis || removed > 0)
+}
+
+/// Saves `start`..`end` of a file as a new library entry and returns its id and path.
+/// Timestamps take seconds or m:ss / h:mm:ss.
+#[tauri::command]
+async fn extract_clip(file_id: String, start: String, end: String) -> Result<serde_json::Value, AppError> {
+    let in_path = db::get_file_path(&file_id)?
+        .ok_or_else(|| AppError::NotFound("File not found".to_string()))?;
+    let start_secs = media::parse_timestamp(&start).ok_or_else(|| AppError::Validation(format!("Invalid start time: {}", start)))?;
+    let end_secs = media::parse_timestamp(&end).ok_or_else(|| AppError::Validation(format!("Invalid end time: {}", end)))?;
+    if end_secs <= start_secs {
+        return Err(AppError::Validation("The clip must end after it starts".to_string()));
+    }
+    let source = std::path::PathBuf::from(&in_path);
+    let duration = media::probe_duration(&source).map_err(AppError::Io)?;
+    if end_secs > duration + 0.5 {
+        return Err(AppError::Validation(format!("The video is only {} long", media::format_timestamp(duration))));
+    }
+    let end_secs = end_secs.min(duration);
+
+    let dir = db::db_path().parent().unwrap_or(std::path::Path::new(".")).to_path_buf();
+    let ext = source.extension().map(|e| e.to_string_lossy().to_string()).unwrap_or_else(|| "mp4".to_string());
+    let clip_id = uuid::Uuid::new_v4().to_string();
+    let (out_path, ext) = media_upload_path(&dir, &clip_id, &ext)?;
+    let out = out_path.clone();
+    tokio::task::spawn_blocking(move || media::cut_clip(&source, start_secs, end_secs, &out))
+        .await?
+        .map_err(AppError::Io)?;
+
+    let name = format!("Clip {}–{}.{}", media::format_timestamp(start_secs), media::format_timestamp(end_secs), ext);
+    let path = out_path.to_string_lossy().to_string();
+    let now = chrono::Utc::now().to_rfc3339();
+    if let Err(e) = db::insert_file(&clip_id, &name, &path, &now) {
+        let _ = std::fs::remove_file(&out_path);
+        return Err(e.into());
+    }
+    if let Ok(p) = generate_thumbnail(clip_id.clone(), None, None).await { let _ = db::set_file_thumb(&clip_id, &p); }
+    Ok(serde_json::json!({ "fileId": clip_id, "path": path }))
+}
+
+#[tauri::command]
+async fn read_file_bytes(file_id: String) -> Result<Vec<u8>, AppError> {
+    match db::get_file_path(&file_id)? {
+        Some(path) => Ok(std::fs::read(path)?),
+        None => Err(AppError::NotFound("File not found".to_string())),
+    }
+}
+
+const DEFAULT_THUMB_WIDTH: u32 = 320;
+
+/// Writes a file's thumbnail and returns its path. `at_seconds` picks the frame (past
+/// the end, the midpoint is used) and `width` its size; whichever is left out comes
+/// from the last thumbnail made for the file, else 1s and 320px.
+#[tauri::command]
+async fn generate_thumbnail(file_id: String, at_seconds: Option<f64>, width: Option<u32>) -> Result<String, AppError> {
+    if at_seconds.is_some_and(|at| !at.is_finite() || at < 0.0) {
+        return Err(AppError::Validation("at_seconds must be a non-negative number".to_string()));
+    }
+    let thumb = tokio::task::spawn_blocking(move || thumbnail_for(&file_id, at_seconds, width)).await??;
+    Ok(thumb)
+}
+
+/// Blocking body of `generate_thumbnail`; writes thumbs/<file_id>.jpg (a .png waveform for audio) via ffmpeg.
+/// The frame time and width used are stored so regenerating gives the same picture.
+fn thumbnail_for(file_id: &str, at: Option<f64>, width: Option<u32>) -> Result<String, AppError> {
+    // Find input path
+    let in_path = db::get_file_path(file_id)?
+        .ok_or_else(|| AppError::NotFound("File not found".to_string()))?;
+    let (stored_at, stored_width) = db::get_thumb_params(file_id).unwrap_or_default();
+    let width = width.or(stored_width).unwrap_or(DEFAULT_THUMB_WIDTH).clamp(32, 1920);
+
+    // Ensure output directory
+    let db_path = db::db_path();
+    let base_dir = db_path.parent().unwrap_or(std::path::Path::new(".")).to_path_buf();
+    let thumbs_dir = base_dir.join("thumbs");
+    std::fs::create_dir_all(&thumbs_dir)?;
+    let out_path = thumbs_dir.join(format!("{}.jpg", file_id));
+
+    let video = std::path::Path::new(&in_path);
+    if !media::has_video_stream(video).map_err(AppError::Io)? {
+        // Audio only: show the waveform instead of a frame
+        let wave_path = thumbs_dir.join(format!("{}.png", file_id));
+        media::render_waveform(video, width, width * 9 / 16, &wave_path).map_err(AppError::Io)?;
+        let _ = std::fs::remove_file(&out_path);
+        let _ = db::set_thumb_params(file_id, 0.0, width);
+        return Ok(wave_path.to_string_lossy().to_string());
+    }
+    let at = match (at.or(stored_at), media::probe_duration(video).ok()) {
+        (Some(at), Some(duration)) if at > duration => duration / 2.0,
+        (Some(at), _) => at,
+        // 1s skips black lead-ins, except for clips shorter than that
+        (None, Some(duration)) if duration < 1.0 => 0.0,
+        (None, _) => 1.0,
+    };
+    let used = match media::extract_frame(video, at, width, &out_path) {
+        Ok(()) => at,
+        Err(e) if at == 0.0 => return Err(AppError::Io(e)),
+        // Duration unknown or overstated; the first frame is better than nothing
+        Err(_) => {
+            media::extract_frame(video, 0.0, width, &out_path).map_err(AppError::Io)?;
+            0.0
+        }
+    };
+    let _ = db::set_thumb_params(file_id, used, width);
+
+    Ok(out_path.to_string_lossy().to_string())
+}
+
+/// Forgets the cached transcript, metadata and storyboard for a file whose
+/// contents changed, and regenerates its thumbnail. Returns the new thumbnail path.
+#[tauri::command]
+async fn invalidate_cache(file_id: String) -> Result<String, AppError> {
+    db::invalidate_file_cache(&file_id)?;
+    let db_path = db::db_path();
+    let base_dir = db_path.parent().unwrap_or(std::path::Path::new(".")).to_path_buf();
+    let _ = std::fs::remove_dir_all(base_dir.join("thumbs").join(&file_id));
+    let thumb = generate_thumbnail(file_id.clone(), None, None).await?;
+    db::set_file_thumb(&file_id, &thumb)?;
+    Ok(thumb)
+}
+
+const MAX_STORYBOARD_FRAMES: u32 = 48;
+
+/// Extracts `count` evenly spaced frames into thumbs/<file_id>/NN.jpg and returns
+/// their paths in playback order. Short videos get at most one frame per second.
+#[tauri::command]
+async fn generate_storyboard(file_id: String, count: u32) -> Result<Vec<String>, AppError> {
+    let in_path = db::get_file_path(&file_id)?
+        .ok_or_else(|| AppError::NotFound("File not found".to_string()))?;
+    let video = std::path::PathBuf::from(&in_path);
+    let count = count.clamp(1, MAX_STORYBOARD_FRAMES);
+
+    let db_path = db::db_path();
+    let base_dir = db_path.parent().unwrap_or(std::path::Path::new(".")).to_path_buf();
+    let frames_dir = base_dir.join("thumbs").join(&file_id);
+    // Drop frames from an earlier storyboard with a different count
+    let _ = std::fs::remove_dir_all(&frames_dir);
+
+    let frames = tokio::task::spawn_blocking(move || media::sample_frames(&video, count, 320, &frames_dir))
+        .await?
+        .map_err(AppError::Io)?;
+    Ok(frames.into_iter().map(|(_, p)| p.to_string_lossy().to_string()).collect())
+}
+
+// Each transcription runs ffmpeg and then occupies the transcription agent
+const BATCH_TRANSCRIBE_CONCURRENCY: usize = 2;
+
+/// Transcribes several files, a couple at a time; cached transcripts are reused.
+/// Emits "batch_transcription_progress" `{fileId, ok, error, done, total}` as each
+/// finishes and returns `{succeeded, failed: [{fileId, error}]}`.
+#[tauri::command]
+async fn transcribe_batch(app: tauri::AppHandle, file_ids: Vec<String>) -> Result<serde_json::Value, AppError> {
+    if !settings::get().agent_enabled("transcription") {
+        return Err(AppError::Validation(disabled_text(Task::Transcribe)));
+    }
+    let mut seen = std::collections::HashSet::new();
+    // Reversed so pop() takes them in the order given
+    let mut pending: Vec<String> = file_ids.into_iter().filter(|id| seen.insert(id.clone())).collect();
+    pending.reverse();
+    let total = pending.len();
+    let mut running = tokio::task::JoinSet::new();
+    let (mut done, mut succeeded) = (0usize, Vec::new());
+    let mut failed: Vec<serde_json::Value> = Vec::new();
+    loop {
+        while running.len() < BATCH_TRANSCRIBE_CONCURRENCY {
+            let Some(id) = pending.pop() else { break };
+            let app = app.clone();
+            running.spawn(async move {
+                let mut runner = TaskRunner::background(&id, Some(app));
+                let result = runner.transcribe().await.map(|_| ());
+                (id, result)
+            });
+        }
+        let Some(joined) = running.join_next().await else { break };
+        done += 1;
+        let (file_id, result) = match joined {
+            Ok((id, result)) => (Some(id), result),
+            Err(e) => (None, Err(e.to_string())),
+        };
+        let error = result.err();
+        match &error {
+            None => succeeded.extend(file_id.clone()),
+            Some(e) => failed.push(serde_json::json!({ "fileId": file_id, "error": e })),
+        }
+        let _ = app.emit("batch_transcription_progress", serde_json::json!({
+            "fileId": file_id, "ok": error.is_none(), "error": error, "done": done, "total": total,
+        }));
+    }
+    Ok(serde_json::json!({ "succeeded": succeeded, "failed": failed }))
+}
+
+#[tauri::command]
+async fn backfill_thumbnails(app: tauri::AppHandle) -> Result<usize, AppError> {
+    let rows = db::list_files(None)?;
+    // Skip files that already have a thumbnail or whose source is missing
+    let mut pending: Vec<String> = rows.into_iter()
+        .filter(|r| r.thumb_path.is_none() && std::path::Path::new(&r.path).exists())
+        .map(|r| r.id)
+        .collect();
+    let total = pending.len();
+    // Each ffmpeg run is independent, so keep one per core in flight
+    let limit = std::thread::available_parallelism().map(|n| n.get()).unwrap_or(2).min(8);
+    let mut running = tokio::task::JoinSet::new();
+    let (mut done, mut updated) = (0usize, 0usize);
+    let _ = app.emit("backfill_progress", serde_json::json!({ "done": 0, "total": total }));
+    loop {
+        while running.len() < limit {
+            let Some(id) = pending.pop() else { break };
+            running.spawn_blocking(move || { let res = thumbnail_for(&id, None, None); (id, res) });
+        }
+        let Some(joined) = running.join_next().await else { break };
+        done += 1;
+        // DB writes stay on this task, one at a time
+        if let Ok((id, Ok(p))) = joined {
+            if db::set_file_thumb(&id, &p).is_ok() { updated += 1; }
+        }
+        let _ = app.emit("backfill_progress", serde_json::json!({ "done": done, "total": total }));
+    }
+    Ok(updated)
 }
 
 #[cfg_attr(mobile, tauri::mobile_entry_point)]
 pub fn run() {
     db::init().expect("db init failed");
-    let handles = AgentHandles(Arc::new(Mutex::new(start_agents())));
     tauri::Builder::default()
         .plugin(tauri_plugin_fs::init())
         .plugin(tauri_plugin_log::Builder::default().build())
-        .manage(handles)
+        .setup(|app| {
+            // Agents start here so bundled builds can find the backend among the app's resources
+            if let Ok(dir) = app.path().resource_dir() {
+                agents::set_resource_dir(dir);
+            }
+            let handles = AgentHandles(Arc::new(Mutex::new(agents::start_agents())));
+            agents::spawn_supervisor(handles.0.clone());
+            app.manage(handles);
+            Ok(())
+        })
+        .manage(RunningTasks::default())
+        .manage(PendingUploads::default())
         .on_window_event(|app, event| {
             if let WindowEvent::CloseRequested { .. } = event { 
                 {
                     let arc = app.state::<AgentHandles>().0.clone();
                     let lock_result = arc.lock();
                     if let Ok(mut vec) = lock_result {
-                        for child in vec.iter_mut() {
-                            let _ = child.kill();
-                        }
-                        vec.clear();
+                        agents::shutdown(&mut vec, Duration::from_secs(2));
                     }
                 }
             }
@@ -733,9 +3218,9 @@ pub fn run() {
         .invoke_handler(tauri::generate_handler![
             save_message,
             get_messages,
+            get_conversation_view,
             send_message,
             get_temp_path,
-            upload_video_bytes,
             auth_callback,
             get_app_data_dir,
             register_file,
@@ -746,7 +3231,220 @@ pub fn run() {
             read_file_bytes
             ,generate_thumbnail
             ,backfill_thumbnails
+            ,run_pipeline
+            ,get_settings
+            ,set_settings
+            ,reset_settings
+            ,agent_status
+            ,reload_intent_keywords
+            ,search_messages
+            ,send_message_structured
+            ,cancel_task
+            ,generate_storyboard
+            ,get_video_metadata
+            ,delete_message
+            ,clear_messages
+            ,export_conversation
+            ,list_artifacts
+            ,reveal_in_folder
+            ,get_transcription_config
+            ,set_transcription_config
+            ,get_agent_log
+            ,invalidate_cache
+            ,library_stats
+            ,extract_clip
+            ,list_conversations
+            ,get_message
+            ,regenerate
+            ,add_tag
+            ,remove_tag
+            ,list_tags
+            ,relocate_file
+            ,verify_files
+            ,preview_intent
+            ,generate_title
+            ,compact_database
+            ,compare_files
+            ,get_transcript
+            ,get_transcript_segments
+            ,cancel_upload
+            ,get_file_settings
+            ,set_file_settings
+            ,transcribe_batch
+            ,diagnostics
+            ,cleanup_orphans
+            ,get_storage_dir
+            ,set_storage_dir
         ])
         .run(tauri::generate_context!())
         .expect("error while running tauri application");
-}
\ No newline at end of file
+}
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::HashSet;
+    use std::path::{Path, PathBuf};
+
+    // Fresh directory under the system temp dir; removed by the test that made it
+    fn scratch_dir() -> PathBuf {
+        let dir = std::env::temp_dir().join(format!("auralink-test-{}", uuid::Uuid::new_v4()));
+        std::fs::create_dir_all(&dir).unwrap();
+        dir.canonicalize().unwrap()
+    }
+
+    #[test]
+    fn artifact_paths_survive_friendly_sentence() {
+        for path in [
+            "/tmp/out/report.pdf",
+            "/tmp/my files/quarterly report.pdf",
+            "/tmp/deck (final).pptx",
+            "/tmp/100%20 done.pdf",
+            "/tmp/odd`name``.docx",
+            "/tmp/`leading and trailing`.pdf",
+        ] {
+            for label in ["PowerPoint", "PDF", "DOCX"] {
+                let reply = friendly_sentence(&format!("{} generated at {}", label, path));
+                assert_eq!(extract_artifact_paths(&reply), vec![path.to_string()], "{}", reply);
+            }
+        }
+    }
+
+    #[test]
+    fn artifact_paths_survive_lowercasing_that_changes_length() {
+        // "İ" is two bytes but lowercases to three, shifting every offset after it
+        let reply = friendly_sentence("PDF for \u{130}STANBUL generated at /tmp/\u{130}zmir.pdf");
+        assert_eq!(extract_artifact_paths(&reply), vec!["/tmp/\u{130}zmir.pdf".to_string()], "{}", reply);
+    }
+
+    #[test]
+    fn clamp_len_cuts_on_a_char_boundary() {
+        assert_eq!(clamp_len("short".to_string(), 10), "short");
+        assert_eq!(clamp_len("abcdef".to_string(), 3), "abc…");
+        // "é" is two bytes and "日" three; a cut inside either walks back before it
+        assert_eq!(clamp_len("caf\u{e9} au lait".to_string(), 4), "caf…");
+        assert_eq!(clamp_len("\u{65e5}\u{672c}\u{8a9e}".to_string(), 4), "\u{65e5}…");
+        assert_eq!(clamp_len("\u{65e5}\u{672c}".to_string(), 2), "…");
+    }
+
+    #[test]
+    fn percent_decode_leaves_malformed_escapes() {
+        assert_eq!(percent_decode("/a%20b%28c%29"), "/a b(c)");
+        assert_eq!(percent_decode("/100%"), "/100%");
+        assert_eq!(percent_decode("/x%zz"), "/x%zz");
+    }
+
+    fn touch(path: &Path) {
+        std::fs::create_dir_all(path.parent().unwrap()).unwrap();
+        std::fs::write(path, b"x").unwrap();
+    }
+
+    #[test]
+    fn orphan_sweep_keeps_to_the_app_data_dir() {
+        let root = scratch_dir();
+        let data = root.join("data");
+        let output = root.join("output");
+        let user_pdf = output.join("notes.pdf");
+        touch(&user_pdf);
+        touch(&data.join("thumbs").join("kept.jpg"));
+        touch(&data.join("thumbs").join("gone.jpg"));
+        touch(&data.join("thumbs").join("gone").join("01.jpg"));
+        let recorded = data.join("artifacts").join("kept").join("report.pdf");
+        touch(&recorded);
+        touch(&data.join("artifacts").join("kept").join("stale.pdf"));
+        touch(&data.join("artifacts").join("gone").join("deck.pptx"));
+
+        let ids: HashSet<String> = ["kept".to_string()].into();
+        let artifacts: HashSet<PathBuf> = [recorded.clone()].into();
+        let orphans: HashSet<PathBuf> = orphan_paths(&data, &ids, &artifacts).into_iter().map(|(p, _)| p).collect();
+        let expected: HashSet<PathBuf> = [
+            data.join("thumbs").join("gone.jpg"),
+            data.join("thumbs").join("gone"),
+            data.join("artifacts").join("kept").join("stale.pdf"),
+            data.join("artifacts").join("gone"),
+        ]
+        .into();
+        assert_eq!(orphans, expected);
+        assert!(!orphans.contains(&user_pdf));
+        assert!(user_pdf.exists());
+        let _ = std::fs::remove_dir_all(&root);
+    }
+
+    #[test]
+    fn upload_path_takes_a_plain_uuid_and_a_media_extension() {
+        let dir = scratch_dir();
+        let id = uuid::Uuid::new_v4().to_string();
+        let (path, ext) = media_upload_path(&dir, &id, " .MP4").unwrap();
+        assert_eq!(ext, "mp4");
+        assert_eq!(path, dir.join(format!("{}.mp4", id)));
+        // The check is case-insensitive, so an uppercased id is still a plain UUID
+        assert!(media_upload_path(&dir, &id.to_uppercase(), "wav").is_ok());
+
+        for bad in [
+            "../escape".to_string(),
+            "not-a-uuid".to_string(),
+            format!("{{{}}}", id),
+            format!("urn:uuid:{}", id),
+            id.replace('-', ""),
+            format!("../{}", id),
+            String::new(),
+        ] {
+            let err = media_upload_path(&dir, &bad, "mp4").unwrap_err();
+            assert!(matches!(err, AppError::Validation(ref m) if m.starts_with("Invalid file id")), "{}: {:?}", bad, err);
+        }
+        for bad in ["exe", ".txt", "mp4/../x", "", "mp4.exe"] {
+            let err = media_upload_path(&dir, &id, bad).unwrap_err();
+            assert!(matches!(err, AppError::Validation(ref m) if m.starts_with("Unsupported file type")), "{}: {:?}", bad, err);
+        }
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn upload_path_needs_an_existing_dir() {
+        let missing = std::env::temp_dir().join(format!("auralink-missing-{}", uuid::Uuid::new_v4()));
+        let id = uuid::Uuid::new_v4().to_string();
+        assert!(matches!(media_upload_path(&missing, &id, "mp4"), Err(AppError::NotFound(_))));
+    }
+
+    #[test]
+    fn thresholds_must_be_in_range_and_ordered() {
+        use settings::IntentMode::{Auto, Balanced, Careful};
+        let with = |mode, execute, clarify| settings::Settings {
+            intent_mode: mode,
+            auto_execute_threshold: execute,
+            clarify_threshold: clarify,
+            ..Default::default()
+        };
+        for ok in [
+            with(Balanced, None, None),
+            with(Auto, None, None),
+            with(Careful, None, None),
+            with(Balanced, Some(8), Some(8)),
+            with(Auto, Some(1), Some(1)),
+        ] {
+            assert!(check_thresholds(&ok).is_ok(), "{:?}", ok.intent_thresholds());
+        }
+        for bad in [
+            with(Balanced, Some(0), None),
+            with(Balanced, None, Some(0)),
+            with(Balanced, Some(11), None),
+            with(Balanced, Some(5), Some(8)),
+            // Auto acts from 5, so an explicit clarify of 8 sits above it
+            with(Auto, None, Some(8)),
+            with(Careful, Some(6), None),
+        ] {
+            assert!(matches!(check_thresholds(&bad), Err(AppError::Validation(_))), "{:?}", bad.intent_thresholds());
+        }
+    }
+
+    #[test]
+    fn analyze_on_drop_resolves_a_known_pipeline() {
+        let settings = settings::Settings { analyze_on_drop: Some("quick-look".to_string()), ..Default::default() };
+        let name = settings.analyze_on_drop.as_deref().unwrap();
+        let steps = pipeline_steps(&settings, name).unwrap();
+        let tasks: Vec<Option<Task>> = steps.iter().map(|s| Task::from_name(&s.intent)).collect();
+        assert_eq!(tasks, vec![Some(Task::Transcribe), Some(Task::Objects)]);
+
+        let err = pipeline_steps(&settings, "nightly").unwrap_err();
+        assert!(matches!(err, AppError::Validation(ref m) if m == "Unknown pipeline: nightly"), "{:?}", err);
+    }
+}
@@ -2,10 +2,6 @@
 #![cfg_attr(not(debug_assertions), windows_subsystem = "windows")]
 
 fn main() {
+  // Python agents are started (and stopped) by app_lib via agents::start_agents
   app_lib::run();
-  let _child = std::process::Command::new("python")
-    .args(["backend/mcp/transcription_server.py"])
-    .spawn()
-    .expect("failed to start transcription server");
-  // store handle and terminate on app exit (handled in app_lib)
 }
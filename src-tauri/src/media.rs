@@ -0,0 +1,440 @@
+use std::path::{Path, PathBuf};
+use sha2::{Digest, Sha256};
+use std::process::Command;
+use std::sync::OnceLock;
+
+static FFMPEG_CHECK: OnceLock<Result<(), String>> = OnceLock::new();
+
+fn tool_runs(tool: &str) -> bool {
+    Command::new(tool)
+        .arg("-version")
+        .output()
+        .map(|o| o.status.success())
+        .unwrap_or(false)
+}
+
+/// Checks once per process that ffmpeg and ffprobe can be launched, so video
+/// features fail fast with an actionable message instead of a raw OS error.
+pub fn ensure_ffmpeg_available() -> Result<(), String> {
+    FFMPEG_CHECK
+        .get_or_init(|| {
+            let missing: Vec<&str> = ["ffmpeg", "ffprobe"].into_iter().filter(|t| !tool_runs(t)).collect();
+            if missing.is_empty() {
+                Ok(())
+            } else {
+                Err(format!(
+                    "{} is required for video features but was not found on PATH; install it from https://ffmpeg.org and restart the app",
+                    missing.join(" and ")
+                ))
+            }
+        })
+        .clone()
+}
+
+/// Extracts a compact 16kHz mono WAV track from a video into a temp file.
+/// The caller is responsible for removing the returned file.
+pub fn extract_audio(video: &Path) -> Result<PathBuf, String> {
+    ensure_ffmpeg_available()?;
+    let out = std::env::temp_dir().join(format!("auralink-{}.wav", uuid::Uuid::new_v4()));
+    let output = Command::new("ffmpeg")
+        .args(["-y", "-i"])
+        .arg(video)
+        .args(["-vn", "-ac", "1", "-ar", "16000", "-f", "wav"])
+        .arg(&out)
+        .output()
+        .map_err(|e| format!("Failed to run ffmpeg: {}", e))?;
+
+    if !output.status.success() {
+        let _ = std::fs::remove_file(&out);
+        let mut msg = String::from("ffmpeg failed to extract audio");
+        if !output.stderr.is_empty() {
+            msg.push_str(": ");
+            msg.push_str(&String::from_utf8_lossy(&output.stderr));
+        }
+        return Err(msg);
+    }
+    Ok(out)
+}
+
+/// Container duration in seconds, via ffprobe.
+pub fn probe_duration(video: &Path) -> Result<f64, String> {
+    ensure_ffmpeg_available()?;
+    let output = Command::new("ffprobe")
+        .args(["-v", "error", "-show_entries", "format=duration", "-of", "default=noprint_wrappers=1:nokey=1"])
+        .arg(video)
+        .output()
+        .map_err(|e| format!("Failed to run ffprobe: {}", e))?;
+    if !output.status.success() {
+        return Err(format!("ffprobe failed: {}", String::from_utf8_lossy(&output.stderr).trim()));
+    }
+    String::from_utf8_lossy(&output.stdout)
+        .trim()
+        .parse::<f64>()
+        .ok()
+        .filter(|d| d.is_finite() && *d > 0.0)
+        .ok_or_else(|| "ffprobe reported no duration".to_string())
+}
+
+/// Whether the file has at least one video stream (audio-only files don't).
+pub fn has_video_stream(path: &Path) -> Result<bool, String> {
+    ensure_ffmpeg_available()?;
+    let output = Command::new("ffprobe")
+        .args(["-v", "error", "-select_streams", "v", "-show_entries", "stream=codec_type", "-of", "csv=p=0"])
+        .arg(path)
+        .output()
+        .map_err(|e| format!("Failed to run ffprobe: {}", e))?;
+    if !output.status.success() {
+        return Err(format!("ffprobe failed: {}", String::from_utf8_lossy(&output.stderr).trim()));
+    }
+    Ok(String::from_utf8_lossy(&output.stdout).lines().any(|l| l.trim() == "video"))
+}
+
+/// Writes a single frame at `at_secs`, scaled to `width` pixels wide, as a JPEG.
+pub fn extract_frame(video: &Path, at_secs: f64, width: u32, out: &Path) -> Result<(), String> {
+    ensure_ffmpeg_available()?;
+    // A stale file would make a failed seek look like success
+    let _ = std::fs::remove_file(out);
+    // -ss before -i seeks on keyframes first, which is much faster on long videos
+    let output = Command::new("ffmpeg")
+        .args(["-y", "-ss", &format!("{:.3}", at_secs), "-i"])
+        .arg(video)
+        .args(["-frames:v", "1", "-q:v", "2", "-vf", &format!("scale={}:-1", width)])
+        .arg(out)
+        .output()
+        .map_err(|e| format!("Failed to run ffmpeg: {}", e))?;
+    if !output.status.success() || !out.exists() {
+        let mut msg = format!("ffmpeg failed to extract a frame at {:.1}s", at_secs);
+        if !output.stderr.is_empty() {
+            msg.push_str(": ");
+            msg.push_str(&String::from_utf8_lossy(&output.stderr));
+        }
+        return Err(msg);
+    }
+    Ok(())
+}
+
+/// Draws the whole track's waveform as a `width`x`height` PNG, for files with no
+/// video frame to show. Channels are mixed down first so stereo and mono files
+/// render the same single wave.
+pub fn render_waveform(audio: &Path, width: u32, height: u32, out: &Path) -> Result<(), String> {
+    ensure_ffmpeg_available()?;
+    let _ = std::fs::remove_file(out);
+    let filter = format!("[0:a:0]aformat=channel_layouts=mono,showwavespic=s={}x{}:colors=0x6366f1[w]", width, height);
+    let output = Command::new("ffmpeg")
+        .args(["-y", "-i"])
+        .arg(audio)
+        .args(["-filter_complex", &filter, "-map", "[w]", "-frames:v", "1"])
+        .arg(out)
+        .output()
+        .map_err(|e| format!("Failed to run ffmpeg: {}", e))?;
+    if !output.status.success() || !out.exists() {
+        let mut msg = "ffmpeg failed to render a waveform".to_string();
+        if !output.stderr.is_empty() {
+            msg.push_str(": ");
+            msg.push_str(&String::from_utf8_lossy(&output.stderr));
+        }
+        return Err(msg);
+    }
+    Ok(())
+}
+
+#[derive(Clone, Debug, Default)]
+pub struct VideoMetadata {
+    pub duration: Option<f64>,
+    pub width: Option<i64>,
+    pub height: Option<i64>,
+    pub video_codec: Option<String>,
+    pub audio_codec: Option<String>,
+    pub bitrate: Option<i64>,
+    pub frame_rate: Option<f64>,
+    pub has_video: bool,
+    pub has_audio: bool,
+}
+
+// ffprobe reports most numbers as strings ("12.345"), rates as fractions ("30000/1001")
+fn num(v: &serde_json::Value) -> Option<f64> {
+    match v {
+        serde_json::Value::Number(n) => n.as_f64(),
+        serde_json::Value::String(s) => match s.split_once('/') {
+            Some((a, b)) => {
+                let (a, b) = (a.parse::<f64>().ok()?, b.parse::<f64>().ok()?);
+                if b == 0.0 { None } else { Some(a / b) }
+            }
+            None => s.parse().ok(),
+        },
+        _ => None,
+    }
+    .filter(|n: &f64| n.is_finite() && *n > 0.0)
+}
+
+/// Duration, dimensions, codecs, bitrate and frame rate of the first video and
+/// audio streams, via `ffprobe -print_format json`.
+pub fn probe_metadata(path: &Path) -> Result<VideoMetadata, String> {
+    ensure_ffmpeg_available()?;
+    let output = Command::new("ffprobe")
+        .args(["-v", "quiet", "-print_format", "json", "-show_format", "-show_streams"])
+        .arg(path)
+        .output()
+        .map_err(|e| format!("Failed to run ffprobe: {}", e))?;
+    if !output.status.success() {
+        return Err(format!("ffprobe could not read {}", path.to_string_lossy()));
+    }
+    let json: serde_json::Value = serde_json::from_slice(&output.stdout).map_err(|e| e.to_string())?;
+    let streams = json["streams"].as_array().cloned().unwrap_or_default();
+    let video = streams.iter().find(|s| s["codec_type"] == "video");
+    let audio = streams.iter().find(|s| s["codec_type"] == "audio");
+    let format = &json["format"];
+    Ok(VideoMetadata {
+        duration: num(&format["duration"]).or_else(|| video.and_then(|v| num(&v["duration"]))),
+        width: video.and_then(|v| v["width"].as_i64()),
+        height: video.and_then(|v| v["height"].as_i64()),
+        video_codec: video.and_then(|v| v["codec_name"].as_str()).map(str::to_string),
+        audio_codec: audio.and_then(|a| a["codec_name"].as_str()).map(str::to_string),
+        bitrate: num(&format["bit_rate"]).map(|b| b as i64),
+        frame_rate: video.and_then(|v| num(&v["avg_frame_rate"]).or_else(|| num(&v["r_frame_rate"]))),
+        has_video: video.is_some(),
+        has_audio: audio.is_some(),
+    })
+}
+
+/// Extracts up to `count` evenly spaced frames into `dir` as NN.jpg and returns
+/// (seconds, path) pairs in playback order. Short videos get at most one frame
+/// per second.
+pub fn sample_frames(video: &Path, count: u32, width: u32, dir: &Path) -> Result<Vec<(f64, PathBuf)>, String> {
+    if !has_video_stream(video)? {
+        return Err("no video stream in this file (audio only)".to_string());
+    }
+    let duration = probe_duration(video)?;
+    let count = count.max(1).min((duration.floor() as u32).max(1));
+    std::fs::create_dir_all(dir).map_err(|e| e.to_string())?;
+    let step = duration / count as f64;
+    let mut frames = Vec::with_capacity(count as usize);
+    for i in 0..count {
+        // Sample the middle of each slice so the first frame isn't a black lead-in
+        let at = step * (i as f64 + 0.5);
+        let out = dir.join(format!("{:02}.jpg", i + 1));
+        extract_frame(video, at, width, &out)?;
+        frames.push((at, out));
+    }
+    Ok(frames)
+}
+
+/// Parses "90", "90.5", "1:30" or "0:01:30" into seconds.
+pub fn parse_timestamp(text: &str) -> Option<f64> {
+    let fields: Vec<&str> = text.trim().split(':').collect();
+    if fields.len() > 3 || fields.iter().any(|f| f.is_empty()) {
+        return None;
+    }
+    let (last, rest) = fields.split_last()?;
+    let secs: f64 = last.parse().ok().filter(|s: &f64| s.is_finite() && *s >= 0.0)?;
+    // Below the leading field, seconds and minutes must stay under 60
+    if !rest.is_empty() && secs >= 60.0 {
+        return None;
+    }
+    let mut total = secs;
+    for (i, field) in rest.iter().rev().enumerate() {
+        let n: u64 = field.parse().ok()?;
+        if i == 0 && rest.len() == 2 && n >= 60 {
+            return None;
+        }
+        total += n as f64 * 60f64.powi(i as i32 + 1);
+    }
+    Some(total)
+}
+
+/// Copies `start..end` seconds of `input` into `out`. Stream copy is tried first;
+/// since it can only cut on keyframes, a result that doesn't probe or whose length
+/// is off by more than a second is redone with a re-encode.
+pub fn cut_clip(input: &Path, start: f64, end: f64, out: &Path) -> Result<(), String> {
+    ensure_ffmpeg_available()?;
+    let run = |codec_args: &[&str]| -> Result<(), String> {
+        let _ = std::fs::remove_file(out);
+        let output = Command::new("ffmpeg")
+            .args(["-y", "-ss", &format!("{:.3}", start), "-to", &format!("{:.3}", end), "-i"])
+            .arg(input)
+            .args(codec_args)
+            .arg(out)
+            .output()
+            .map_err(|e| format!("Failed to run ffmpeg: {}", e))?;
+        if !output.status.success() || !out.exists() {
+            let mut msg = "ffmpeg failed to cut the clip".to_string();
+            if !output.stderr.is_empty() {
+                msg.push_str(": ");
+                msg.push_str(&String::from_utf8_lossy(&output.stderr));
+            }
+            return Err(msg);
+        }
+        Ok(())
+    };
+    cut_with_fallback(run, || probe_duration(out), end - start).inspect_err(|_| {
+        let _ = std::fs::remove_file(out);
+    })
+}
+
+// Stream copy first, re-encoding when it fails or lands more than a second off `wanted`
+fn cut_with_fallback(
+    mut run: impl FnMut(&[&str]) -> Result<(), String>,
+    probe: impl FnOnce() -> Result<f64, String>,
+    wanted: f64,
+) -> Result<(), String> {
+    let copied = run(&["-c", "copy", "-avoid_negative_ts", "make_zero"])
+        .and_then(|_| probe())
+        .is_ok_and(|d| (d - wanted).abs() <= 1.0);
+    if copied {
+        return Ok(());
+    }
+    run(&["-c:v", "libx264", "-preset", "veryfast", "-c:a", "aac"])
+}
+
+/// m:ss, or h:mm:ss past the hour
+pub fn format_timestamp(secs: f64) -> String {
+    let total = secs.max(0.0).round() as u64;
+    let (h, m, s) = (total / 3600, (total % 3600) / 60, total % 60);
+    if h > 0 { format!("{}:{:02}:{:02}", h, m, s) } else { format!("{}:{:02}", m, s) }
+}
+
+/// Hex SHA-256 of a file, streamed so large videos aren't loaded into memory.
+pub fn hash_file(path: &Path) -> Result<String, String> {
+    let mut file = std::fs::File::open(path).map_err(|e| e.to_string())?;
+    let mut hasher = Sha256::new();
+    std::io::copy(&mut file, &mut hasher).map_err(|e| e.to_string())?;
+    Ok(format!("{:x}", hasher.finalize()))
+}
+
+/// Hex SHA-256 of an in-memory buffer, comparable with `hash_file`.
+pub fn hash_bytes(bytes: &[u8]) -> String {
+    format!("{:x}", Sha256::digest(bytes))
+}
+
+/// Container family recognized from a file's first bytes: "mp4" (also MOV/M4A),
+/// "matroska" (MKV/WebM), "avi", "wav", "mp3", "aac", "flac" or "ogg".
+pub fn sniff_container(head: &[u8]) -> Option<&'static str> {
+    let at = |offset: usize, magic: &[u8]| head.get(offset..offset + magic.len()) == Some(magic);
+    if at(4, b"ftyp") || at(4, b"moov") || at(4, b"mdat") || at(4, b"wide") || at(4, b"free") {
+        return Some("mp4");
+    }
+    if at(0, &[0x1A, 0x45, 0xDF, 0xA3]) {
+        return Some("matroska");
+    }
+    if at(0, b"RIFF") && at(8, b"AVI ") {
+        return Some("avi");
+    }
+    if at(0, b"RIFF") && at(8, b"WAVE") {
+        return Some("wav");
+    }
+    if at(0, b"fLaC") {
+        return Some("flac");
+    }
+    if at(0, b"OggS") {
+        return Some("ogg");
+    }
+    if at(0, b"ID3") {
+        return Some("mp3");
+    }
+    // Frame sync: 12 bits set with layer 00 is ADTS AAC, any other layer is MPEG audio
+    match head {
+        [0xFF, b, ..] if b & 0xF6 == 0xF0 => Some("aac"),
+        [0xFF, b, ..] if b & 0xE0 == 0xE0 && b & 0x06 != 0 => Some("mp3"),
+        _ => None,
+    }
+}
+
+/// Whether a file named `.{ext}` may hold the `container` sniff_container found.
+pub fn container_matches(ext: &str, container: &str) -> bool {
+    let expected: &[&str] = match ext {
+        "mp4" | "mov" | "m4v" | "m4a" => &["mp4"],
+        "mkv" | "webm" => &["matroska"],
+        "avi" => &["avi"],
+        "wav" => &["wav"],
+        "mp3" => &["mp3"],
+        // Raw ADTS or AAC in an MP4 box; an ID3 tag (sniffed as mp3) may come first
+        "aac" => &["aac", "mp4", "mp3"],
+        "flac" => &["flac", "mp3"],
+        "ogg" => &["ogg"],
+        _ => &[],
+    };
+    expected.contains(&container)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::cell::RefCell;
+
+    #[test]
+    fn timestamps_parse_as_seconds() {
+        assert_eq!(parse_timestamp("90"), Some(90.0));
+        assert_eq!(parse_timestamp(" 2.5 "), Some(2.5));
+        assert_eq!(parse_timestamp("1:30"), Some(90.0));
+        assert_eq!(parse_timestamp("75:00"), Some(4500.0));
+        assert_eq!(parse_timestamp("1:02:03.5"), Some(3723.5));
+        assert_eq!(parse_timestamp("0:00"), Some(0.0));
+    }
+
+    #[test]
+    fn malformed_timestamps_are_rejected() {
+        for bad in ["", "abc", "1:", ":30", "1::2", "1:60", "1:60:00", "1:2:3:4", "-5", "1:-5", "inf", "NaN", "1.5:30"] {
+            assert_eq!(parse_timestamp(bad), None, "{:?}", bad);
+        }
+        assert_eq!(format_timestamp(parse_timestamp("1:02:03").unwrap()), "1:02:03");
+    }
+
+    // Stands in for ffmpeg, noting each attempt as "copy" or "encode"
+    fn fake_ffmpeg(calls: &RefCell<Vec<&'static str>>, copy_ok: bool, encode_ok: bool) -> impl FnMut(&[&str]) -> Result<(), String> + '_ {
+        move |args: &[&str]| {
+            let copy = args.contains(&"copy");
+            calls.borrow_mut().push(if copy { "copy" } else { "encode" });
+            if (copy && copy_ok) || (!copy && encode_ok) { Ok(()) } else { Err("ffmpeg failed to cut the clip".to_string()) }
+        }
+    }
+
+    #[test]
+    fn clip_keeps_a_stream_copy_of_the_right_length() {
+        let calls = RefCell::new(Vec::new());
+        assert!(cut_with_fallback(fake_ffmpeg(&calls, true, true), || Ok(10.4), 10.0).is_ok());
+        assert_eq!(*calls.borrow(), vec!["copy"]);
+    }
+
+    #[test]
+    fn clip_is_reencoded_when_the_copy_is_off_or_fails() {
+        // Keyframe cut landed two seconds early
+        let calls = RefCell::new(Vec::new());
+        assert!(cut_with_fallback(fake_ffmpeg(&calls, true, true), || Ok(8.0), 10.0).is_ok());
+        assert_eq!(*calls.borrow(), vec!["copy", "encode"]);
+
+        // Copy produced something ffprobe can't read
+        let calls = RefCell::new(Vec::new());
+        assert!(cut_with_fallback(fake_ffmpeg(&calls, true, true), || Err("no duration".to_string()), 10.0).is_ok());
+        assert_eq!(*calls.borrow(), vec!["copy", "encode"]);
+
+        let calls = RefCell::new(Vec::new());
+        assert!(cut_with_fallback(fake_ffmpeg(&calls, false, true), || panic!("nothing to probe"), 10.0).is_ok());
+        assert_eq!(*calls.borrow(), vec!["copy", "encode"]);
+
+        let calls = RefCell::new(Vec::new());
+        assert!(cut_with_fallback(fake_ffmpeg(&calls, false, false), || Ok(10.0), 10.0).is_err());
+    }
+
+    #[test]
+    fn sniffed_container_must_fit_the_extension() {
+        let mut mp4 = vec![0, 0, 0, 0x20];
+        mp4.extend_from_slice(b"ftypisom");
+        assert_eq!(sniff_container(&mp4), Some("mp4"));
+        assert!(container_matches("mov", "mp4"));
+        assert!(container_matches("m4a", "mp4"));
+        assert!(!container_matches("mkv", "mp4"));
+
+        let wav = b"RIFF\x24\x00\x00\x00WAVEfmt ";
+        assert_eq!(sniff_container(wav), Some("wav"));
+        assert!(container_matches("wav", "wav"));
+        assert!(!container_matches("avi", "wav"));
+        assert!(!container_matches("mp4", "wav"));
+
+        // A tagged AAC stream starts with ID3 like an MP3 does
+        assert_eq!(sniff_container(b"ID3\x04\x00"), Some("mp3"));
+        assert!(container_matches("aac", "mp3"));
+        assert!(!container_matches("ogg", "mp3"));
+        assert!(!container_matches("txt", "mp3"));
+        assert_eq!(sniff_container(b"hello world"), None);
+    }
+}
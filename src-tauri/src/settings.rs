@@ -0,0 +1,156 @@
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::path::PathBuf;
+use std::sync::{Mutex, OnceLock};
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct PipelineStep {
+    pub intent: String,
+    #[serde(default)]
+    pub params: serde_json::Map<String, serde_json::Value>,
+}
+
+/// How readily chat requests run without asking first.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum IntentMode {
+    /// Act on the best guess; only clarify very weak requests
+    Auto,
+    #[default]
+    Balanced,
+    /// Confirm anything short of an explicit request
+    Careful,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Settings {
+    // Named, ordered lists of intents that can be run in one go
+    pub pipelines: BTreeMap<String, Vec<PipelineStep>>,
+    // Pipeline to run automatically right after a file is dropped in
+    pub analyze_on_drop: Option<String>,
+    // Where generated documents are expected to land; used to resolve relative paths
+    pub output_dir: Option<String>,
+    // Frames sampled across the video for object/graph detection
+    pub vision_frames: u32,
+    // Agents can be switched off to save memory; their features then report as disabled
+    pub enable_transcription: bool,
+    pub enable_vision: bool,
+    pub enable_generation: bool,
+    // Whisper model size; None falls back to AURALINK_WHISPER_MODEL, then "base"
+    pub whisper_model: Option<String>,
+    // Chat reply length: total budget shared by the bullets, and the floor per bullet
+    pub response_max_chars: usize,
+    pub response_min_item_chars: usize,
+    // Skip truncation entirely
+    pub verbose: bool,
+    // Transcribe right after upload instead of on the first request that needs it
+    pub eager_transcription: bool,
+    // Auto-execute vs clarify; the explicit thresholds (1-10) override the mode's
+    pub intent_mode: IntentMode,
+    pub auto_execute_threshold: Option<u8>,
+    pub clarify_threshold: Option<u8>,
+    // Library location (database, media, thumbnails); None is the per-user data dir.
+    // Changed only through set_storage_dir, which moves the data along with it
+    pub storage_dir: Option<String>,
+}
+
+impl Default for Settings {
+    fn default() -> Self {
+        let step = |intent: &str| PipelineStep { intent: intent.to_string(), params: Default::default() };
+        let mut pipelines = BTreeMap::new();
+        pipelines.insert("quick-look".to_string(), vec![step("transcribe"), step("objects")]);
+        pipelines.insert("full-report".to_string(), vec![step("transcribe"), step("objects"), step("graphs"), step("summary_pdf")]);
+        Self {
+            pipelines,
+            analyze_on_drop: None,
+            output_dir: None,
+            vision_frames: 4,
+            enable_transcription: true,
+            enable_vision: true,
+            enable_generation: true,
+            whisper_model: None,
+            response_max_chars: 600,
+            response_min_item_chars: 140,
+            verbose: false,
+            eager_transcription: false,
+            intent_mode: IntentMode::Balanced,
+            auto_execute_threshold: None,
+            clarify_threshold: None,
+            storage_dir: None,
+        }
+    }
+}
+
+impl Settings {
+    /// Whether the named agent ("transcription", "vision", "generation") is enabled.
+    pub fn agent_enabled(&self, agent: &str) -> bool {
+        match agent {
+            "transcription" => self.enable_transcription,
+            "vision" => self.enable_vision,
+            "generation" => self.enable_generation,
+            _ => true,
+        }
+    }
+
+    /// Routing thresholds for the chosen mode, with any explicit overrides applied.
+    pub fn intent_thresholds(&self) -> crate::intent::Thresholds {
+        use crate::intent::Thresholds;
+        let mode = match self.intent_mode {
+            IntentMode::Auto => Thresholds { execute: 5, clarify: 3 },
+            IntentMode::Balanced => Thresholds::default(),
+            IntentMode::Careful => Thresholds { execute: 9, clarify: 9 },
+        };
+        Thresholds {
+            execute: self.auto_execute_threshold.unwrap_or(mode.execute),
+            clarify: self.clarify_threshold.unwrap_or(mode.clarify),
+        }
+    }
+
+    /// The Whisper model the transcription agent should load.
+    pub fn whisper_model(&self) -> String {
+        self.whisper_model
+            .clone()
+            .or_else(|| std::env::var("AURALINK_WHISPER_MODEL").ok())
+            .map(|m| m.trim().to_string())
+            .filter(|m| !m.is_empty())
+            .unwrap_or_else(|| "base".to_string())
+    }
+}
+
+static SETTINGS: OnceLock<Mutex<Settings>> = OnceLock::new();
+
+// Always in the default data dir, since it records where the rest of the library is
+pub fn settings_path() -> PathBuf {
+    crate::db::default_data_dir().join("settings.json")
+}
+
+fn read_from_disk() -> Settings {
+    std::fs::read_to_string(settings_path())
+        .ok()
+        .and_then(|s| serde_json::from_str(&s).ok())
+        .unwrap_or_default()
+}
+
+fn cell() -> &'static Mutex<Settings> {
+    SETTINGS.get_or_init(|| Mutex::new(read_from_disk()))
+}
+
+pub fn get() -> Settings {
+    cell().lock().map(|s| s.clone()).unwrap_or_default()
+}
+
+pub fn save(settings: Settings) -> Result<Settings, String> {
+    let path = settings_path();
+    if let Some(dir) = path.parent() { let _ = std::fs::create_dir_all(dir); }
+    let json = serde_json::to_string_pretty(&settings).map_err(|e| e.to_string())?;
+    std::fs::write(&path, json).map_err(|e| e.to_string())?;
+    if let Ok(mut guard) = cell().lock() { *guard = settings.clone(); }
+    Ok(settings)
+}
+
+/// Restores defaults on disk and in memory. The DB and stored media are untouched,
+/// and so is where they're stored.
+pub fn reset() -> Result<Settings, String> {
+    save(Settings { storage_dir: get().storage_dir, ..Settings::default() })
+}
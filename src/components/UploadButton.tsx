@@ -52,7 +52,7 @@ const UploadDropzone = () => {
       const uint8Array = new Uint8Array(arrayBuffer);
       
       const ext = (file.name.split('.').pop() || 'mp4');
-      await invoke<string>('save_file_bytes', {
+      const saved = await invoke<{ fileId: string; path: string; duplicate: boolean }>('save_file_bytes', {
         fileId,
         ext,
         bytes: Array.from(uint8Array),
@@ -62,15 +62,21 @@ const UploadDropzone = () => {
       clearInterval(progressInterval);
       setUploadProgress(100);
 
-      // Navigate to file view
-      router.push(`/dashboard/${fileId}`);
+      // Navigate to file view (the existing entry when this file was already uploaded)
+      router.push(`/dashboard/${saved.fileId}`);
 
-      toast({ title: 'Success', description: 'File saved locally' });
+      toast({
+        title: 'Success',
+        description: saved.duplicate ? 'This file is already in your library' : 'File saved locally',
+      });
     } catch (error) {
       clearInterval(progressInterval);
       toast({
         title: 'Error',
-        description: error instanceof Error ? error.message : 'Upload failed',
+        // Tauri commands reject with { kind, message }
+        description: error instanceof Error
+          ? error.message
+          : (error as { message?: string } | null)?.message ?? 'Upload failed',
         variant: 'destructive',
       });
     } finally {